@@ -1,43 +1,71 @@
-use crate::{core::Token, semantics::utils::Type};
+use crate::core::{TextSpan, Token};
 
 pub type AstNodePtr = Box<AstNode>;
 
+#[derive(Debug, Clone)]
 pub enum AstNode {
     Program {
         statements: Vec<AstNode>,
+        span: TextSpan,
     },
     VarDec {
         identifier: Token,
         var_type: Token,
         expression: AstNodePtr,
+        span: TextSpan,
+    },
+    /// A `const`, whose initialiser is worked out at compile time
+    ConstDec {
+        identifier: Token,
+        const_type: Token,
+        expression: AstNodePtr,
+        span: TextSpan,
     },
     Block {
         statements: Vec<AstNode>,
+        span: TextSpan,
     },
     Expression {
         casted_type: Option<Token>,
         expr: AstNodePtr,
+        span: TextSpan,
     },
     SubExpression {
         bin_op: AstNodePtr,
+        span: TextSpan,
     },
     UnaryOp {
         operator: Token,
         expr: AstNodePtr,
+        span: TextSpan,
     },
     BinOp {
         left: AstNodePtr,
         operator: Token,
         right: AstNodePtr,
+        span: TextSpan,
+    },
+    /// `if condition then if_true else if_false`, as an expression
+    Conditional {
+        condition: AstNodePtr,
+        if_true: AstNodePtr,
+        if_false: AstNodePtr,
+        span: TextSpan,
+    },
+    PadWidth {
+        span: TextSpan,
     },
-    PadWidth,
     PadRandI {
         upper_bound: AstNodePtr,
+        span: TextSpan,
+    },
+    PadHeight {
+        span: TextSpan,
     },
-    PadHeight,
     PadRead {
         x: AstNodePtr,
         y: AstNodePtr,
+        span: TextSpan,
     },
     IntLiteral(Token),
     FloatLiteral(Token),
@@ -46,12 +74,21 @@ pub enum AstNode {
     FunctionCall {
         identifier: Token,
         args: Vec<AstNode>,
+        span: TextSpan,
     },
     Delay {
         expression: AstNodePtr,
+        span: TextSpan,
     },
     Return {
         expression: AstNodePtr,
+        span: TextSpan,
+    },
+    Break {
+        span: TextSpan,
+    },
+    Continue {
+        span: TextSpan,
     },
     PadWriteBox {
         loc_x: AstNodePtr,
@@ -59,11 +96,13 @@ pub enum AstNode {
         width: AstNodePtr,
         height: AstNodePtr,
         colour: AstNodePtr,
+        span: TextSpan,
     },
     PadWrite {
         loc_x: AstNodePtr,
         loc_y: AstNodePtr,
         colour: AstNodePtr,
+        span: TextSpan,
     },
     Identifier {
         token: Token,
@@ -72,50 +111,159 @@ pub enum AstNode {
         condition: AstNodePtr,
         if_true: AstNodePtr,
         if_false: Option<AstNodePtr>,
+        span: TextSpan,
+    },
+    Match {
+        scrutinee: AstNodePtr,
+        /// The literal each arm is taken for, with the block it runs
+        arms: Vec<(AstNode, AstNode)>,
+        /// The block run when no arm matches, written `_ => { ... }`
+        default: Option<AstNodePtr>,
+        span: TextSpan,
     },
     For {
         initializer: Option<AstNodePtr>,
         condition: AstNodePtr,
         increment: Option<AstNodePtr>,
         body: AstNodePtr,
+        span: TextSpan,
     },
     While {
         condition: AstNodePtr,
         body: AstNodePtr,
+        span: TextSpan,
     },
     FormalParam {
         identifier: Token,
         param_type: Token,
-        length: Option<Token>,
+        /// The sizes of an array parameter, outermost first, each an int
+        /// literal or the name of a constant
+        dimensions: Vec<Token>,
+        span: TextSpan,
     },
     FunctionDecl {
         identifier: Token,
         params: Vec<AstNode>,
-        return_type: Type,
+        return_type: Token,
+        /// The sizes of an array return type, outermost first, each an int
+        /// literal or the name of a constant
+        return_dimensions: Vec<Token>,
         block: AstNodePtr,
+        span: TextSpan,
     },
     Print {
         expression: AstNodePtr,
+        span: TextSpan,
     },
     Assignment {
         identifier: Token,
         expression: AstNodePtr,
-        index: Option<AstNodePtr>,
+        /// One index per dimension when assigning to an array element
+        indices: Vec<AstNode>,
+        /// The path to the field when assigning to a field of a struct
+        fields: Vec<Token>,
+        /// `=`, a compound operator like `+=`, or `++`/`--`, whose
+        /// expression is an implicit `1`
+        operator: Token,
+        span: TextSpan,
     },
     PadClear {
         expr: AstNodePtr,
+        span: TextSpan,
     },
     VarDecArray {
         identifier: Token,
         element_type: Token,
-        size: usize,
+        /// The declared sizes, outermost first, each an int literal or the
+        /// name of a constant. Only the first one can be left out, to be
+        /// inferred from the elements.
+        dimensions: Vec<Option<Token>>,
         elements: Vec<AstNode>,
+        span: TextSpan,
+    },
+    /// A nested list of elements in the initialiser of a multi-dimensional array
+    ArrayLiteral {
+        elements: Vec<AstNode>,
+        span: TextSpan,
     },
     ArrayAccess {
         identifier: Token,
-        index: AstNodePtr,
+        /// One index per dimension, outermost first
+        indices: Vec<AstNode>,
+        span: TextSpan,
+    },
+    StructDecl {
+        identifier: Token,
+        /// The name and type of each field, in the order they are laid out
+        fields: Vec<(Token, Token)>,
+        span: TextSpan,
+    },
+    /// A value of a struct, e.g. `Point { x: 1, y: 2 }`
+    StructLiteral {
+        identifier: Token,
+        fields: Vec<(Token, AstNode)>,
+        span: TextSpan,
     },
-    EndOfFile,
+    FieldAccess {
+        identifier: Token,
+        /// The path to the field, outermost first, e.g. `start` and `x` in
+        /// `line.start.x`
+        fields: Vec<Token>,
+        span: TextSpan,
+    },
+    EndOfFile {
+        span: TextSpan,
+    },
+}
+
+impl AstNode {
+    /// Returns the span covering the whole node in the source, from its first
+    /// token to its last one, including keywords, brackets and the closing `;`.
+    pub fn span(&self) -> &TextSpan {
+        match self {
+            AstNode::IntLiteral(token)
+            | AstNode::FloatLiteral(token)
+            | AstNode::BoolLiteral(token)
+            | AstNode::ColourLiteral(token)
+            | AstNode::Identifier { token } => &token.span,
+            AstNode::Program { span, .. }
+            | AstNode::VarDec { span, .. }
+            | AstNode::ConstDec { span, .. }
+            | AstNode::Block { span, .. }
+            | AstNode::Expression { span, .. }
+            | AstNode::SubExpression { span, .. }
+            | AstNode::UnaryOp { span, .. }
+            | AstNode::BinOp { span, .. }
+            | AstNode::Conditional { span, .. }
+            | AstNode::PadWidth { span }
+            | AstNode::PadRandI { span, .. }
+            | AstNode::PadHeight { span }
+            | AstNode::Break { span }
+            | AstNode::Continue { span }
+            | AstNode::PadRead { span, .. }
+            | AstNode::FunctionCall { span, .. }
+            | AstNode::Delay { span, .. }
+            | AstNode::Return { span, .. }
+            | AstNode::PadWriteBox { span, .. }
+            | AstNode::PadWrite { span, .. }
+            | AstNode::If { span, .. }
+            | AstNode::Match { span, .. }
+            | AstNode::For { span, .. }
+            | AstNode::While { span, .. }
+            | AstNode::FormalParam { span, .. }
+            | AstNode::FunctionDecl { span, .. }
+            | AstNode::Print { span, .. }
+            | AstNode::Assignment { span, .. }
+            | AstNode::PadClear { span, .. }
+            | AstNode::VarDecArray { span, .. }
+            | AstNode::ArrayLiteral { span, .. }
+            | AstNode::ArrayAccess { span, .. }
+            | AstNode::StructDecl { span, .. }
+            | AstNode::StructLiteral { span, .. }
+            | AstNode::FieldAccess { span, .. }
+            | AstNode::EndOfFile { span } => span,
+        }
+    }
 }
 
 pub trait Visitor<T> {
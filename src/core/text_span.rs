@@ -25,6 +25,16 @@ impl TextSpan {
             lexeme: lexeme.to_string(),
         }
     }
+
+    /// Returns the smallest span covering both `self` and `other`. The text in
+    /// between the two is not known, so the merged span has an empty lexeme.
+    pub fn merge(&self, other: &TextSpan) -> TextSpan {
+        let (from_line, from_col) =
+            (self.from_line, self.from_col).min((other.from_line, other.from_col));
+        let (to_line, to_col) = (self.to_line, self.to_col).max((other.to_line, other.to_col));
+
+        TextSpan::new(from_line, to_line, from_col, to_col, "")
+    }
 }
 
 impl Display for TextSpan {
@@ -3,6 +3,7 @@ pub enum TokenKind {
     Colon,
     Comma,
     Comment,
+    Dot,
     EndOfFile,
     Equals,
     Identifier,
@@ -18,12 +19,16 @@ pub enum TokenKind {
     Whitespace,
     // Keywords
     As,
+    Break,
+    Const,
+    Continue,
     Delay,
     Else,
     For,
     Function,
     If,
     Let,
+    Match,
     PadClear,
     PadHeight,
     PadRandI,
@@ -33,6 +38,8 @@ pub enum TokenKind {
     PadWriteBox,
     Print,
     Return,
+    Struct,
+    Then,
     While,
     // Binary operators
     And,
@@ -57,4 +64,36 @@ pub enum TokenKind {
     IntLiteral,
     Type,
     Arrow,
+    FatArrow,
+    // Compound assignment operators
+    PlusEquals,
+    MinusEquals,
+    MultiplyEquals,
+    DivideEquals,
+    ModEquals,
+    PlusPlus,
+    MinusMinus,
+}
+
+impl TokenKind {
+    /// Whether the token only matters for layout and is dropped before parsing.
+    pub fn is_trivia(&self) -> bool {
+        matches!(
+            self,
+            TokenKind::Whitespace | TokenKind::Newline | TokenKind::Comment
+        )
+    }
+
+    /// The binary operator that a compound assignment applies, e.g. `+` for
+    /// both `+=` and `++`.
+    pub fn compound_operator(&self) -> Option<TokenKind> {
+        match self {
+            TokenKind::PlusEquals | TokenKind::PlusPlus => Some(TokenKind::Plus),
+            TokenKind::MinusEquals | TokenKind::MinusMinus => Some(TokenKind::Minus),
+            TokenKind::MultiplyEquals => Some(TokenKind::Multiply),
+            TokenKind::DivideEquals => Some(TokenKind::Divide),
+            TokenKind::ModEquals => Some(TokenKind::Mod),
+            _ => None,
+        }
+    }
 }
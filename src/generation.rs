@@ -1,3 +1,4 @@
+pub mod assembler;
 pub mod instructions;
 pub mod visitors;
 
@@ -0,0 +1,250 @@
+use crate::{
+    generation::instructions::{Instruction, Program},
+    semantics::utils::MemoryLocation,
+    utils::errors::{AssemblyError, Error},
+};
+
+/// Parses PArIR text, in the format produced by the `Display` impl of
+/// [`Program`], back into a program.
+///
+/// Everything before the `.main` label is taken to be a function. Blank lines
+/// and `//` comments are skipped, and every malformed line is reported.
+pub fn assemble(text: &str) -> Result<Program, Vec<Error>> {
+    let mut program = Program {
+        functions: Vec::new(),
+        main: Vec::new(),
+    };
+    let mut errors = Vec::new();
+    let mut in_main = false;
+
+    for (index, line) in text.lines().enumerate() {
+        let line = match line.find("//") {
+            Some(comment) => &line[..comment],
+            None => line,
+        }
+        .trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        match parse_instruction(index + 1, line) {
+            Ok(instruction) => {
+                if let Instruction::FunctionLabel(name) = &instruction {
+                    in_main = name == "main";
+                }
+
+                match in_main {
+                    true => program.main.push(instruction),
+                    false => program.functions.push(instruction),
+                }
+            }
+            Err(e) => errors.push(e.into()),
+        }
+    }
+
+    match errors.is_empty() {
+        true => Ok(program),
+        false => Err(errors),
+    }
+}
+
+fn parse_instruction(line: usize, text: &str) -> Result<Instruction, AssemblyError> {
+    if let Some(label) = text.strip_prefix('.') {
+        return match is_label(label) {
+            true => Ok(Instruction::FunctionLabel(label.to_string())),
+            false => Err(AssemblyError::InvalidOperand(line, text.to_string())),
+        };
+    }
+
+    let (mnemonic, operand) = match text.split_once(char::is_whitespace) {
+        Some((mnemonic, operand)) => (mnemonic, Some(operand.trim())),
+        None => (text, None),
+    };
+
+    match (mnemonic, operand) {
+        ("push", Some(operand)) => parse_push(line, operand),
+        ("pusha", Some(operand)) => Ok(Instruction::PushArray(parse_memory_location(
+            line, operand,
+        )?)),
+        ("push" | "pusha", None) => Err(AssemblyError::MissingOperand(line, mnemonic.to_string())),
+        (_, Some(operand)) if parse_nullary(mnemonic).is_some() => Err(
+            AssemblyError::UnexpectedOperand(line, mnemonic.to_string(), operand.to_string()),
+        ),
+        (_, _) => {
+            parse_nullary(mnemonic).ok_or(AssemblyError::UnknownInstruction(line, text.to_string()))
+        }
+    }
+}
+
+fn parse_push(line: usize, operand: &str) -> Result<Instruction, AssemblyError> {
+    let invalid = || AssemblyError::InvalidOperand(line, operand.to_string());
+
+    if let Some(offset) = operand.strip_prefix("#PC") {
+        let offset = match offset.strip_prefix('+') {
+            _ if offset.is_empty() => 0,
+            Some(offset) => offset.parse().map_err(|_| invalid())?,
+            None => offset.parse().map_err(|_| invalid())?,
+        };
+
+        return Ok(Instruction::PushOffsetFromPC(offset));
+    }
+
+    if let Some(label) = operand.strip_prefix('.') {
+        return match is_label(label) {
+            true => Ok(Instruction::PushFunction(label.to_string())),
+            false => Err(invalid()),
+        };
+    }
+
+    if let Some(mem_loc) = operand.strip_prefix('+') {
+        return Ok(Instruction::PushOffsetFromOpS(parse_memory_location(
+            line, mem_loc,
+        )?));
+    }
+
+    if operand.starts_with('[') {
+        return Ok(Instruction::PushFromStack(parse_memory_location(
+            line, operand,
+        )?));
+    }
+
+    if let Ok(value) = operand.parse() {
+        return Ok(Instruction::PushIntValue(value));
+    }
+
+    operand
+        .parse()
+        .map(Instruction::PushFloatValue)
+        .map_err(|_| invalid())
+}
+
+/// Parses a `[frame_index:stack_level]` operand.
+fn parse_memory_location(line: usize, operand: &str) -> Result<MemoryLocation, AssemblyError> {
+    let invalid = || AssemblyError::InvalidOperand(line, operand.to_string());
+
+    let (frame_index, stack_level) = operand
+        .strip_prefix('[')
+        .and_then(|o| o.strip_suffix(']'))
+        .and_then(|o| o.split_once(':'))
+        .ok_or_else(invalid)?;
+
+    Ok(MemoryLocation {
+        frame_index: frame_index.trim().parse().map_err(|_| invalid())?,
+        stack_level: stack_level.trim().parse().map_err(|_| invalid())?,
+    })
+}
+
+fn is_label(label: &str) -> bool {
+    !label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn parse_nullary(mnemonic: &str) -> Option<Instruction> {
+    Some(match mnemonic {
+        "st" => Instruction::Store,
+        "sta" => Instruction::StoreArray,
+        "nop" => Instruction::NoOperation,
+        "add" => Instruction::Add,
+        "sub" => Instruction::Sub,
+        "mul" => Instruction::Mul,
+        "div" => Instruction::Div,
+        "mod" => Instruction::Mod,
+        "irnd" => Instruction::RandInt,
+        "and" => Instruction::And,
+        "or" => Instruction::Or,
+        "not" => Instruction::Not,
+        "lt" => Instruction::LessThan,
+        "gt" => Instruction::GreaterThan,
+        "le" => Instruction::LessThanOrEqual,
+        "ge" => Instruction::GreaterThanOrEqual,
+        "eq" => Instruction::Equal,
+        "jmp" => Instruction::Jump,
+        "cjmp" => Instruction::JumpIfNotZero,
+        "call" => Instruction::Call,
+        "ret" => Instruction::Return,
+        "halt" => Instruction::Halt,
+        "oframe" => Instruction::NewFrame,
+        "cframe" => Instruction::PopFrame,
+        "alloc" => Instruction::Alloc,
+        "delay" => Instruction::Delay,
+        "write" => Instruction::Write,
+        "writebox" => Instruction::WriteBox,
+        "clear" => Instruction::Clear,
+        "width" => Instruction::Width,
+        "read" => Instruction::Read,
+        "height" => Instruction::Height,
+        "print" => Instruction::Print,
+        "printa" => Instruction::PrintArray,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{generation::PArIRWriter, lexing::Lexer, parsing::Parser, utils::SimpleBuffer};
+
+    use super::*;
+    use assert_matches::assert_matches;
+    use rstest::rstest;
+    use std::path::Path;
+
+    fn compile(input: &str) -> Program {
+        let mut lexer: Lexer<SimpleBuffer> = Lexer::new(input, Path::new(""), None);
+        let tokens = lexer.lex().unwrap();
+
+        let mut parser = Parser::new(&tokens, Path::new(""));
+        let ast = parser.parse().unwrap();
+
+        let mut writer = PArIRWriter::new();
+        writer.get_program(ast).clone()
+    }
+
+    #[rstest]
+    #[case(include_str!("../../samples/arr.parl"))]
+    #[case(include_str!("../../samples/flags_good.parl"))]
+    #[case(include_str!("../../samples/max_in_array.parl"))]
+    #[case(include_str!("../../samples/race.parl"))]
+    #[case("let f: float = 5.0 + 1.25; __print f;")]
+    fn test_round_trip(#[case] input: &str) {
+        let program = compile(input);
+
+        assert_eq!(assemble(&program.to_string()).unwrap(), program);
+    }
+
+    #[rstest]
+    #[case("push #PC-4", Instruction::PushOffsetFromPC(-4))]
+    #[case("push #PC", Instruction::PushOffsetFromPC(0))]
+    #[case("push .foo", Instruction::PushFunction("foo".to_string()))]
+    #[case("push +[2:1]", Instruction::PushOffsetFromOpS(MemoryLocation { frame_index: 2, stack_level: 1 }))]
+    #[case("pusha [0:3]", Instruction::PushArray(MemoryLocation { frame_index: 0, stack_level: 3 }))]
+    #[case("push -1.5", Instruction::PushFloatValue(-1.5))]
+    #[case("writebox", Instruction::WriteBox)]
+    fn test_instruction(#[case] text: &str, #[case] expected: Instruction) {
+        assert_eq!(parse_instruction(1, text).unwrap(), expected);
+    }
+
+    #[rstest]
+    fn test_errors() {
+        let text = ".main\n\n// comment\npush [1;0]\npush\ndup\nhalt 1\n";
+
+        let errors = assemble(text).unwrap_err();
+
+        assert_eq!(errors.len(), 4);
+        assert_matches!(
+            errors[0],
+            Error::Assembly(AssemblyError::InvalidOperand(4, _))
+        );
+        assert_matches!(
+            errors[1],
+            Error::Assembly(AssemblyError::MissingOperand(5, _))
+        );
+        assert_matches!(
+            errors[2],
+            Error::Assembly(AssemblyError::UnknownInstruction(6, _))
+        );
+        assert_matches!(
+            errors[3],
+            Error::Assembly(AssemblyError::UnexpectedOperand(7, _, _))
+        );
+    }
+}
@@ -1,13 +1,21 @@
 use std::fmt::Display;
 
-use crate::{core::Token, semantics::utils::MemoryLocation};
+use crate::semantics::utils::MemoryLocation;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Program {
     pub functions: Vec<Instruction>,
     pub main: Vec<Instruction>,
 }
 
+impl Program {
+    /// Lays out the functions followed by `.main`, in the same order as the
+    /// textual PArIR output.
+    pub fn instructions(&self) -> impl Iterator<Item = &Instruction> {
+        self.functions.iter().chain(self.main.iter())
+    }
+}
+
 impl Display for Program {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         for instruction in &self.functions {
@@ -20,12 +28,12 @@ impl Display for Program {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Instruction {
     FunctionLabel(String),
     PushArray(MemoryLocation),
     PushFromStack(MemoryLocation),
-    PushFunction(Token),
+    PushFunction(String),
     PushOffsetFromPC(i32),
     PushOffsetFromOpS(MemoryLocation),
     PushIntValue(usize),
@@ -80,7 +88,7 @@ impl Display for Instruction {
             Instruction::Read => writeln!(f, "read"),
             Instruction::FunctionLabel(name) => writeln!(f, ".{}", name),
             Instruction::PushIntValue(value) => writeln!(f, "push {}", value),
-            Instruction::PushFloatValue(value) => writeln!(f, "push {}", value),
+            Instruction::PushFloatValue(value) => writeln!(f, "push {:?}", value),
             Instruction::PushArray(mem_loc) => {
                 writeln!(f, "pusha [{}:{}]", mem_loc.frame_index, mem_loc.stack_level)
             }
@@ -1,4 +1,6 @@
-use crate::semantics::utils::{Signature, Symbol, SymbolTable, SymbolType};
+use crate::semantics::utils::{
+    array_size, fold_constant, ConstValue, Signature, Symbol, SymbolTable, SymbolType,
+};
 use crate::{
     generation::instructions::{Instruction, Program},
     semantics::utils::Type,
@@ -7,7 +9,23 @@ use crate::{
 use crate::core::{AstNode, Token, Visitor};
 use crate::{core::TokenKind, semantics::utils::MemoryLocation};
 
-#[derive(Debug)]
+/// The label of the routine that out-of-range indices jump to.
+const BOUNDS_TRAP: &str = "__bounds";
+
+/// The value that the bounds trap prints before halting.
+pub const BOUNDS_TRAP_VALUE: usize = 0xDEAD;
+
+/// The jumps out of a loop, patched once the loop has been generated and
+/// their targets are known.
+#[derive(Debug, Clone)]
+struct LoopJumps {
+    /// The stack level of the frame the loop opens
+    stack_level: usize,
+    breaks: Vec<usize>,
+    continues: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
 pub struct PArIRWriter {
     /// Stack of symbol tables, each representing a scope
     symbol_table: Vec<SymbolTable>,
@@ -19,6 +37,10 @@ pub struct PArIRWriter {
     stack_level: usize,
     /// The current frame index
     frame_index: usize,
+    /// Whether array indices are checked at runtime
+    bounds_checks: bool,
+    /// The loops around the code being generated, the innermost last
+    loops: Vec<LoopJumps>,
 }
 
 impl PArIRWriter {
@@ -32,12 +54,65 @@ impl PArIRWriter {
             instr_ptr: 0,
             stack_level: 0,
             frame_index: 0,
+            bounds_checks: false,
+            loops: Vec::new(),
         }
     }
 
-    pub fn get_program(&mut self, ast: &AstNode) -> String {
+    /// Checks every array index at runtime, jumping to a trap that prints
+    /// [`BOUNDS_TRAP_VALUE`] and halts if it is out of range.
+    pub fn with_bounds_checks(mut self, bounds_checks: bool) -> Self {
+        self.bounds_checks = bounds_checks;
+        self
+    }
+
+    pub fn get_program(&mut self, ast: &AstNode) -> &Program {
         self.visit(ast);
-        format!("{}", self.program)
+        &self.program
+    }
+
+    /// Generates code for statements entered one at a time, as in the REPL.
+    ///
+    /// The global scope and its frame outlive each chunk: the first chunk
+    /// opens the frame and every later one grows it by the variables that it
+    /// declares. Only the functions declared by these statements are part of
+    /// the returned program, and `.main` ends with a `halt` so that values
+    /// left on the operand stack, like that of a bare expression, can be read
+    /// back.
+    pub fn get_chunk(&mut self, statements: &[AstNode]) -> Program {
+        let first_chunk = self.symbol_table.is_empty();
+        if first_chunk {
+            self.push_scope();
+        }
+
+        self.program.main.clear();
+        self.instr_ptr = 0;
+        self.stack_level = 0;
+
+        let var_count_before = self.get_scope_var_count();
+
+        self.add_instruction(Instruction::FunctionLabel("main".to_string()));
+        let var_count_push = self.add_instruction(Instruction::PushIntValue(0));
+        self.add_instruction(match first_chunk {
+            true => Instruction::NewFrame,
+            false => Instruction::Alloc,
+        });
+
+        for statement in statements {
+            // Blocks and functions reset the frame index, so start every
+            // statement back past the globals declared so far
+            self.frame_index = self.get_scope_var_count();
+            self.visit(statement);
+        }
+
+        self.program.main[var_count_push] =
+            Instruction::PushIntValue(self.get_scope_var_count() - var_count_before);
+        self.add_instruction(Instruction::Halt);
+
+        Program {
+            functions: std::mem::take(&mut self.program.functions),
+            main: std::mem::take(&mut self.program.main),
+        }
     }
 
     fn add_instruction(&mut self, instruction: Instruction) -> usize {
@@ -46,15 +121,156 @@ impl PArIRWriter {
         self.instr_ptr - 1
     }
 
+    /// Reverses the order of the top `count` values on the stack, by storing
+    /// them in a temporary frame and pushing them back.
+    fn add_reverse(&mut self, count: usize) {
+        self.add_instruction(Instruction::PushIntValue(count));
+        self.add_instruction(Instruction::NewFrame);
+        self.add_instruction(Instruction::PushIntValue(count));
+        self.add_instruction(Instruction::PushIntValue(0));
+        self.add_instruction(Instruction::PushIntValue(0));
+        self.add_instruction(Instruction::StoreArray);
+        self.add_instruction(Instruction::PushIntValue(count));
+        self.add_instruction(Instruction::PushArray(MemoryLocation {
+            stack_level: 0,
+            frame_index: 0,
+        }));
+        self.add_instruction(Instruction::PopFrame);
+    }
+
+    /// Pushes `size` values from the frame starting at `mem_loc`, the first
+    /// one ending up on top, which is how arrays and structs are passed around.
+    fn add_push_values(&mut self, mem_loc: MemoryLocation, size: usize) {
+        if size == 1 {
+            self.add_instruction(Instruction::PushFromStack(mem_loc));
+        } else {
+            self.add_instruction(Instruction::PushIntValue(size));
+            self.add_instruction(Instruction::PushArray(mem_loc));
+            self.add_reverse(size);
+        }
+    }
+
+    /// Stores the top `size` values on the stack in the frame starting at
+    /// `mem_loc`, the top one first.
+    fn add_store_values(&mut self, mem_loc: MemoryLocation, size: usize) {
+        if size != 1 {
+            self.add_instruction(Instruction::PushIntValue(size));
+        }
+        self.add_instruction(Instruction::PushIntValue(mem_loc.frame_index));
+        self.add_instruction(Instruction::PushIntValue(mem_loc.stack_level));
+        self.add_instruction(match size {
+            1 => Instruction::Store,
+            _ => Instruction::StoreArray,
+        });
+    }
+
+    /// The fields of the struct named `name`, in the order they are laid out.
+    fn struct_fields(&self, name: &str) -> Vec<(String, Type)> {
+        self.symbol_table
+            .iter()
+            .rev()
+            .find_map(|table| match table.find_symbol(name) {
+                Some(Symbol {
+                    symbol_type: SymbolType::Struct(fields),
+                    ..
+                }) => Some(fields.clone()),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    /// How many values a value of type `t` takes up.
+    fn type_size(&self, t: &Type) -> usize {
+        match t {
+            Type::Array(element, size) => self.type_size(element) * size,
+            Type::Struct(name) => self
+                .struct_fields(name)
+                .iter()
+                .map(|(_, t)| self.type_size(t))
+                .sum(),
+            _ => 1,
+        }
+    }
+
+    fn symbol_size(&self, symbol_type: &SymbolType) -> usize {
+        match symbol_type {
+            SymbolType::Variable(t) => self.type_size(t),
+            _ => symbol_type.size(),
+        }
+    }
+
+    /// How many values evaluating the expression leaves on the stack.
+    fn value_size(&self, node: &AstNode) -> usize {
+        match node {
+            AstNode::Expression {
+                casted_type: None,
+                expr,
+                ..
+            }
+            | AstNode::SubExpression { bin_op: expr, .. } => self.value_size(expr),
+            AstNode::Identifier { token } => match self.find_symbol(token) {
+                Some(Symbol {
+                    symbol_type: SymbolType::Constant(..),
+                    ..
+                })
+                | None => 1,
+                Some(symbol) => self.symbol_size(&symbol.symbol_type),
+            },
+            AstNode::FunctionCall { identifier, .. } => match self.find_symbol(identifier) {
+                Some(Symbol {
+                    symbol_type: SymbolType::Function(signature),
+                    ..
+                }) => self.type_size(&signature.return_type),
+                _ => 1,
+            },
+            AstNode::StructLiteral { identifier, .. } => {
+                self.type_size(&Type::Struct(identifier.span.lexeme.clone()))
+            }
+            AstNode::FieldAccess {
+                identifier, fields, ..
+            } => self
+                .field_location(identifier, fields)
+                .map_or(1, |(_, t)| self.type_size(&t)),
+            _ => 1,
+        }
+    }
+
+    /// Where the field that `fields` leads to in the struct `identifier` is
+    /// stored, along with its type.
+    fn field_location(
+        &self,
+        identifier: &Token,
+        fields: &[Token],
+    ) -> Option<(MemoryLocation, Type)> {
+        let mut mem_loc = self.get_memory_location(identifier)?;
+        let mut current = match &self.find_symbol(identifier)?.symbol_type {
+            SymbolType::Variable(t) => t.clone(),
+            _ => return None,
+        };
+
+        for field in fields {
+            let Type::Struct(name) = &current else {
+                return None;
+            };
+            let mut found = None;
+            for (f, t) in self.struct_fields(name) {
+                if f == field.span.lexeme {
+                    found = Some(t);
+                    break;
+                }
+                mem_loc.frame_index += self.type_size(&t);
+            }
+            current = found?;
+        }
+
+        Some((mem_loc, current))
+    }
+
     fn get_scope_var_count(&self) -> usize {
         self.current_scope()
             .symbols
             .iter()
-            .map(|s| match s.symbol_type {
-                SymbolType::Array(_, size) => size,
-                SymbolType::Variable(_) => 1,
-                _ => 0,
-            })
+            .map(|s| self.symbol_size(&s.symbol_type))
             .sum()
     }
 
@@ -89,6 +305,40 @@ impl PArIRWriter {
             .is_some()
     }
 
+    /// The value of the constant named by `token`, if it names one.
+    fn constant_value(&self, token: &Token) -> Option<f64> {
+        match self.find_symbol(token).map(|s| &s.symbol_type) {
+            Some(SymbolType::Constant(_, value)) => Some(value.0),
+            _ => None,
+        }
+    }
+
+    /// The size of an array dimension, given as an int literal or as the name
+    /// of a constant, which the analyser has already checked.
+    fn dimension_size(&self, size: &Token) -> usize {
+        self.constant_value(size)
+            .or_else(|| size.span.lexeme.parse().ok())
+            .and_then(array_size)
+            .expect("array sizes are checked by the analyser")
+    }
+
+    /// Pushes the value of a constant, which is never stored in a frame, the
+    /// way a literal of its declared type is. A negative value is pushed the
+    /// way a negated literal is.
+    fn add_push_constant(&mut self, value: f64, const_type: &Type) {
+        if value < 0.0 {
+            self.add_push_constant(-value, const_type);
+            self.add_instruction(Instruction::PushIntValue(0));
+            self.add_instruction(Instruction::Sub);
+            return;
+        }
+
+        match const_type != &Type::Float && value.fract() == 0.0 {
+            true => self.add_instruction(Instruction::PushIntValue(value as usize)),
+            false => self.add_instruction(Instruction::PushFloatValue(value)),
+        };
+    }
+
     fn get_memory_location(&self, symbol: &Token) -> Option<MemoryLocation> {
         self.find_symbol(symbol).and_then(|s| {
             let relative_mem_loc = s.memory_location;
@@ -107,9 +357,88 @@ impl PArIRWriter {
         self.symbol_table.pop();
     }
 
+    /// Checks that the index on top of the stack is in `0..size`, jumping to
+    /// the bounds trap otherwise. The index is kept in a temporary frame while
+    /// it is compared, and left on the stack.
+    fn add_bounds_check(&mut self, size: usize) {
+        let index = MemoryLocation {
+            stack_level: 0,
+            frame_index: 0,
+        };
+
+        self.add_instruction(Instruction::PushIntValue(1));
+        self.add_instruction(Instruction::NewFrame);
+        self.add_instruction(Instruction::PushIntValue(0));
+        self.add_instruction(Instruction::PushIntValue(0));
+        self.add_instruction(Instruction::Store);
+
+        // index < size && 0 <= index
+        self.add_instruction(Instruction::PushIntValue(size));
+        self.add_instruction(Instruction::PushFromStack(index));
+        self.add_instruction(Instruction::LessThan);
+        self.add_instruction(Instruction::PushFromStack(index));
+        self.add_instruction(Instruction::PushIntValue(0));
+        self.add_instruction(Instruction::LessThanOrEqual);
+        self.add_instruction(Instruction::And);
+
+        self.add_instruction(Instruction::Not);
+        self.add_instruction(Instruction::PushFunction(BOUNDS_TRAP.to_string()));
+        self.add_instruction(Instruction::JumpIfNotZero);
+
+        self.add_instruction(Instruction::PushFromStack(index));
+        self.add_instruction(Instruction::PopFrame);
+    }
+
+    /// Computes the row-major offset of an element of an array from its
+    /// indices, leaving it on top of the stack.
+    fn add_offset(&mut self, identifier: &Token, indices: &[AstNode]) {
+        let dimensions = match self.find_symbol(identifier).map(|s| &s.symbol_type) {
+            Some(SymbolType::Array(_, dimensions)) => dimensions.clone(),
+            _ => vec![],
+        };
+
+        for (i, index) in indices.iter().enumerate() {
+            if i > 0 {
+                self.add_instruction(Instruction::PushIntValue(dimensions[i]));
+                self.add_instruction(Instruction::Mul);
+            }
+            self.visit(index);
+            if let (true, Some(size)) = (self.bounds_checks, dimensions.get(i)) {
+                self.add_bounds_check(*size);
+            }
+            if i > 0 {
+                self.add_instruction(Instruction::Add);
+            }
+        }
+    }
+
+    /// Closes the frames opened inside the innermost loop, then jumps to a
+    /// target that is patched in by [`PArIRWriter::patch_jumps`].
+    fn add_loop_jump(&mut self, is_break: bool) {
+        let stack_level = self.loops.last().unwrap().stack_level;
+        for _ in stack_level..self.stack_level {
+            self.add_instruction(Instruction::PopFrame);
+        }
+
+        let jump = self.add_instruction(Instruction::PushIntValue(0));
+        self.add_instruction(Instruction::Jump);
+
+        let loop_jumps = self.loops.last_mut().unwrap();
+        match is_break {
+            true => loop_jumps.breaks.push(jump),
+            false => loop_jumps.continues.push(jump),
+        }
+    }
+
+    fn patch_jumps(&mut self, jumps: &[usize], target: usize) {
+        for &jump in jumps {
+            self.program.main[jump] = Instruction::PushOffsetFromPC(target as i32 - jump as i32);
+        }
+    }
+
     fn visit_unscoped_block(&mut self, block_node: &AstNode) -> usize {
         match block_node {
-            AstNode::Block { statements } => {
+            AstNode::Block { statements, .. } => {
                 for statement in statements {
                     self.visit(statement);
                 }
@@ -120,11 +449,83 @@ impl PArIRWriter {
     }
 }
 
+/// Lists the elements of an array initialiser in row-major order, repeating
+/// those that fill a whole dimension or a whole sub-array.
+fn flatten_elements<'a>(elements: &'a [AstNode], dimensions: &[usize]) -> Vec<&'a AstNode> {
+    let rows = match elements {
+        [element] => vec![element; dimensions[0]],
+        _ => elements.iter().collect(),
+    };
+
+    rows.into_iter()
+        .flat_map(|element| match element {
+            AstNode::ArrayLiteral { elements, .. } if dimensions.len() > 1 => {
+                flatten_elements(elements, &dimensions[1..])
+            }
+            _ => vec![element; dimensions[1..].iter().product()],
+        })
+        .collect()
+}
+
+/// Whether evaluating the expression has no side effects, so that it can be
+/// evaluated in any order, or any number of times.
+fn is_pure(node: &AstNode) -> bool {
+    match node {
+        AstNode::Expression { expr, .. }
+        | AstNode::SubExpression { bin_op: expr, .. }
+        | AstNode::UnaryOp { expr, .. } => is_pure(expr),
+        AstNode::ArrayAccess { indices, .. } => indices.iter().all(is_pure),
+        AstNode::BinOp { left, right, .. }
+        | AstNode::PadRead {
+            x: left, y: right, ..
+        } => is_pure(left) && is_pure(right),
+        AstNode::IntLiteral(_)
+        | AstNode::FloatLiteral(_)
+        | AstNode::BoolLiteral(_)
+        | AstNode::ColourLiteral(_)
+        | AstNode::Identifier { .. }
+        | AstNode::FieldAccess { .. }
+        | AstNode::PadWidth { .. }
+        | AstNode::PadHeight { .. } => true,
+        _ => false,
+    }
+}
+
+/// The instruction computing a binary operator, with the left operand on top
+/// of the stack.
+fn binary_instruction(operator: TokenKind) -> Instruction {
+    match operator {
+        TokenKind::Plus => Instruction::Add,
+        TokenKind::Minus => Instruction::Sub,
+        TokenKind::Multiply => Instruction::Mul,
+        TokenKind::Mod => Instruction::Mod,
+        TokenKind::Divide => Instruction::Div,
+        TokenKind::EqEq => Instruction::Equal,
+        TokenKind::LessThan => Instruction::LessThan,
+        TokenKind::LessThanEqual => Instruction::LessThanOrEqual,
+        TokenKind::GreaterThan => Instruction::GreaterThan,
+        TokenKind::GreaterThanEqual => Instruction::GreaterThanOrEqual,
+        TokenKind::And => Instruction::And,
+        TokenKind::Or => Instruction::Or,
+        _ => Instruction::NoOperation,
+    }
+}
+
 impl Visitor<usize> for PArIRWriter {
     fn visit(&mut self, node: &AstNode) -> usize {
         match node {
-            AstNode::Program { statements } => {
+            AstNode::Program { statements, .. } => {
                 self.push_scope();
+
+                if self.bounds_checks {
+                    self.program.functions.extend([
+                        Instruction::FunctionLabel(BOUNDS_TRAP.to_string()),
+                        Instruction::PushIntValue(BOUNDS_TRAP_VALUE),
+                        Instruction::Print,
+                        Instruction::Halt,
+                    ]);
+                }
+
                 self.add_instruction(Instruction::FunctionLabel("main".to_string()));
 
                 let var_count_push = self.add_instruction(Instruction::PushIntValue(0));
@@ -142,8 +543,12 @@ impl Visitor<usize> for PArIRWriter {
                 self.pop_scope();
             }
 
-            AstNode::ArrayAccess { identifier, index } => {
-                self.visit(index);
+            AstNode::ArrayAccess {
+                identifier,
+                indices,
+                ..
+            } => {
+                self.add_offset(identifier, indices);
 
                 let mem_loc = self.get_memory_location(identifier);
 
@@ -155,11 +560,53 @@ impl Visitor<usize> for PArIRWriter {
             AstNode::VarDecArray {
                 identifier,
                 element_type,
-                size,
+                dimensions,
                 elements,
+                ..
             } => {
-                for element in elements.iter().rev() {
-                    self.visit(element);
+                let dimensions = dimensions
+                    .iter()
+                    .map(|size| {
+                        size.as_ref()
+                            .map_or(elements.len(), |size| self.dimension_size(size))
+                    })
+                    .collect::<Vec<_>>();
+                let elements = flatten_elements(elements, &dimensions);
+                let size = elements.len();
+
+                // `sta` stores the top of the stack first, so the first element
+                // has to end up on top
+                if elements.iter().all(|element| is_pure(element)) {
+                    // Without side effects, the order they are evaluated in
+                    // does not matter
+                    for element in elements.iter().rev() {
+                        self.visit(element);
+                    }
+                } else {
+                    // Each element is evaluated once and in order, into the
+                    // first slot it fills, and copied over from there
+                    let mut slots: Vec<(&AstNode, MemoryLocation)> = vec![];
+                    for (i, element) in elements.iter().enumerate() {
+                        if slots.iter().any(|(e, _)| std::ptr::eq(*e, *element)) {
+                            continue;
+                        }
+                        let slot = MemoryLocation {
+                            stack_level: 0,
+                            frame_index: self.frame_index + i,
+                        };
+                        self.visit(element);
+                        self.add_instruction(Instruction::PushIntValue(slot.frame_index));
+                        self.add_instruction(Instruction::PushIntValue(0));
+                        self.add_instruction(Instruction::Store);
+                        slots.push((element, slot));
+                    }
+                    for element in elements.iter().rev() {
+                        let (_, slot) = slots
+                            .iter()
+                            .find(|(e, _)| std::ptr::eq(*e, *element))
+                            .unwrap();
+                        self.add_instruction(Instruction::PushFromStack(*slot));
+                    }
                 }
 
                 let element_type = self
@@ -169,14 +616,14 @@ impl Visitor<usize> for PArIRWriter {
                 if !self.check_scope(identifier) {
                     self.add_symbol(
                         identifier,
-                        &SymbolType::Array(element_type, *size),
+                        &SymbolType::Array(element_type, dimensions),
                         Some(MemoryLocation {
                             stack_level: self.stack_level,
                             frame_index: self.frame_index,
                         }),
                     );
                 }
-                self.add_instruction(Instruction::PushIntValue(*size));
+                self.add_instruction(Instruction::PushIntValue(size));
 
                 self.add_instruction(Instruction::PushIntValue(self.frame_index));
                 self.add_instruction(Instruction::PushIntValue(0));
@@ -184,22 +631,24 @@ impl Visitor<usize> for PArIRWriter {
 
                 self.add_instruction(Instruction::StoreArray);
             }
-            AstNode::Block { statements } => {
+            AstNode::Block { statements, .. } => {
                 self.push_scope();
                 let var_dec_count = self.add_instruction(Instruction::PushIntValue(0));
                 self.add_instruction(Instruction::NewFrame);
                 self.stack_level += 1;
-                self.frame_index = 0;
+                // The enclosing frame carries on where it left off afterwards
+                let frame_index = std::mem::take(&mut self.frame_index);
                 for statement in statements {
                     // if the statement is a return statement, we don't need to
                     // check the rest of the block
-                    if let AstNode::Return { expression } = statement {
+                    if let AstNode::Return { expression, .. } = statement {
                         self.visit(expression);
                         self.program.main[var_dec_count] =
                             Instruction::PushIntValue(self.get_scope_var_count());
                         self.add_instruction(Instruction::Return);
                         self.add_instruction(Instruction::PopFrame);
                         self.stack_level -= 1;
+                        self.frame_index = frame_index;
                         self.pop_scope();
                         return self.instr_ptr;
                     } else {
@@ -212,6 +661,7 @@ impl Visitor<usize> for PArIRWriter {
 
                 self.add_instruction(Instruction::PopFrame);
                 self.stack_level -= 1;
+                self.frame_index = frame_index;
                 self.pop_scope();
             }
 
@@ -219,8 +669,19 @@ impl Visitor<usize> for PArIRWriter {
                 identifier,
                 params,
                 return_type,
+                return_dimensions,
                 block,
+                ..
             } => {
+                let return_dimensions = return_dimensions
+                    .iter()
+                    .map(|size| self.dimension_size(size))
+                    .collect::<Vec<_>>();
+                let return_type = Type::array(
+                    self.current_scope().token_to_type(&return_type.span.lexeme),
+                    &return_dimensions,
+                );
+
                 self.add_symbol(
                     identifier,
                     &SymbolType::Function(Signature::new(return_type.clone())),
@@ -229,7 +690,7 @@ impl Visitor<usize> for PArIRWriter {
 
                 self.push_scope();
                 self.stack_level += 1;
-                self.frame_index = 0;
+                let frame_index = std::mem::take(&mut self.frame_index);
 
                 // Add the parameter symbols to the symbol table in this scope
                 for param in params {
@@ -263,63 +724,125 @@ impl Visitor<usize> for PArIRWriter {
                 self.pop_scope();
                 self.stack_level = 0;
                 self.instr_ptr -= end - start;
-                self.frame_index = 0;
+                self.frame_index = frame_index;
             }
 
-            AstNode::FunctionCall { identifier, args } => {
+            AstNode::FunctionCall {
+                identifier, args, ..
+            } => {
                 let mut len = 0;
 
                 for arg in args.iter().rev() {
-                    if let AstNode::Expression { expr, .. } = arg {
-                        if let AstNode::Identifier { token } = expr.as_ref() {
-                            let symbol = self.find_symbol(token).unwrap();
-                            if let SymbolType::Array(_type, s) = symbol.symbol_type.clone() {
-                                len += s;
-                            } else {
-                                len += 1;
-                            }
-                        } else {
-                            len += 1;
-                        }
-                    }
-
+                    len += self.value_size(arg);
                     self.visit(arg);
                 }
 
                 self.add_instruction(Instruction::PushIntValue(len));
-                self.add_instruction(Instruction::PushFunction(identifier.clone()));
+                self.add_instruction(Instruction::PushFunction(identifier.span.lexeme.clone()));
                 self.add_instruction(Instruction::Call);
             }
 
             AstNode::Identifier { token } => {
-                let symbol = self.find_symbol(token).unwrap();
-
-                match symbol.symbol_type {
-                    SymbolType::Array(_, s) => {
-                        if let Some(mem_loc) = self.get_memory_location(token) {
-                            self.add_instruction(Instruction::PushIntValue(s));
-                            self.add_instruction(Instruction::PushArray(mem_loc));
-                            // workaround
-                            self.add_instruction(Instruction::PushIntValue(s)); // push s
-                            self.add_instruction(Instruction::NewFrame); // oframe
-                            self.add_instruction(Instruction::PushIntValue(s)); // push s
-                            self.add_instruction(Instruction::PushIntValue(0)); // push 0
-                            self.add_instruction(Instruction::PushIntValue(0)); // push 0
-                            self.add_instruction(Instruction::StoreArray);
-                            self.add_instruction(Instruction::PushIntValue(s));
-                            self.add_instruction(Instruction::PushArray(MemoryLocation {
-                                stack_level: 0,
-                                frame_index: 0,
-                            }));
-                            self.add_instruction(Instruction::PopFrame);
-                            // end workaround
-                        }
-                    }
-                    _ => {
-                        if let Some(mem_loc) = self.get_memory_location(token) {
-                            return self.add_instruction(Instruction::PushFromStack(mem_loc));
-                        }
-                    }
+                if let Some(SymbolType::Constant(const_type, value)) =
+                    self.find_symbol(token).map(|s| s.symbol_type.clone())
+                {
+                    self.add_push_constant(value.0, &const_type);
+                    return self.instr_ptr;
+                }
+
+                let size = self.symbol_size(&self.find_symbol(token).unwrap().symbol_type);
+
+                if let Some(mem_loc) = self.get_memory_location(token) {
+                    self.add_push_values(mem_loc, size);
+                }
+            }
+
+            AstNode::FieldAccess {
+                identifier, fields, ..
+            } => {
+                if let Some((mem_loc, field_type)) = self.field_location(identifier, fields) {
+                    let size = self.type_size(&field_type);
+                    self.add_push_values(mem_loc, size);
+                }
+            }
+
+            AstNode::StructDecl {
+                identifier, fields, ..
+            } => {
+                let fields = fields
+                    .iter()
+                    .map(|(name, t)| {
+                        (
+                            name.span.lexeme.clone(),
+                            self.current_scope().token_to_type(&t.span.lexeme),
+                        )
+                    })
+                    .collect();
+
+                self.add_symbol(identifier, &SymbolType::Struct(fields), None);
+            }
+
+            AstNode::StructLiteral {
+                identifier, fields, ..
+            } => {
+                let layout = self.struct_fields(&identifier.span.lexeme);
+                if let [(_, value)] = fields.as_slice() {
+                    self.visit(value);
+                    return self.instr_ptr;
+                }
+
+                // The fields are evaluated in the order they are written, into
+                // a temporary frame laid out like the struct, which is then
+                // pushed with the first field on top
+                let size = self.type_size(&Type::Struct(identifier.span.lexeme.clone()));
+                self.add_instruction(Instruction::PushIntValue(size));
+                self.add_instruction(Instruction::NewFrame);
+                self.stack_level += 1;
+
+                for (field, value) in fields {
+                    let Some(index) = layout.iter().position(|(f, _)| *f == field.span.lexeme)
+                    else {
+                        continue;
+                    };
+                    let offset = layout[..index].iter().map(|(_, t)| self.type_size(t)).sum();
+
+                    self.visit(value);
+                    self.add_store_values(
+                        MemoryLocation {
+                            stack_level: 0,
+                            frame_index: offset,
+                        },
+                        self.type_size(&layout[index].1),
+                    );
+                }
+
+                self.add_push_values(
+                    MemoryLocation {
+                        stack_level: 0,
+                        frame_index: 0,
+                    },
+                    size,
+                );
+                self.add_instruction(Instruction::PopFrame);
+                self.stack_level -= 1;
+            }
+
+            AstNode::ConstDec {
+                identifier,
+                const_type,
+                expression,
+                ..
+            } => {
+                // Nothing is emitted, as the value is pushed wherever it is used
+                let value = fold_constant(expression, &|token| self.constant_value(token));
+                let const_type = self.current_scope().token_to_type(&const_type.span.lexeme);
+
+                if !self.check_scope(identifier) {
+                    self.add_symbol(
+                        identifier,
+                        &SymbolType::Constant(const_type, ConstValue(value.unwrap_or_default())),
+                        None,
+                    );
                 }
             }
 
@@ -327,15 +850,17 @@ impl Visitor<usize> for PArIRWriter {
                 identifier,
                 var_type: r#type,
                 expression,
+                ..
             } => {
                 self.visit(expression);
 
+                let var_type = self.current_scope().token_to_type(&r#type.span.lexeme);
+                let size = self.type_size(&var_type);
+
                 if !self.check_scope(identifier) {
                     self.add_symbol(
                         identifier,
-                        &SymbolType::Variable(
-                            self.current_scope().token_to_type(&r#type.span.lexeme),
-                        ),
+                        &SymbolType::Variable(var_type),
                         Some(MemoryLocation {
                             stack_level: self.stack_level,
                             frame_index: self.frame_index,
@@ -343,75 +868,118 @@ impl Visitor<usize> for PArIRWriter {
                     );
                 }
 
-                self.add_instruction(Instruction::PushIntValue(self.frame_index));
-                self.add_instruction(Instruction::PushIntValue(0));
-                self.frame_index += 1;
-                self.add_instruction(Instruction::Store);
+                self.add_store_values(
+                    MemoryLocation {
+                        stack_level: 0,
+                        frame_index: self.frame_index,
+                    },
+                    size,
+                );
+                self.frame_index += size;
             }
 
             AstNode::FormalParam {
                 identifier,
                 param_type,
-                length,
-            } => match length {
-                None => {
-                    self.add_symbol(
-                        identifier,
-                        &SymbolType::Variable(
-                            self.current_scope().token_to_type(&param_type.span.lexeme),
-                        ),
-                        Some(MemoryLocation {
-                            stack_level: self.stack_level,
-                            frame_index: self.frame_index,
-                        }),
-                    );
-                    self.frame_index += 1;
-                }
-                Some(index) => {
-                    let size: usize = index.span.lexeme.parse().unwrap();
-
-                    self.add_symbol(
-                        identifier,
-                        &SymbolType::Array(
-                            self.current_scope().token_to_type(&param_type.span.lexeme),
-                            size,
-                        ),
-                        Some(MemoryLocation {
-                            stack_level: self.stack_level,
-                            frame_index: self.frame_index,
-                        }),
-                    );
+                dimensions,
+                ..
+            } => {
+                let param_type = self.current_scope().token_to_type(&param_type.span.lexeme);
+                let symbol_type = match dimensions.is_empty() {
+                    true => SymbolType::Variable(param_type),
+                    false => SymbolType::Array(
+                        param_type,
+                        dimensions
+                            .iter()
+                            .map(|size| self.dimension_size(size))
+                            .collect(),
+                    ),
+                };
 
-                    self.frame_index += size;
-                }
-            },
+                self.add_symbol(
+                    identifier,
+                    &symbol_type,
+                    Some(MemoryLocation {
+                        stack_level: self.stack_level,
+                        frame_index: self.frame_index,
+                    }),
+                );
+                self.frame_index += self.symbol_size(&symbol_type);
+            }
 
             AstNode::Expression {
                 casted_type: _,
                 expr,
+                ..
             } => {
                 self.visit(expr);
             }
 
-            AstNode::SubExpression { bin_op } => {
+            AstNode::SubExpression { bin_op, .. } => {
                 self.visit(bin_op);
             }
 
             AstNode::Assignment {
                 identifier,
-                index,
+                indices,
+                fields,
+                operator,
                 expression,
+                ..
             } => {
                 self.visit(expression);
-                let mem_loc = self.get_memory_location(identifier);
+                let operation = operator.kind.compound_operator().map(binary_instruction);
+
+                if !fields.is_empty() || self.value_size(expression) > 1 {
+                    // Whole structs and their fields live at a fixed offset
+                    if let Some((mem_loc, field_type)) = self.field_location(identifier, fields) {
+                        if let Some(operation) = operation {
+                            self.add_instruction(Instruction::PushFromStack(mem_loc));
+                            self.add_instruction(operation);
+                        }
+                        let size = self.type_size(&field_type);
+                        self.add_store_values(mem_loc, size);
+                    }
+                } else if let Some(mem_loc) = self.get_memory_location(identifier) {
+                    self.add_offset(identifier, indices);
 
-                if let Some(mem_loc) = mem_loc {
-                    if let Some(index) = index {
-                        self.visit(index);
+                    if let Some(operation) = operation {
+                        if indices.is_empty() {
+                            self.add_instruction(Instruction::PushFromStack(mem_loc));
+                            self.add_instruction(operation);
+                        } else {
+                            // The offset is needed twice, so it is kept in a
+                            // temporary frame in between
+                            let offset = MemoryLocation {
+                                stack_level: 0,
+                                frame_index: 0,
+                            };
+                            let element = MemoryLocation {
+                                stack_level: mem_loc.stack_level + 1,
+                                ..mem_loc
+                            };
+                            self.add_instruction(Instruction::PushIntValue(1));
+                            self.add_instruction(Instruction::NewFrame);
+                            self.add_instruction(Instruction::PushIntValue(0));
+                            self.add_instruction(Instruction::PushIntValue(0));
+                            self.add_instruction(Instruction::Store);
+
+                            self.add_instruction(Instruction::PushFromStack(offset));
+                            self.add_instruction(Instruction::PushOffsetFromOpS(element));
+                            self.add_instruction(operation);
+                            self.add_instruction(Instruction::PushFromStack(offset));
+
+                            self.add_instruction(Instruction::PushIntValue(element.frame_index));
+                            self.add_instruction(Instruction::Add);
+                            self.add_instruction(Instruction::PushIntValue(element.stack_level));
+                            self.add_instruction(Instruction::Store);
+                            self.add_instruction(Instruction::PopFrame);
+                            return self.instr_ptr;
+                        }
                     }
 
                     self.add_instruction(Instruction::PushIntValue(mem_loc.frame_index));
-                    if index.is_some() {
+                    if !indices.is_empty() {
                         self.add_instruction(Instruction::Add);
                     }
                     self.add_instruction(Instruction::PushIntValue(mem_loc.stack_level));
@@ -423,52 +991,96 @@ impl Visitor<usize> for PArIRWriter {
                 left,
                 operator,
                 right,
+                ..
+            } if matches!(operator.kind, TokenKind::And | TokenKind::Or) => {
+                // The right operand is only evaluated if the left one does not
+                // decide the result on its own
+                self.visit(left);
+                if operator.kind == TokenKind::And {
+                    self.add_instruction(Instruction::Not);
+                }
+                let jump_to_result = self.add_instruction(Instruction::PushIntValue(0));
+                self.add_instruction(Instruction::JumpIfNotZero);
+
+                self.visit(right);
+                let jump_to_end = self.add_instruction(Instruction::PushIntValue(0));
+                self.add_instruction(Instruction::Jump);
+
+                self.patch_jumps(&[jump_to_result], self.instr_ptr);
+                self.add_instruction(Instruction::PushIntValue(match operator.kind {
+                    TokenKind::Or => 1,
+                    _ => 0,
+                }));
+                self.patch_jumps(&[jump_to_end], self.instr_ptr);
+            }
+
+            AstNode::BinOp {
+                left,
+                operator,
+                right,
+                ..
             } => {
                 self.visit(right);
                 self.visit(left);
 
-                self.add_instruction(match operator.kind {
-                    TokenKind::Plus => Instruction::Add,
-                    TokenKind::Minus => Instruction::Sub,
-                    TokenKind::Multiply => Instruction::Mul,
-                    TokenKind::Mod => Instruction::Mod,
-                    TokenKind::Divide => Instruction::Div,
-                    TokenKind::EqEq => Instruction::Equal,
-                    TokenKind::LessThan => Instruction::LessThan,
-                    TokenKind::LessThanEqual => Instruction::LessThanOrEqual,
-                    TokenKind::GreaterThan => Instruction::GreaterThan,
-                    TokenKind::GreaterThanEqual => Instruction::GreaterThanOrEqual,
-                    TokenKind::And => Instruction::And,
-                    TokenKind::Or => Instruction::Or,
-                    _ => Instruction::NoOperation,
-                });
+                // There is no `neq`, so `!=` is the negation of `eq`
+                if operator.kind == TokenKind::NotEqual {
+                    self.add_instruction(Instruction::Equal);
+                    self.add_instruction(Instruction::Not);
+                } else {
+                    self.add_instruction(binary_instruction(operator.kind));
+                }
             }
 
-            AstNode::UnaryOp { operator, expr } => {
+            AstNode::Conditional {
+                condition,
+                if_true,
+                if_false,
+                ..
+            } => {
+                self.visit(condition);
+
+                let jump_to_true = self.add_instruction(Instruction::PushOffsetFromPC(0));
+                self.add_instruction(Instruction::JumpIfNotZero);
+
+                self.visit(if_false);
+                let jump_to_end = self.add_instruction(Instruction::PushOffsetFromPC(0));
+                self.add_instruction(Instruction::Jump);
+
+                self.patch_jumps(&[jump_to_true], self.instr_ptr);
+                self.visit(if_true);
+                self.patch_jumps(&[jump_to_end], self.instr_ptr);
+            }
+
+            AstNode::UnaryOp { operator, expr, .. } => {
                 self.visit(expr);
 
                 match operator.kind {
-                    TokenKind::Minus => self.add_instruction(Instruction::Sub),
+                    TokenKind::Minus => {
+                        // `sub` takes the top of the stack as its left operand
+                        self.add_instruction(Instruction::PushIntValue(0));
+                        self.add_instruction(Instruction::Sub)
+                    }
                     TokenKind::Not => self.add_instruction(Instruction::Not),
                     _ => unreachable!(),
                 };
             }
 
-            AstNode::PadWidth => {
+            AstNode::PadWidth { .. } => {
                 self.add_instruction(Instruction::Width);
             }
 
-            AstNode::PadRandI { upper_bound } => {
+            AstNode::PadRandI { upper_bound, .. } => {
                 self.visit(upper_bound);
 
                 self.add_instruction(Instruction::RandInt);
             }
 
-            AstNode::PadHeight => {
+            AstNode::PadHeight { .. } => {
                 self.add_instruction(Instruction::Height);
             }
 
-            AstNode::PadRead { x, y } => {
+            AstNode::PadRead { x, y, .. } => {
                 self.visit(y);
                 self.visit(x);
 
@@ -497,23 +1109,28 @@ impl Visitor<usize> for PArIRWriter {
                 self.add_instruction(Instruction::PushIntValue(colour as usize));
             }
 
-            AstNode::Delay { expression } => {
+            AstNode::Delay { expression, .. } => {
                 self.visit(expression);
                 self.add_instruction(Instruction::Delay);
             }
 
-            AstNode::Return { expression } => {
+            AstNode::Return { expression, .. } => {
                 self.visit(expression);
 
                 self.add_instruction(Instruction::Return);
             }
 
+            AstNode::Break { .. } => self.add_loop_jump(true),
+
+            AstNode::Continue { .. } => self.add_loop_jump(false),
+
             AstNode::PadWriteBox {
                 loc_x,
                 loc_y,
                 width,
                 height,
                 colour,
+                ..
             } => {
                 self.visit(colour);
                 self.visit(height);
@@ -528,6 +1145,7 @@ impl Visitor<usize> for PArIRWriter {
                 loc_x,
                 loc_y,
                 colour,
+                ..
             } => {
                 self.visit(colour);
                 self.visit(loc_y);
@@ -540,14 +1158,21 @@ impl Visitor<usize> for PArIRWriter {
                 condition,
                 if_true,
                 if_false,
+                ..
             } => {
                 self.visit(condition);
 
                 let jump_to_true = self.add_instruction(Instruction::PushOffsetFromPC(0));
                 self.add_instruction(Instruction::JumpIfNotZero);
 
-                if let Some(if_false) = if_false {
-                    self.visit_unscoped_block(if_false);
+                match if_false.as_deref() {
+                    Some(AstNode::If { .. }) => {
+                        self.visit(if_false.as_ref().unwrap());
+                    }
+                    Some(if_false) => {
+                        self.visit_unscoped_block(if_false);
+                    }
+                    None => {}
                 }
 
                 let jump_to_end = self.add_instruction(Instruction::PushOffsetFromPC(
@@ -565,17 +1190,66 @@ impl Visitor<usize> for PArIRWriter {
                     Instruction::PushOffsetFromPC(self.instr_ptr as i32 - jump_to_end as i32);
             }
 
+            AstNode::Match {
+                scrutinee,
+                arms,
+                default,
+                ..
+            } => {
+                self.visit(scrutinee);
+
+                // The scrutinee is kept in a frame of its own while it is
+                // compared, which every arm closes before it runs
+                let scrutinee = MemoryLocation {
+                    stack_level: 0,
+                    frame_index: 0,
+                };
+                self.add_instruction(Instruction::PushIntValue(1));
+                self.add_instruction(Instruction::NewFrame);
+                self.add_instruction(Instruction::PushIntValue(0));
+                self.add_instruction(Instruction::PushIntValue(0));
+                self.add_instruction(Instruction::Store);
+
+                let mut jumps_to_arms = vec![];
+                for (literal, _) in arms {
+                    self.visit(literal);
+                    self.add_instruction(Instruction::PushFromStack(scrutinee));
+                    self.add_instruction(Instruction::Equal);
+                    jumps_to_arms.push(self.add_instruction(Instruction::PushIntValue(0)));
+                    self.add_instruction(Instruction::JumpIfNotZero);
+                }
+
+                let mut jumps_to_end = vec![];
+                self.add_instruction(Instruction::PopFrame);
+                if let Some(default) = default {
+                    self.visit_unscoped_block(default);
+                }
+                jumps_to_end.push(self.add_instruction(Instruction::PushIntValue(0)));
+                self.add_instruction(Instruction::Jump);
+
+                for ((_, block), jump) in arms.iter().zip(jumps_to_arms) {
+                    self.patch_jumps(&[jump], self.instr_ptr);
+                    self.add_instruction(Instruction::PopFrame);
+                    self.visit_unscoped_block(block);
+                    jumps_to_end.push(self.add_instruction(Instruction::PushIntValue(0)));
+                    self.add_instruction(Instruction::Jump);
+                }
+
+                self.patch_jumps(&jumps_to_end, self.instr_ptr);
+            }
+
             AstNode::For {
                 initializer,
                 condition,
                 increment,
                 body,
+                ..
             } => {
                 self.push_scope();
                 let push_var_count_placeholder = self.add_instruction(Instruction::PushIntValue(0));
                 self.add_instruction(Instruction::NewFrame);
                 self.stack_level += 1;
-                self.frame_index = 0;
+                let frame_index = std::mem::take(&mut self.frame_index);
 
                 if let Some(initializer) = initializer {
                     self.visit(initializer);
@@ -587,7 +1261,15 @@ impl Visitor<usize> for PArIRWriter {
 
                 let jump_to_end_placeholder = self.add_instruction(Instruction::PushIntValue(0));
                 self.add_instruction(Instruction::JumpIfNotZero);
+
+                self.loops.push(LoopJumps {
+                    stack_level: self.stack_level,
+                    breaks: vec![],
+                    continues: vec![],
+                });
                 self.visit_unscoped_block(body);
+                let loop_jumps = self.loops.pop().unwrap();
+                self.patch_jumps(&loop_jumps.continues, self.instr_ptr);
 
                 if let Some(increment) = increment {
                     self.visit(increment);
@@ -604,14 +1286,18 @@ impl Visitor<usize> for PArIRWriter {
                 let pop = self.add_instruction(Instruction::PopFrame);
                 self.program.main[jump_to_end_placeholder] =
                     Instruction::PushOffsetFromPC(pop as i32 - jump_to_end_placeholder as i32);
+                self.patch_jumps(&loop_jumps.breaks, pop);
                 self.pop_scope();
                 self.stack_level -= 1;
+                self.frame_index = frame_index;
             }
 
-            AstNode::While { condition, body } => {
+            AstNode::While {
+                condition, body, ..
+            } => {
                 self.push_scope();
                 self.stack_level += 1;
-                self.frame_index = 0;
+                let frame_index = std::mem::take(&mut self.frame_index);
 
                 let var_count_push = self.add_instruction(Instruction::PushIntValue(0));
                 self.add_instruction(Instruction::NewFrame);
@@ -622,7 +1308,16 @@ impl Visitor<usize> for PArIRWriter {
                 let jump_to_end = self.add_instruction(Instruction::PushIntValue(0));
 
                 self.add_instruction(Instruction::JumpIfNotZero);
+
+                self.loops.push(LoopJumps {
+                    stack_level: self.stack_level,
+                    breaks: vec![],
+                    continues: vec![],
+                });
                 self.visit_unscoped_block(body);
+                let loop_jumps = self.loops.pop().unwrap();
+                self.patch_jumps(&loop_jumps.continues, before_condition);
+
                 self.add_instruction(Instruction::PushOffsetFromPC(
                     before_condition as i32 - self.instr_ptr as i32,
                 ));
@@ -635,62 +1330,33 @@ impl Visitor<usize> for PArIRWriter {
                 let pop = self.add_instruction(Instruction::PopFrame);
                 self.program.main[jump_to_end] =
                     Instruction::PushOffsetFromPC(pop as i32 - jump_to_end as i32);
+                self.patch_jumps(&loop_jumps.breaks, pop);
+                self.frame_index = frame_index;
 
                 self.pop_scope();
             }
 
-            AstNode::Print { expression } => {
+            AstNode::Print { expression, .. } => {
                 self.visit(expression);
 
-                if let AstNode::Expression { expr, .. } = expression.as_ref() {
-                    match expr.as_ref() {
-                        AstNode::FunctionCall { identifier, .. } => {
-                            let return_type =
-                                self.find_symbol(identifier).unwrap().symbol_type.clone();
-
-                            match return_type {
-                                SymbolType::Function(signature) => match signature.return_type {
-                                    Type::Array(_, s) => {
-                                        self.add_instruction(Instruction::PushIntValue(s));
-                                        self.add_instruction(Instruction::PrintArray);
-                                    }
-                                    _ => {
-                                        self.add_instruction(Instruction::Print);
-                                    }
-                                },
-                                _ => {
-                                    self.add_instruction(Instruction::Print);
-                                }
-                            }
-                        }
-
-                        AstNode::Identifier { token } => {
-                            let symbol = self.find_symbol(token).unwrap();
-
-                            match symbol.symbol_type {
-                                SymbolType::Array(_, s) => {
-                                    self.add_instruction(Instruction::PushIntValue(s));
-                                    self.add_instruction(Instruction::PrintArray);
-                                }
-                                _ => {
-                                    self.add_instruction(Instruction::Print);
-                                }
-                            }
-                        }
-
-                        _ => {
-                            self.add_instruction(Instruction::Print);
-                        }
+                match self.value_size(expression) {
+                    1 => self.add_instruction(Instruction::Print),
+                    size => {
+                        self.add_instruction(Instruction::PushIntValue(size));
+                        self.add_instruction(Instruction::PrintArray)
                     }
-                }
+                };
             }
 
-            AstNode::PadClear { expr } => {
+            AstNode::PadClear { expr, .. } => {
                 self.visit(expr);
                 self.add_instruction(Instruction::Clear);
             }
 
-            AstNode::EndOfFile => {}
+            // Flattened into its elements by the array declaration
+            AstNode::ArrayLiteral { .. } => unreachable!(),
+
+            AstNode::EndOfFile { .. } => {}
         }
         self.instr_ptr
     }
@@ -716,7 +1382,7 @@ mod tests {
         let tokens = lexer.lex().unwrap();
 
         let mut parser = Parser::new(&tokens, Path::new(""));
-        let ast = parser.parse()?;
+        let ast = parser.parse().unwrap();
 
         let mut scope_checker = PArIRWriter::new();
         scope_checker.visit(ast);
@@ -724,6 +1390,18 @@ mod tests {
         Ok(())
     }
 
+    /// Writes `input` and returns the instructions of its main function.
+    fn write_main(input: &str) -> Vec<Instruction> {
+        let mut lexer: Lexer<SimpleBuffer> = Lexer::new(input, Path::new(""), None);
+        let tokens = lexer.lex().unwrap();
+        let ast = Parser::new(&tokens, Path::new("")).parse().unwrap().clone();
+
+        let mut writer = PArIRWriter::new();
+        writer.visit(&ast);
+
+        writer.program.main
+    }
+
     #[rstest]
     fn test_symbol_table() {
         let mut symbol_table = SymbolTable::new();
@@ -768,4 +1446,63 @@ mod tests {
 
         assert!(run_scope_checker(input).is_ok());
     }
+
+    #[rstest]
+    fn test_constants_are_inlined() {
+        let input = "const W: int = 4; const H: int = W + 3; let x: int = H; __print -H;";
+        let main = write_main(input);
+
+        // Only `x` takes up a slot in the frame
+        assert_eq!(
+            main[1..3],
+            [Instruction::PushIntValue(1), Instruction::NewFrame]
+        );
+        assert_eq!(
+            main.iter()
+                .filter(|i| **i == Instruction::PushIntValue(7))
+                .count(),
+            2
+        );
+    }
+
+    #[rstest]
+    fn test_float_constants_are_float_pushes() {
+        let input = "const F: float = 2.0; __print F;";
+        let main = write_main(input);
+
+        assert!(main.contains(&Instruction::PushFloatValue(2.0)));
+        assert!(!main.contains(&Instruction::PushIntValue(2)));
+    }
+
+    #[rstest]
+    fn test_not_equal_negates_equality() {
+        let input = "__print 1 != 2;";
+        let main = write_main(input);
+
+        let equal = main.iter().position(|i| *i == Instruction::Equal).unwrap();
+        assert_eq!(
+            main[equal..equal + 2],
+            [Instruction::Equal, Instruction::Not]
+        );
+    }
+
+    #[rstest]
+    fn test_negative_constants_are_negated_ints() {
+        let input = "const N: int = -2; __print N;";
+        let main = write_main(input);
+
+        let push = main
+            .iter()
+            .position(|i| *i == Instruction::PushIntValue(2))
+            .unwrap();
+        assert_eq!(
+            main[push..push + 4],
+            [
+                Instruction::PushIntValue(2),
+                Instruction::PushIntValue(0),
+                Instruction::Sub,
+                Instruction::Print
+            ]
+        );
+    }
 }
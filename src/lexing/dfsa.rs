@@ -159,7 +159,7 @@ impl DfsaBuilder {
         next_state
     }
 
-    pub fn transition(&mut self) -> Transition {
+    pub fn transition(&mut self) -> Transition<'_> {
         self.max_state += 1;
         Transition::new(self)
     }
@@ -226,9 +226,13 @@ impl DfsaBuilder {
             Some(TokenKind::Comment),
         );
 
-        // If we see a single slash after the first slash, then we're in a single line comment
+        // If we see a single slash after the first slash, then we're in a single line comment,
+        // which is final so that a comment on the last line of a file needs no newline
         let single_line_comment_state =
-            self.auto_add_transition(slash_state, Category::Slash, None, None);
+            self.auto_add_transition(slash_state, Category::Slash, None, Some(TokenKind::Comment));
+
+        // The end of the file ends the comment too, and is not part of it
+        self.auto_add_transition(single_line_comment_state, Category::Eof, Some(-2), None);
 
         // In which we accept any character
         self.auto_add_transition(
@@ -337,12 +341,15 @@ impl DfsaBuilder {
             .goes_to(TokenKind::GreaterThanEqual)
             .done();
 
-        self.transition()
-            .to([Category::Equals])
-            .goes_to(TokenKind::Equals)
-            .to([Category::Equals])
-            .goes_to(TokenKind::EqEq)
-            .done();
+        let equals_state =
+            self.auto_add_transition(0, Category::Equals, None, Some(TokenKind::Equals));
+        self.auto_add_transition(equals_state, Category::Equals, None, Some(TokenKind::EqEq));
+        self.auto_add_transition(
+            equals_state,
+            Category::GreaterThan,
+            None,
+            Some(TokenKind::FatArrow),
+        );
 
         self.transition()
             .to([Category::Exclamation])
@@ -353,6 +360,32 @@ impl DfsaBuilder {
         self
     }
 
+    /// Adds `+=`, `-=`, `*=`, `/=`, `%=`, `++` and `--`. These carry on from
+    /// the states of the single character operators, so this has to be called
+    /// after those have been added.
+    pub fn add_compound_assignment_ops(&mut self) -> &mut Self {
+        for (category, token_kind) in [
+            (Category::Plus, TokenKind::PlusEquals),
+            (Category::Minus, TokenKind::MinusEquals),
+            (Category::Asterisk, TokenKind::MultiplyEquals),
+            (Category::Slash, TokenKind::DivideEquals),
+            (Category::Percent, TokenKind::ModEquals),
+        ] {
+            let state = self.transition_table[&(0, category)];
+            self.auto_add_transition(state, Category::Equals, None, Some(token_kind));
+        }
+
+        for (category, token_kind) in [
+            (Category::Plus, TokenKind::PlusPlus),
+            (Category::Minus, TokenKind::MinusMinus),
+        ] {
+            let state = self.transition_table[&(0, category)];
+            self.auto_add_transition(state, category, None, Some(token_kind));
+        }
+
+        self
+    }
+
     pub fn build(&mut self) -> Dfsa {
         Dfsa::new(
             self.accepted_states.clone(),
@@ -374,7 +407,7 @@ impl std::fmt::Display for DfsaBuilder {
             .map(|((a, b), c)| (*a, *b, *c))
             .collect();
 
-        sorted_transitions.sort_by(|a, b| a.2.cmp(&b.2));
+        sorted_transitions.sort_by_key(|a| a.2);
 
         for (a, b, c) in sorted_transitions {
             writeln!(f, "({}, {:?}) -> {}", a, b, c)?;
@@ -46,10 +46,12 @@ impl<B: Stream + Clone> Lexer<B> {
                         (',', Category::Comma, TokenKind::Comma),
                         ('\0', Category::Eof, TokenKind::EndOfFile),
                         ('%', Category::Percent, TokenKind::Mod),
+                        ('.', Category::Period, TokenKind::Dot),
                     ])
                     .add_whitespace_logic()
                     .add_comment_functionality()
                     .add_multi_char_rel_ops()
+                    .add_compound_assignment_ops()
                     .add_identifier_logic()
                     .add_number_logic()
                     .build();
@@ -77,15 +79,21 @@ impl<B: Stream + Clone> Lexer<B> {
             "__write" => TokenKind::PadWrite,
             "and" => TokenKind::And,
             "as" => TokenKind::As,
+            "break" => TokenKind::Break,
+            "const" => TokenKind::Const,
+            "continue" => TokenKind::Continue,
             "else" => TokenKind::Else,
             "for" => TokenKind::For,
             "fun" => TokenKind::Function,
             "if" => TokenKind::If,
             "int" | "float" | "bool" | "colour" => TokenKind::Type,
             "let" => TokenKind::Let,
+            "match" => TokenKind::Match,
             "not" => TokenKind::Not,
             "or" => TokenKind::Or,
             "return" => TokenKind::Return,
+            "struct" => TokenKind::Struct,
+            "then" => TokenKind::Then,
             "true" | "false" => TokenKind::BoolLiteral,
             "while" => TokenKind::While,
             _ => TokenKind::Identifier,
@@ -137,36 +145,45 @@ impl<B: Stream + Clone> Lexer<B> {
                 text_span,
             )),
             false => {
+                // The offending character is skipped so that lexing can go on
+                let skipped = self.buffer.next_char();
                 let error = match prev_state {
-                    _ if self.dfsa.is_accepting(&prev_state) => LexicalError::InvalidCharacter(
-                        TextSpan::new(start_line, start_line, start_col, start_col, &lexeme),
-                    ),
+                    _ if self.dfsa.is_accepting(&prev_state) || lexeme.is_empty() => {
+                        LexicalError::InvalidCharacter(TextSpan::new(
+                            start_line,
+                            start_line,
+                            start_col,
+                            start_col + 1,
+                            &skipped.to_string(),
+                        ))
+                    }
                     _ => LexicalError::InvalidCharacter(TextSpan::new(
                         start_line, end_line, start_col, end_col, &lexeme,
                     )),
                 };
-                self.buffer.next_char();
                 Err(Error::Lexical(error))
             }
         }
     }
 
     pub fn lex(&mut self) -> Result<Vec<Token>, Vec<Error>> {
+        self.lex_with_trivia().map(|tokens| {
+            tokens
+                .into_iter()
+                .filter(|token| !token.kind.is_trivia())
+                .collect()
+        })
+    }
+
+    /// Like [`Lexer::lex`], but keeps whitespace, newlines and comments in the
+    /// token stream, so that the source can be laid out again around them.
+    pub fn lex_with_trivia(&mut self) -> Result<Vec<Token>, Vec<Error>> {
         let mut tokens = Vec::new();
         let mut errors = Vec::new();
 
         loop {
-            let token = self.next_token();
-
-            match token {
-                Ok(token) => {
-                    if token.kind != TokenKind::Whitespace
-                        && token.kind != TokenKind::Comment
-                        && token.kind != TokenKind::Newline
-                    {
-                        tokens.push(token);
-                    }
-                }
+            match self.next_token() {
+                Ok(token) => tokens.push(token),
                 Err(err) => errors.push(err),
             }
 
@@ -208,4 +225,78 @@ mod tests {
 
         assert_matches!(tokens, Ok(tokens) => tokens);
     }
+
+    #[rstest]
+    fn test_lex_with_trivia() {
+        let input = "let x: int = 1; // one\n\n/* two */";
+        let mut lexer: Lexer<SimpleBuffer> = Lexer::new(input, Path::new("fake_path"), None);
+        let tokens = lexer.lex_with_trivia().unwrap();
+
+        let comments = tokens
+            .iter()
+            .filter(|token| token.kind == TokenKind::Comment)
+            .map(|token| token.span.lexeme.trim_end())
+            .collect::<Vec<_>>();
+        assert_eq!(comments, ["// one", "/* two */"]);
+        assert!(tokens.iter().any(|token| token.kind == TokenKind::Newline));
+
+        let mut lexer: Lexer<SimpleBuffer> = Lexer::new(input, Path::new("fake_path"), None);
+        let tokens = lexer.lex().unwrap();
+        assert!(tokens.iter().all(|token| !token.kind.is_trivia()));
+    }
+
+    #[rstest]
+    fn test_line_comment_at_eof() {
+        let input = "let x: int = 1; // last";
+        let mut lexer: Lexer<SimpleBuffer> = Lexer::new(input, Path::new("fake_path"), None);
+        let tokens = lexer.lex_with_trivia().unwrap();
+
+        assert_matches!(
+            tokens.as_slice(),
+            [.., comment, eof] if comment.span.lexeme == "// last" && eof.kind == TokenKind::EndOfFile
+        );
+    }
+
+    #[rstest]
+    #[case("p.x", &[TokenKind::Identifier, TokenKind::Dot, TokenKind::Identifier])]
+    #[case("1.5", &[TokenKind::FloatLiteral])]
+    #[case("a.b.c", &[TokenKind::Identifier, TokenKind::Dot, TokenKind::Identifier, TokenKind::Dot, TokenKind::Identifier])]
+    #[case("struct P", &[TokenKind::Struct, TokenKind::Identifier])]
+    #[case("break; continue;", &[TokenKind::Break, TokenKind::Semicolon, TokenKind::Continue, TokenKind::Semicolon])]
+    #[case("breaker", &[TokenKind::Identifier])]
+    #[case("a+=1 b-=1 c*=1 d/=1 e%=1", &[
+        TokenKind::Identifier, TokenKind::PlusEquals, TokenKind::IntLiteral,
+        TokenKind::Identifier, TokenKind::MinusEquals, TokenKind::IntLiteral,
+        TokenKind::Identifier, TokenKind::MultiplyEquals, TokenKind::IntLiteral,
+        TokenKind::Identifier, TokenKind::DivideEquals, TokenKind::IntLiteral,
+        TokenKind::Identifier, TokenKind::ModEquals, TokenKind::IntLiteral,
+    ])]
+    #[case("i++ j-- -> a/b // c", &[
+        TokenKind::Identifier, TokenKind::PlusPlus, TokenKind::Identifier, TokenKind::MinusMinus,
+        TokenKind::Arrow, TokenKind::Identifier, TokenKind::Divide, TokenKind::Identifier,
+    ])]
+    #[case("match (x) { 1 => {} }", &[TokenKind::Match, TokenKind::LParen, TokenKind::Identifier, TokenKind::RParen, TokenKind::LBrace, TokenKind::IntLiteral, TokenKind::FatArrow, TokenKind::LBrace, TokenKind::RBrace, TokenKind::RBrace])]
+    #[case("const W: int = 2;", &[TokenKind::Const, TokenKind::Identifier, TokenKind::Colon, TokenKind::Type, TokenKind::Equals, TokenKind::IntLiteral, TokenKind::Semicolon])]
+    #[case("if x then y else z", &[TokenKind::If, TokenKind::Identifier, TokenKind::Then, TokenKind::Identifier, TokenKind::Else, TokenKind::Identifier])]
+    #[case("x==y x=y", &[TokenKind::Identifier, TokenKind::EqEq, TokenKind::Identifier, TokenKind::Identifier, TokenKind::Equals, TokenKind::Identifier])]
+    fn test_keywords(#[case] input: &str, #[case] expected: &[TokenKind]) {
+        let mut lexer: Lexer<SimpleBuffer> = Lexer::new(input, Path::new("fake_path"), None);
+        let tokens = lexer.lex().unwrap();
+
+        let kinds = tokens.iter().map(|token| token.kind).collect::<Vec<_>>();
+        assert_eq!(kinds[..kinds.len() - 1], *expected);
+    }
+
+    #[rstest]
+    fn test_invalid_character() {
+        let input = "let x: int = 1 $;";
+        let mut lexer: Lexer<SimpleBuffer> = Lexer::new(input, Path::new("fake_path"), None);
+        let errors = lexer.lex().unwrap_err();
+
+        assert_matches!(
+            errors.as_slice(),
+            [Error::Lexical(LexicalError::InvalidCharacter(span))]
+                if span.lexeme == "$" && (span.from_col, span.to_col) == (16, 17)
+        );
+    }
 }
@@ -0,0 +1,206 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    core::{AstNode, TextSpan, Token},
+    lexing::Lexer,
+    parsing::Parser,
+    semantics::{
+        utils::{SymbolType, Type},
+        visitors::{Reference, SemanticAnalyser},
+    },
+    utils::{diagnostics::Diagnostic, SimpleBuffer},
+};
+
+/// A `fun` declaration, as listed by the document symbols request.
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub identifier: Token,
+    /// The span of the whole declaration
+    pub span: TextSpan,
+    pub detail: String,
+}
+
+/// An open source file along with the results of analysing it.
+pub struct Document {
+    path: PathBuf,
+    text: String,
+    diagnostics: Vec<Diagnostic>,
+    references: Vec<Reference>,
+    functions: Vec<Function>,
+}
+
+impl Document {
+    pub fn new(path: &Path, text: String) -> Self {
+        let mut document = Document {
+            path: path.to_path_buf(),
+            text,
+            diagnostics: vec![],
+            references: vec![],
+            functions: vec![],
+        };
+
+        document.analyze();
+        document
+    }
+
+    pub fn update(&mut self, text: String) {
+        self.text = text;
+        self.analyze();
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    pub fn functions(&self) -> &[Function] {
+        &self.functions
+    }
+
+    /// Returns the reference under the 1-based `line` and `col`.
+    pub fn reference_at(&self, line: usize, col: usize) -> Option<&Reference> {
+        self.references
+            .iter()
+            .find(|r| r.span.from_line == line && r.span.from_col <= col && col < r.span.to_col)
+    }
+
+    /// Runs the whole front end over the text. Symbols are only updated when
+    /// the text parses, so that hovers keep working while it is being edited.
+    fn analyze(&mut self) {
+        let mut lexer: Lexer<SimpleBuffer> = Lexer::new(&self.text, &self.path, None);
+        let tokens = match lexer.lex() {
+            Ok(tokens) => tokens,
+            Err(errors) => {
+                self.diagnostics = errors.iter().map(Diagnostic::from).collect();
+                return;
+            }
+        };
+
+        let mut parser = Parser::new(&tokens, &self.path);
+        let ast = match parser.parse() {
+            Ok(ast) => ast,
+            Err(errors) => {
+                self.diagnostics = errors.iter().map(Diagnostic::from).collect();
+                return;
+            }
+        };
+
+        let mut analyser = SemanticAnalyser::new();
+        let result = analyser.analyze(ast);
+
+        self.diagnostics = result
+            .warnings
+            .iter()
+            .map(|w| Diagnostic::from(w).into_warning())
+            .chain(result.errors.iter().map(Diagnostic::from))
+            .collect();
+        self.references = result.references.clone();
+
+        self.functions = vec![];
+        collect_functions(ast, &mut self.functions);
+    }
+}
+
+/// Describes the symbol that `reference` points to, e.g. `let x: int`.
+pub fn describe(reference: &Reference) -> String {
+    let name = &reference.span.lexeme;
+
+    match &reference.symbol_type {
+        SymbolType::Variable(t) => format!("let {}: {}", name, t),
+        SymbolType::Array(t, dimensions) => {
+            format!("let {}: {}", name, Type::array(t.clone(), dimensions))
+        }
+        SymbolType::Function(signature) => {
+            // The analyser stores the parameters in reverse
+            let params = signature
+                .parameters
+                .iter()
+                .rev()
+                .map(|(t, name)| format!("{}: {}", name, t))
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            format!("fun {}({}) -> {}", name, params, signature.return_type)
+        }
+        SymbolType::Struct(fields) => {
+            let fields = fields
+                .iter()
+                .map(|(name, t)| format!("{}: {}", name, t))
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            format!("struct {} {{ {} }}", name, fields)
+        }
+        SymbolType::Constant(t, _) => format!("const {}: {}", name, t),
+    }
+}
+
+fn collect_functions(node: &AstNode, functions: &mut Vec<Function>) {
+    match node {
+        AstNode::Program { statements, .. } | AstNode::Block { statements, .. } => {
+            for statement in statements {
+                collect_functions(statement, functions);
+            }
+        }
+        AstNode::FunctionDecl {
+            identifier,
+            params,
+            return_type,
+            return_dimensions,
+            block,
+            ..
+        } => {
+            let return_type = return_dimensions
+                .iter()
+                .fold(return_type.to_string(), |t, size| {
+                    format!("{}[{}]", t, size)
+                });
+            let params = params
+                .iter()
+                .filter_map(|param| match param {
+                    AstNode::FormalParam {
+                        identifier,
+                        param_type,
+                        dimensions,
+                        ..
+                    } => Some(
+                        dimensions
+                            .iter()
+                            .fold(format!("{}: {}", identifier, param_type), |param, size| {
+                                format!("{}[{}]", param, size)
+                            }),
+                    ),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            functions.push(Function {
+                identifier: identifier.clone(),
+                span: node.span().clone(),
+                detail: format!("fun {}({}) -> {}", identifier, params, return_type),
+            });
+
+            collect_functions(block, functions);
+        }
+        AstNode::If {
+            if_true, if_false, ..
+        } => {
+            collect_functions(if_true, functions);
+            if let Some(if_false) = if_false {
+                collect_functions(if_false, functions);
+            }
+        }
+        AstNode::Match { arms, default, .. } => {
+            for (_, block) in arms {
+                collect_functions(block, functions);
+            }
+            if let Some(default) = default {
+                collect_functions(default, functions);
+            }
+        }
+        AstNode::For { body, .. } | AstNode::While { body, .. } => {
+            collect_functions(body, functions)
+        }
+        _ => {}
+    }
+}
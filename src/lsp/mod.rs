@@ -0,0 +1,5 @@
+mod document;
+mod server;
+mod transport;
+
+pub use server::Server;
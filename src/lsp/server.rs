@@ -0,0 +1,430 @@
+use std::{
+    collections::HashMap,
+    io::{BufRead, Write},
+    path::PathBuf,
+};
+
+use serde_json::{json, Value};
+
+use crate::{
+    core::TextSpan,
+    lsp::{
+        document::{describe, Document},
+        transport::{read_message, write_message},
+    },
+    utils::{
+        diagnostics::{Diagnostic, Severity},
+        Result,
+    },
+};
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// LSP symbol kind of functions
+const FUNCTION_KIND: u32 = 12;
+
+/// A language server speaking LSP over a pair of streams, usually stdin and
+/// stdout. Documents are synced in full on every change.
+pub struct Server<R: BufRead, W: Write> {
+    reader: R,
+    writer: W,
+    documents: HashMap<String, Document>,
+}
+
+impl<R: BufRead, W: Write> Server<R, W> {
+    pub fn new(reader: R, writer: W) -> Self {
+        Server {
+            reader,
+            writer,
+            documents: HashMap::new(),
+        }
+    }
+
+    /// Serves messages until the client sends `exit` or closes the input.
+    pub fn run(&mut self) -> Result<()> {
+        while let Some(message) = read_message(&mut self.reader)? {
+            let method = message["method"].as_str().unwrap_or_default();
+            if method == "exit" {
+                break;
+            }
+
+            let params = &message["params"];
+            match message.get("id") {
+                Some(id) => {
+                    let response = match self.handle_request(method, params) {
+                        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                        Err((code, message)) => json!({
+                            "jsonrpc": "2.0",
+                            "id": id,
+                            "error": { "code": code, "message": message },
+                        }),
+                    };
+                    write_message(&mut self.writer, &response)?;
+                }
+                None => self.handle_notification(method, params)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle_request(
+        &mut self,
+        method: &str,
+        params: &Value,
+    ) -> std::result::Result<Value, (i64, String)> {
+        match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                    "documentSymbolProvider": true,
+                },
+                "serverInfo": { "name": "parlc", "version": env!("CARGO_PKG_VERSION") },
+            })),
+            "shutdown" => Ok(Value::Null),
+            "textDocument/hover" => {
+                let (document, line, col) = self.position(params)?;
+
+                Ok(match document.reference_at(line, col) {
+                    Some(reference) => json!({
+                        "contents": {
+                            "kind": "markdown",
+                            "value": format!("```parl\n{}\n```", describe(reference)),
+                        },
+                        "range": range(&reference.span),
+                    }),
+                    None => Value::Null,
+                })
+            }
+            "textDocument/definition" => {
+                let (document, line, col) = self.position(params)?;
+                let uri = &params["textDocument"]["uri"];
+
+                Ok(document
+                    .reference_at(line, col)
+                    .and_then(|reference| reference.declaration.as_ref())
+                    .map_or(
+                        Value::Null,
+                        |declaration| json!({ "uri": uri, "range": range(declaration) }),
+                    ))
+            }
+            "textDocument/documentSymbol" => {
+                let document = self.document(params)?;
+
+                let symbols = document
+                    .functions()
+                    .iter()
+                    .map(|function| {
+                        json!({
+                            "name": function.identifier.span.lexeme,
+                            "detail": function.detail,
+                            "kind": FUNCTION_KIND,
+                            "range": range(&function.span),
+                            "selectionRange": range(&function.identifier.span),
+                        })
+                    })
+                    .collect();
+
+                Ok(Value::Array(symbols))
+            }
+            _ => Err((METHOD_NOT_FOUND, format!("Unknown method '{}'.", method))),
+        }
+    }
+
+    fn handle_notification(&mut self, method: &str, params: &Value) -> Result<()> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                let document = Document::new(&uri_to_path(uri), text.to_string());
+                self.documents.insert(uri.to_string(), document);
+            }
+            "textDocument/didChange" => {
+                // Full sync, so the last change holds the whole text
+                let text = params["contentChanges"]
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str());
+
+                match (self.documents.get_mut(uri), text) {
+                    (Some(document), Some(text)) => document.update(text.to_string()),
+                    _ => return Ok(()),
+                }
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+            }
+            _ => return Ok(()),
+        }
+
+        self.publish_diagnostics(uri)
+    }
+
+    fn publish_diagnostics(&mut self, uri: &str) -> Result<()> {
+        let diagnostics = self
+            .documents
+            .get(uri)
+            .map(|document| document.diagnostics().iter().map(to_lsp).collect())
+            .unwrap_or_default();
+
+        let notification = json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": uri, "diagnostics": Value::Array(diagnostics) },
+        });
+
+        Ok(write_message(&mut self.writer, &notification)?)
+    }
+
+    fn document(&self, params: &Value) -> std::result::Result<&Document, (i64, String)> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+
+        self.documents
+            .get(uri)
+            .ok_or((INVALID_PARAMS, format!("Unknown document '{}'.", uri)))
+    }
+
+    /// Returns the document and the 1-based line and column of a text
+    /// document position request.
+    fn position(
+        &self,
+        params: &Value,
+    ) -> std::result::Result<(&Document, usize, usize), (i64, String)> {
+        let document = self.document(params)?;
+
+        let position = &params["position"];
+        match (position["line"].as_u64(), position["character"].as_u64()) {
+            (Some(line), Some(character)) => {
+                Ok((document, line as usize + 1, character as usize + 1))
+            }
+            _ => Err((INVALID_PARAMS, "Missing position.".to_string())),
+        }
+    }
+}
+
+/// Converts a span to an LSP range, whose lines and characters are 0-based.
+fn range(span: &TextSpan) -> Value {
+    json!({
+        "start": {
+            "line": span.from_line.saturating_sub(1),
+            "character": span.from_col.saturating_sub(1),
+        },
+        "end": {
+            "line": span.to_line.saturating_sub(1),
+            "character": span.to_col.saturating_sub(1),
+        },
+    })
+}
+
+fn to_lsp(diagnostic: &Diagnostic) -> Value {
+    let message = match &diagnostic.help {
+        Some(help) => format!("{}\nhelp: {}", diagnostic.message, help),
+        None => diagnostic.message.clone(),
+    };
+
+    let range = match &diagnostic.span {
+        Some(span) => range(span),
+        None => range(&TextSpan::new(1, 1, 1, 1, "")),
+    };
+
+    json!({
+        "range": range,
+        "severity": match diagnostic.severity {
+            Severity::Error => 1,
+            Severity::Warning => 2,
+        },
+        "code": diagnostic.code,
+        "source": "parlc",
+        "message": message,
+    })
+}
+
+/// Turns a `file://` URI into a path, decoding any percent-encoded bytes.
+fn uri_to_path(uri: &str) -> PathBuf {
+    let path = uri.strip_prefix("file://").unwrap_or(uri);
+
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut rest = path.as_bytes();
+
+    while let Some((&byte, tail)) = rest.split_first() {
+        let decoded = match (byte, tail) {
+            (b'%', [hi, lo, ..]) => std::str::from_utf8(&[*hi, *lo])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+
+        match decoded {
+            Some(decoded) => {
+                bytes.push(decoded);
+                rest = &tail[2..];
+            }
+            None => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    const URI: &str = "file:///tmp/main.parl";
+
+    /// Feeds `messages` to a server and returns everything it sent back.
+    fn serve(messages: &[Value]) -> Vec<Value> {
+        let mut input = Vec::new();
+        for message in messages {
+            write_message(&mut input, message).unwrap();
+        }
+
+        let mut output = Vec::new();
+        Server::new(input.as_slice(), &mut output).run().unwrap();
+
+        let mut reader = output.as_slice();
+        let mut responses = Vec::new();
+        while let Some(response) = read_message(&mut reader).unwrap() {
+            responses.push(response);
+        }
+        responses
+    }
+
+    fn open(text: &str) -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": {
+                "textDocument": { "uri": URI, "languageId": "parl", "version": 1, "text": text },
+            },
+        })
+    }
+
+    fn request(id: u64, method: &str, line: u64, character: u64) -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": {
+                "textDocument": { "uri": URI },
+                "position": { "line": line, "character": character },
+            },
+        })
+    }
+
+    const SOURCE: &str = "\
+fun double(x: int) -> int {
+    return x * 2;
+}
+
+let y: int = double(4);
+__print y;
+";
+
+    #[rstest]
+    fn test_initialize() {
+        let responses = serve(&[json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "initialize",
+            "params": { "capabilities": {} },
+        })]);
+
+        assert_eq!(responses[0]["id"], 1);
+        assert_eq!(
+            responses[0]["result"]["capabilities"]["hoverProvider"],
+            true
+        );
+    }
+
+    #[rstest]
+    fn test_diagnostics() {
+        let change = json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didChange",
+            "params": {
+                "textDocument": { "uri": URI, "version": 2 },
+                "contentChanges": [{ "text": "let x: int = 1;" }],
+            },
+        });
+
+        let responses = serve(&[open("__print x;"), change]);
+
+        let diagnostics = &responses[0]["params"]["diagnostics"];
+        assert_eq!(diagnostics[0]["code"], "E0201");
+        assert_eq!(diagnostics[0]["severity"], 1);
+        assert_eq!(
+            diagnostics[0]["range"],
+            json!({ "start": { "line": 0, "character": 8 }, "end": { "line": 0, "character": 9 } })
+        );
+        assert_eq!(responses[1]["params"]["diagnostics"], json!([]));
+    }
+
+    #[rstest]
+    fn test_hover_and_definition() {
+        let responses = serve(&[
+            open(SOURCE),
+            request(1, "textDocument/hover", 4, 14),
+            request(2, "textDocument/hover", 5, 8),
+            request(3, "textDocument/definition", 5, 8),
+            request(4, "textDocument/hover", 5, 2),
+        ]);
+
+        assert_eq!(
+            responses[1]["result"]["contents"]["value"],
+            "```parl\nfun double(x: int) -> int\n```"
+        );
+        assert_eq!(
+            responses[2]["result"]["contents"]["value"],
+            "```parl\nlet y: int\n```"
+        );
+        assert_eq!(responses[3]["result"]["uri"], URI);
+        assert_eq!(
+            responses[3]["result"]["range"]["start"],
+            json!({ "line": 4, "character": 4 })
+        );
+        assert_eq!(responses[4]["result"], Value::Null);
+    }
+
+    #[rstest]
+    fn test_document_symbols() {
+        let responses = serve(&[
+            open(SOURCE),
+            json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "textDocument/documentSymbol",
+                "params": { "textDocument": { "uri": URI } },
+            }),
+        ]);
+
+        let symbols = responses[1]["result"].as_array().unwrap();
+        assert_eq!(symbols.len(), 1);
+        assert_eq!(symbols[0]["name"], "double");
+        assert_eq!(symbols[0]["detail"], "fun double(x: int) -> int");
+        assert_eq!(symbols[0]["kind"], FUNCTION_KIND);
+    }
+
+    #[rstest]
+    fn test_unknown_method() {
+        let responses = serve(&[json!({ "jsonrpc": "2.0", "id": 7, "method": "foo/bar" })]);
+
+        assert_eq!(responses[0]["error"]["code"], METHOD_NOT_FOUND);
+    }
+
+    #[rstest]
+    #[case("file:///tmp/main.parl", "/tmp/main.parl")]
+    #[case("file:///tmp/my%20file.parl", "/tmp/my file.parl")]
+    #[case("file:///tmp/100%", "/tmp/100%")]
+    fn test_uri_to_path(#[case] uri: &str, #[case] path: &str) {
+        assert_eq!(uri_to_path(uri), PathBuf::from(path));
+    }
+}
@@ -0,0 +1,86 @@
+use std::io::{BufRead, Error, ErrorKind, Result, Write};
+
+use serde_json::Value;
+
+/// Reads the next message, framed by a `Content-Length` header, returning
+/// `None` once the input is closed.
+pub fn read_message<R: BufRead>(reader: &mut R) -> Result<Option<Value>> {
+    let mut content_length = None;
+
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                content_length = Some(
+                    value
+                        .trim()
+                        .parse()
+                        .map_err(|e| Error::new(ErrorKind::InvalidData, e))?,
+                );
+            }
+        }
+    }
+
+    let Some(content_length) = content_length else {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "message without a Content-Length header",
+        ));
+    };
+
+    let mut content = vec![0; content_length];
+    reader.read_exact(&mut content)?;
+
+    serde_json::from_slice(&content)
+        .map(Some)
+        .map_err(|e| Error::new(ErrorKind::InvalidData, e))
+}
+
+pub fn write_message<W: Write>(writer: &mut W, message: &Value) -> Result<()> {
+    let content = message.to_string();
+
+    write!(
+        writer,
+        "Content-Length: {}\r\n\r\n{}",
+        content.len(),
+        content
+    )?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use serde_json::json;
+
+    #[rstest]
+    fn test_round_trip() {
+        let message = json!({ "jsonrpc": "2.0", "method": "exit" });
+
+        let mut buffer = Vec::new();
+        write_message(&mut buffer, &message).unwrap();
+        write_message(&mut buffer, &message).unwrap();
+
+        let mut reader = buffer.as_slice();
+        assert_eq!(read_message(&mut reader).unwrap(), Some(message.clone()));
+        assert_eq!(read_message(&mut reader).unwrap(), Some(message));
+        assert_eq!(read_message(&mut reader).unwrap(), None);
+    }
+
+    #[rstest]
+    fn test_missing_content_length() {
+        let mut reader = "Content-Type: foo\r\n\r\n{}".as_bytes();
+
+        assert!(read_message(&mut reader).is_err());
+    }
+}
@@ -1,20 +1,32 @@
 mod core;
 mod generation;
 mod lexing;
+mod lsp;
 mod parsing;
+mod repl;
 mod semantics;
 mod utils;
+mod vm;
 
-use clap::{command, Parser as ClapParser, Subcommand};
+use clap::{Args, Parser as ClapParser, Subcommand};
 use console::style;
-use std::{io::Write, path::PathBuf};
+use std::{
+    io::{IsTerminal, Write},
+    path::{Path, PathBuf},
+};
 use utils::SimpleBuffer;
 
 use crate::{
     core::Visitor,
+    generation::{assembler::assemble, instructions::Program},
     lexing::Lexer,
     parsing::Parser,
-    semantics::visitors::{Formatter, SemanticAnalyser, TreePrinter},
+    semantics::visitors::{Formatter, SemanticAnalyser, SemanticResult, Style, TreePrinter},
+    utils::{
+        diagnostics::{Diagnostic, MessageFormat},
+        explanations,
+    },
+    vm::{ImageFormat, VirtualMachine},
 };
 
 #[derive(ClapParser)]
@@ -22,6 +34,9 @@ use crate::{
 struct Cli {
     #[command(subcommand)]
     subcmd: Commands,
+    /// How errors and warnings are reported.
+    #[clap(long, global = true, value_enum, default_value_t = MessageFormat::Human)]
+    message_format: MessageFormat,
 }
 
 #[derive(Subcommand)]
@@ -40,12 +55,20 @@ enum Commands {
         #[clap(name = "file")]
         in_file: PathBuf,
     },
-    /// Runs the PArL formatter on the given file.
+    /// Runs the PArL formatter on the given file and prints the result.
     #[clap(name = "fmt")]
     Format {
         /// The file to format.
         #[clap(name = "file")]
         in_file: PathBuf,
+        /// Writes the formatted source back to the file.
+        #[clap(long, conflicts_with = "check")]
+        write: bool,
+        /// Exits with an error if the file is not formatted, without changing it.
+        #[clap(long)]
+        check: bool,
+        #[command(flatten)]
+        style: StyleArgs,
     },
     /// Runs the PArL semantic analyzer on the given file.
     #[clap(name = "sem")]
@@ -63,18 +86,137 @@ enum Commands {
         #[clap(short, long)]
         /// The PArIR output file.
         output: Option<PathBuf>,
+        /// Checks array indices at runtime, halting on one that is out of range.
+        #[clap(long)]
+        bounds_checks: bool,
+    },
+    /// Compiles the given file and executes it on the built-in PArIR VM.
+    /// Files with a `.parir` extension are run as PArIR.
+    #[clap(name = "run")]
+    Run {
+        /// The PArL source file to run.
+        #[clap(name = "file")]
+        in_file: PathBuf,
+        /// The width of the pad.
+        #[clap(long, default_value_t = 36, value_parser = clap::value_parser!(u32).range(1..))]
+        width: u32,
+        /// The height of the pad.
+        #[clap(long, default_value_t = 36, value_parser = clap::value_parser!(u32).range(1..))]
+        height: u32,
+        /// Dumps the final frame to this .png or .ppm file.
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+        /// Dumps a frame into this directory on every `__delay`.
+        #[clap(long)]
+        frames: Option<PathBuf>,
+        /// The image format of the frames dumped on every `__delay`.
+        #[clap(long, value_enum, default_value_t = ImageFormat::Png)]
+        frame_format: ImageFormat,
+        /// Checks array indices at runtime, halting on one that is out of range.
+        #[clap(long)]
+        bounds_checks: bool,
+    },
+    /// Prints a longer description of an error or warning code.
+    #[clap(name = "explain")]
+    Explain {
+        /// The code to explain, e.g. `E0201`.
+        code: String,
     },
+    /// Starts a language server speaking LSP over stdin and stdout.
+    #[clap(name = "lsp")]
+    Lsp,
+    /// Starts an interactive session that runs statements as they are entered.
+    #[clap(name = "repl")]
+    Repl,
+}
+
+/// Formatter style options, which override the ones in `parlfmt.toml`.
+#[derive(Args)]
+struct StyleArgs {
+    /// Indent with tabs rather than spaces.
+    #[clap(long)]
+    hard_tabs: Option<bool>,
+    /// The width of one indent level, in spaces. Indents with spaces unless
+    /// `--hard-tabs true` is also given.
+    #[clap(long)]
+    indent_width: Option<usize>,
+    /// Statements wider than this have their argument lists wrapped.
+    #[clap(long)]
+    max_width: Option<usize>,
+    /// Put spaces around symbolic binary operators.
+    #[clap(long)]
+    spaces_around_operators: Option<bool>,
+    /// The blank lines around top-level function declarations.
+    #[clap(long)]
+    blank_lines_between_functions: Option<usize>,
+}
+
+impl StyleArgs {
+    fn apply(&self, style: &mut Style) {
+        if let Some(indent_width) = self.indent_width {
+            style.indent_width = indent_width;
+            style.hard_tabs = false;
+        }
+        if let Some(hard_tabs) = self.hard_tabs {
+            style.hard_tabs = hard_tabs;
+        }
+        if let Some(max_width) = self.max_width {
+            style.max_width = max_width;
+        }
+        if let Some(spaces) = self.spaces_around_operators {
+            style.spaces_around_operators = spaces;
+        }
+        if let Some(blank_lines) = self.blank_lines_between_functions {
+            style.blank_lines_between_functions = Some(blank_lines);
+        }
+    }
 }
 
 fn main() {
     let cli = Cli::parse();
 
+    if let Commands::Explain { code } = &cli.subcmd {
+        match explanations::explain(code) {
+            Some(explanation) => println!("{}", explanation),
+            None => {
+                let msg = style("error: unknown code").red().bold().for_stderr();
+                eprintln!("{} `{}`...", msg, style(code).cyan());
+                std::process::exit(1);
+            }
+        }
+        std::process::exit(0);
+    }
+
+    if let Commands::Lsp = &cli.subcmd {
+        let stdin = std::io::stdin();
+        let mut server = lsp::Server::new(stdin.lock(), std::io::stdout());
+        if let Err(e) = server.run() {
+            let msg = style("error:").red().bold().for_stderr();
+            eprintln!("{} {}", msg, e);
+            std::process::exit(1);
+        }
+        std::process::exit(0);
+    }
+
+    if let Commands::Repl = &cli.subcmd {
+        let stdin = std::io::stdin();
+        let prompt = stdin.is_terminal();
+        if let Err(e) = repl::Repl::new(std::io::stdout()).run(stdin.lock(), prompt) {
+            let msg = style("error:").red().bold().for_stderr();
+            eprintln!("{} {}", msg, e);
+            std::process::exit(1);
+        }
+        std::process::exit(0);
+    }
+
     let in_file = match &cli.subcmd {
         Commands::Lexer { in_file } => in_file,
-        Commands::Format { in_file } => in_file,
+        Commands::Format { in_file, .. } => in_file,
         Commands::Semantic { in_file } => in_file,
         Commands::Parse { in_file } => in_file,
         Commands::Compile { in_file, .. } => in_file,
+        Commands::Run { in_file, .. } => in_file,
+        Commands::Explain { .. } | Commands::Lsp | Commands::Repl => unreachable!(),
     };
 
     if !in_file.exists() {
@@ -103,20 +245,43 @@ fn main() {
     //         Commands::Semantic { .. } => "Analyzing",
     //         Commands::Parse { .. } => "Printing",
     //         Commands::Compile { .. } => "Compiling",
+    //         Commands::Run { .. } => "Running",
     //     })
     //     .green()
     //     .bold(),
     //     style(in_file.display())
     // );
 
+    // PArIR files are assembled and run as they are, skipping the compiler
+    if let Commands::Run { .. } = &cli.subcmd {
+        if in_file.extension().is_some_and(|ext| ext == "parir") {
+            match assemble(&input) {
+                Ok(program) => run(&program, &cli.subcmd),
+                Err(e) => {
+                    report(
+                        e.iter().map(Diagnostic::from),
+                        in_file,
+                        &input,
+                        cli.message_format,
+                    );
+                    std::process::exit(1);
+                }
+            }
+            std::process::exit(0);
+        }
+    }
+
     let mut lexer: Lexer<SimpleBuffer> = Lexer::new(&input, in_file, None);
 
     let tokens = match lexer.lex() {
         Ok(tokens) => tokens,
         Err(e) => {
-            for err in e {
-                eprintln!("{}", err);
-            }
+            report(
+                e.iter().map(Diagnostic::from),
+                in_file,
+                &input,
+                cli.message_format,
+            );
             std::process::exit(1);
         }
     };
@@ -130,21 +295,68 @@ fn main() {
             std::process::exit(0);
         }
 
-        Commands::Format { in_file } => {
+        Commands::Format {
+            in_file,
+            write,
+            check,
+            style: style_args,
+        } => {
             let mut parser = Parser::new(&tokens, in_file);
             let ast = parser.parse();
 
             match ast {
                 Ok(ast) => {
-                    let mut printer = Formatter::new(in_file);
-                    printer.visit(ast).unwrap();
-                    println!(
-                        "{} formatted successfully.",
-                        style(in_file.display()).cyan()
-                    );
+                    // Lexing already succeeded once, so it cannot fail here
+                    let trivia = Lexer::<SimpleBuffer>::new(&input, in_file, None)
+                        .lex_with_trivia()
+                        .unwrap_or_default();
+                    let mut fmt_style = match Style::find(in_file) {
+                        Ok(fmt_style) => fmt_style,
+                        Err(e) => {
+                            let msg = style("error:").red().bold().for_stderr();
+                            eprintln!("{} {}", msg, e);
+                            std::process::exit(1);
+                        }
+                    };
+                    style_args.apply(&mut fmt_style);
+
+                    let formatted = Formatter::new(&trivia, fmt_style).format(ast).unwrap();
+
+                    if *check {
+                        if formatted != input {
+                            let msg = style("error: file is not formatted")
+                                .red()
+                                .bold()
+                                .for_stderr();
+                            eprintln!("{} `{}`", msg, style(in_file.display()).cyan());
+                            std::process::exit(1);
+                        }
+                    } else if *write {
+                        if formatted != input {
+                            if let Err(e) = std::fs::write(in_file, &formatted) {
+                                let msg = style("error: could not write file")
+                                    .red()
+                                    .bold()
+                                    .for_stderr();
+                                eprintln!("{} `{}`: {}", msg, style(in_file.display()).cyan(), e);
+                                std::process::exit(1);
+                            }
+                        }
+                        println!(
+                            "{} formatted successfully.",
+                            style(in_file.display()).cyan()
+                        );
+                    } else {
+                        print!("{}", formatted);
+                    }
                 }
                 Err(e) => {
-                    eprintln!("{}", e);
+                    report(
+                        e.iter().map(Diagnostic::from),
+                        in_file,
+                        &input,
+                        cli.message_format,
+                    );
                     std::process::exit(1);
                 }
             }
@@ -159,23 +371,17 @@ fn main() {
                     let mut sem_analyzer = SemanticAnalyser::new();
                     let result = sem_analyzer.analyze(ast);
 
-                    if result.has_warnings() {
-                        for warn in &result.warnings {
-                            eprintln!("{}", warn);
-                        }
-                    }
-
-                    if result.has_errors() {
-                        for err in &result.errors {
-                            eprintln!("{}", err);
-                        }
-                        std::process::exit(1);
-                    }
+                    report_semantic(result, file, &input, cli.message_format);
 
                     println!("{} analyzed successfully.", style(file.display()).cyan());
                 }
                 Err(e) => {
-                    eprintln!("{}", e);
+                    report(
+                        e.iter().map(Diagnostic::from),
+                        in_file,
+                        &input,
+                        cli.message_format,
+                    );
                     std::process::exit(1);
                 }
             }
@@ -191,20 +397,38 @@ fn main() {
                     printer.visit(ast).unwrap();
                 }
                 Err(e) => {
-                    eprintln!("{}", e);
+                    report(
+                        e.iter().map(Diagnostic::from),
+                        in_file,
+                        &input,
+                        cli.message_format,
+                    );
+
+                    // Still show whatever the parser managed to recover
+                    let mut printer = TreePrinter::new();
+                    printer.visit(parser.root()).unwrap();
                     std::process::exit(1);
                 }
             }
         }
 
-        Commands::Compile { in_file, output } => {
+        Commands::Compile {
+            in_file,
+            output,
+            bounds_checks,
+        } => {
             let mut parser = Parser::new(&tokens, in_file);
             let ast = parser.parse();
 
             let ast = match ast {
                 Ok(ast) => ast,
                 Err(e) => {
-                    eprintln!("{}", e);
+                    report(
+                        e.iter().map(Diagnostic::from),
+                        in_file,
+                        &input,
+                        cli.message_format,
+                    );
                     std::process::exit(1);
                 }
             };
@@ -212,24 +436,9 @@ fn main() {
             let mut sem_analyzer = SemanticAnalyser::new();
             let result = sem_analyzer.analyze(ast);
 
-            // Print "warninngs" in yellow
-            let warnings_yellow = style("warning").yellow().bold();
-            let errors_red = style("errors").red().bold();
-
-            if result.has_warnings() {
-                for warn in &result.warnings {
-                    eprintln!("{}: {}", warnings_yellow, warn);
-                }
-            }
-
-            if result.has_errors() {
-                for err in &result.errors {
-                    eprintln!("{}: {}", errors_red, err);
-                }
-                std::process::exit(1);
-            }
+            report_semantic(result, in_file, &input, cli.message_format);
 
-            let mut gen = generation::PArIRWriter::new();
+            let mut gen = generation::PArIRWriter::new().with_bounds_checks(*bounds_checks);
             let par_ir_instr = gen.get_program(ast);
 
             if let Some(output) = output {
@@ -239,11 +448,15 @@ fn main() {
                     .unwrap();
             } else {
                 println!("{}", par_ir_instr);
-                eprintln!(
-                    "{} compiled {}.",
-                    style(in_file.display()).cyan(),
-                    style("successfully").green()
-                );
+
+                // Keep stderr machine-readable
+                if cli.message_format == MessageFormat::Human {
+                    eprintln!(
+                        "{} compiled {}.",
+                        style(in_file.display()).cyan(),
+                        style("successfully").green()
+                    );
+                }
             }
 
             if let Some(output) = output {
@@ -254,5 +467,127 @@ fn main() {
                 );
             }
         }
+
+        Commands::Run {
+            in_file,
+            bounds_checks,
+            ..
+        } => {
+            let mut parser = Parser::new(&tokens, in_file);
+
+            let ast = match parser.parse() {
+                Ok(ast) => ast,
+                Err(e) => {
+                    report(
+                        e.iter().map(Diagnostic::from),
+                        in_file,
+                        &input,
+                        cli.message_format,
+                    );
+                    std::process::exit(1);
+                }
+            };
+
+            let mut sem_analyzer = SemanticAnalyser::new();
+            let result = sem_analyzer.analyze(ast);
+
+            report_semantic(result, in_file, &input, cli.message_format);
+
+            let mut gen = generation::PArIRWriter::new().with_bounds_checks(*bounds_checks);
+            run(gen.get_program(ast), &cli.subcmd);
+        }
+
+        Commands::Explain { .. } | Commands::Lsp | Commands::Repl => unreachable!(),
+    }
+}
+
+/// Prints every diagnostic to stderr, either along with the part of `source`
+/// that it points at or as JSON.
+fn report(
+    diagnostics: impl IntoIterator<Item = Diagnostic>,
+    file: &Path,
+    source: &str,
+    format: MessageFormat,
+) {
+    for diagnostic in diagnostics {
+        match format {
+            MessageFormat::Human => eprintln!("{}\n", diagnostic.render(file, source)),
+            MessageFormat::Json => eprintln!("{}", diagnostic.to_json(file)),
+        }
+    }
+}
+
+/// Reports the warnings and errors of the semantic analysis, exiting if there
+/// were any errors.
+fn report_semantic(result: &SemanticResult, file: &Path, source: &str, format: MessageFormat) {
+    if result.has_warnings() {
+        let warnings = result
+            .warnings
+            .iter()
+            .map(|w| Diagnostic::from(w).into_warning());
+        report(warnings, file, source, format);
+    }
+
+    if result.has_errors() {
+        report(
+            result.errors.iter().map(Diagnostic::from),
+            file,
+            source,
+            format,
+        );
+        std::process::exit(1);
+    }
+}
+
+/// Executes `program` on the built-in VM with the options of the `run`
+/// subcommand.
+fn run(program: &Program, subcmd: &Commands) {
+    let Commands::Run {
+        width,
+        height,
+        output,
+        frames,
+        frame_format,
+        ..
+    } = subcmd
+    else {
+        unreachable!()
+    };
+
+    let errors_red = style("errors").red().bold();
+
+    let output_format = match output {
+        Some(output) => match ImageFormat::from_path(output) {
+            Some(format) => Some(format),
+            None => {
+                let msg = style("error: unsupported image format").red().bold();
+                eprintln!("{} `{}`...", msg, style(output.display()).cyan());
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    let mut vm = VirtualMachine::new(program, std::io::stdout())
+        .with_pad_size(*width as usize, *height as usize);
+
+    if let Some(frames) = frames {
+        if let Err(e) = std::fs::create_dir_all(frames) {
+            eprintln!("{}: {}", errors_red, e);
+            std::process::exit(1);
+        }
+        vm = vm.with_frame_dump(frames.clone(), *frame_format);
+    }
+
+    if let Err(e) = vm.run() {
+        eprintln!("{}: {}", errors_red, e);
+        std::process::exit(1);
+    }
+
+    if let (Some(output), Some(format)) = (output, output_format) {
+        if let Err(e) = vm.pad().save(output, format) {
+            eprintln!("{}: {}", errors_red, e);
+            std::process::exit(1);
+        }
     }
 }
@@ -2,20 +2,19 @@ use std::path::{Path, PathBuf};
 
 use crate::{
     core::{TextSpan, Token, TokenKind},
-    semantics::utils::Type,
-    utils::{
-        errors::{Error, ParseError},
-        Result,
-    },
+    utils::errors::ParseError,
 };
 
 use crate::core::AstNode;
 
+type Result<T> = std::result::Result<T, ParseError>;
+
 pub struct Parser {
     tokens: Vec<Token>,
     source_file: PathBuf,
     current: usize,
     root: AstNode,
+    errors: Vec<ParseError>,
 }
 
 impl Parser {
@@ -24,7 +23,11 @@ impl Parser {
             tokens: tokens.to_vec(),
             current: 0,
             source_file: source_file.to_path_buf(),
-            root: AstNode::Program { statements: vec![] },
+            root: AstNode::Program {
+                statements: vec![],
+                span: TextSpan::new(1, 1, 1, 1, ""),
+            },
+            errors: vec![],
         }
     }
 
@@ -40,6 +43,20 @@ impl Parser {
         }
     }
 
+    /// Returns the span from the token at `start` up to the last token that
+    /// was consumed.
+    fn span_from(&self, start: usize) -> TextSpan {
+        let first = match self.tokens.get(start) {
+            Some(token) => token.span.clone(),
+            None => self.current_token().span,
+        };
+
+        match self.tokens.get(self.current.wrapping_sub(1)) {
+            Some(last) if self.current > start => first.merge(&last.span),
+            _ => first,
+        }
+    }
+
     fn peek_token(&self) -> Option<&Token> {
         self.tokens.get(self.current + 1)
     }
@@ -52,8 +69,7 @@ impl Parser {
                 expected: kind,
                 source_file: self.source_file.clone(),
                 found: self.current_token().clone(),
-            }
-            .into())
+            })
         }
     }
 
@@ -70,55 +86,159 @@ impl Parser {
                 source_file: self.source_file.clone(),
                 found: self.current_token(),
                 expected: possible_kinds.into_iter().collect(),
-            }
-            .into())
+            })
         } else {
             Ok(())
         }
     }
 
-    pub fn parse(&mut self) -> Result<&AstNode> {
-        self.root = self.parse_program()?;
-        Ok(&self.root)
+    /// Parses the whole token stream, recovering from errors so that every
+    /// one of them is reported at once.
+    ///
+    /// The partial program, holding every statement that parsed cleanly, is
+    /// still available through [`Parser::root`] when parsing fails.
+    pub fn parse(&mut self) -> std::result::Result<&AstNode, Vec<ParseError>> {
+        self.errors.clear();
+        self.root = self.parse_program();
+
+        match self.errors.is_empty() {
+            true => Ok(&self.root),
+            false => Err(std::mem::take(&mut self.errors)),
+        }
+    }
+
+    pub fn root(&self) -> &AstNode {
+        &self.root
+    }
+
+    /// Parses the token stream as a single expression, optionally followed
+    /// by a `;`, like the bare expressions typed at the REPL.
+    pub fn parse_bare_expression(&mut self) -> Result<AstNode> {
+        self.current = 0;
+        let expression = self.parse_expression()?;
+
+        if self.current_token().kind == TokenKind::Semicolon {
+            self.consume();
+        }
+        self.consume_if(TokenKind::EndOfFile)?;
+
+        Ok(expression)
     }
 
-    fn parse_program(&mut self) -> Result<AstNode> {
+    fn parse_program(&mut self) -> AstNode {
         let mut statements = vec![];
         while self.current < self.tokens.len() {
-            let next_statement = self.parse_statement()?;
+            let start = self.current;
 
-            if let AstNode::EndOfFile = next_statement {
-                break;
+            match self.parse_statement() {
+                Ok(AstNode::EndOfFile { .. }) => break,
+                Ok(statement) => statements.push(statement),
+                Err(e) => {
+                    self.errors.push(e);
+                    self.synchronize(start);
+
+                    // A stray closing brace has no block to end at the top level
+                    if self.current_token().kind == TokenKind::RBrace {
+                        self.consume();
+                    }
+                }
             }
+        }
+        AstNode::Program {
+            statements,
+            span: self.span_from(0),
+        }
+    }
 
-            statements.push(next_statement);
+    /// Skips tokens until the parser is somewhere a new statement can start:
+    /// just past a `;`, or right before a `}` or a statement keyword. Blocks
+    /// met along the way are skipped whole, so a broken `if` or `fun` header
+    /// does not leave its body to be parsed as loose statements.
+    ///
+    /// `start` is where the failed statement began, and at least one token
+    /// past it is always skipped so that the parser cannot get stuck.
+    fn synchronize(&mut self, start: usize) {
+        let mut depth = 0;
+
+        if self.current == start && self.current_token().kind != TokenKind::EndOfFile {
+            self.consume();
+        }
+
+        loop {
+            match self.current_token().kind {
+                TokenKind::EndOfFile => return,
+                TokenKind::LBrace => depth += 1,
+                TokenKind::RBrace if depth == 0 => return,
+                TokenKind::RBrace => depth -= 1,
+                TokenKind::Semicolon if depth == 0 => {
+                    self.consume();
+                    return;
+                }
+                TokenKind::Let
+                | TokenKind::Const
+                | TokenKind::Print
+                | TokenKind::Delay
+                | TokenKind::PadWrite
+                | TokenKind::PadWriteBox
+                | TokenKind::PadClear
+                | TokenKind::If
+                | TokenKind::Match
+                | TokenKind::For
+                | TokenKind::While
+                | TokenKind::Function
+                | TokenKind::Struct
+                | TokenKind::Return
+                | TokenKind::Break
+                | TokenKind::Continue
+                    if depth == 0 =>
+                {
+                    return
+                }
+                _ => {}
+            }
+
+            self.consume();
         }
-        Ok(AstNode::Program { statements })
     }
 
     fn parse_statement(&mut self) -> Result<AstNode> {
         self.assert_token_is_any([
             TokenKind::Let,
+            TokenKind::Const,
             TokenKind::Print,
             TokenKind::Delay,
             TokenKind::PadWrite,
             TokenKind::PadWriteBox,
             TokenKind::PadClear,
             TokenKind::If,
+            TokenKind::Match,
             TokenKind::Identifier,
             TokenKind::For,
             TokenKind::While,
             TokenKind::Function,
+            TokenKind::Struct,
             TokenKind::Return,
+            TokenKind::Break,
+            TokenKind::Continue,
             TokenKind::LBrace,
             TokenKind::EndOfFile,
         ])?;
 
         match self.current_token_kind() {
             TokenKind::Let => self.parse_var_decl(),
+            TokenKind::Const => self.parse_const_decl(),
             TokenKind::Identifier => match self.peek_token() {
                 Some(tok) => match tok.kind {
-                    TokenKind::LBracket | TokenKind::Equals => {
+                    TokenKind::LBracket
+                    | TokenKind::Dot
+                    | TokenKind::Equals
+                    | TokenKind::PlusEquals
+                    | TokenKind::MinusEquals
+                    | TokenKind::MultiplyEquals
+                    | TokenKind::DivideEquals
+                    | TokenKind::ModEquals
+                    | TokenKind::PlusPlus
+                    | TokenKind::MinusMinus => {
                         let assignment_stmnt = self.parse_assignment_statement();
                         self.consume_if(TokenKind::Semicolon)?;
                         assignment_stmnt
@@ -133,21 +253,27 @@ impl Parser {
             TokenKind::PadWriteBox => self.parse_write_box(),
             TokenKind::PadClear => self.parse_clear_statement(),
             TokenKind::If => self.parse_if(),
+            TokenKind::Match => self.parse_match(),
             TokenKind::For => self.parse_for(),
             TokenKind::While => self.parse_while(),
             TokenKind::Function => self.parse_function_decl(),
+            TokenKind::Struct => self.parse_struct_decl(),
             TokenKind::Return => self.parse_return(),
+            TokenKind::Break | TokenKind::Continue => self.parse_loop_control(),
             TokenKind::LBrace => self.parse_block(),
-            TokenKind::EndOfFile => Ok(AstNode::EndOfFile),
-            _ => Err(Error::Parse(ParseError::UnexpectedToken {
+            TokenKind::EndOfFile => Ok(AstNode::EndOfFile {
+                span: self.current_token().span,
+            }),
+            _ => Err(ParseError::UnexpectedToken {
                 expected: TokenKind::Invalid,
                 source_file: self.source_file.clone(),
                 found: self.current_token(),
-            })),
+            }),
         }
     }
 
     fn parse_function_decl(&mut self) -> Result<AstNode> {
+        let start = self.current;
         self.consume_if(TokenKind::Function)?;
         let identifier = self.consume_if(TokenKind::Identifier)?.clone();
         self.consume_if(TokenKind::LParen)?;
@@ -161,32 +287,12 @@ impl Parser {
 
         self.consume_if(TokenKind::Arrow)?;
 
-        let return_type = self.consume_if(TokenKind::Type)?.clone();
+        let return_type = self.parse_type()?;
 
-        // TODO: Add array like function array_list() -> int[] {}
-        let return_type = if let TokenKind::LBracket = self.current_token().kind {
-            self.consume_if(TokenKind::LBracket)?;
-            let size = self.consume_if(TokenKind::IntLiteral)?.clone();
-            self.consume_if(TokenKind::RBracket)?;
-            Type::Array(
-                Box::new(match return_type.span.lexeme.as_str() {
-                    "int" => Type::Int,
-                    "float" => Type::Float,
-                    "bool" => Type::Bool,
-                    "colour" => Type::Colour,
-                    _ => unreachable!(),
-                }),
-                size.span.lexeme.parse().unwrap(),
-            )
-        } else {
-            // token to type
-            match return_type.span.lexeme.as_str() {
-                "int" => Type::Int,
-                "float" => Type::Float,
-                "bool" => Type::Bool,
-                "colour" => Type::Colour,
-                _ => unreachable!(),
-            }
+        // Only arrays of the built-in types are supported
+        let return_dimensions = match return_type.kind {
+            TokenKind::Type => self.parse_dimensions()?,
+            _ => vec![],
         };
 
         let block = self.parse_block()?;
@@ -194,8 +300,10 @@ impl Parser {
         Ok(AstNode::FunctionDecl {
             identifier: identifier.clone(),
             params,
-            return_type: return_type.clone(),
+            return_type,
+            return_dimensions,
             block: Box::new(block),
+            span: self.span_from(start),
         })
     }
 
@@ -214,30 +322,127 @@ impl Parser {
     }
 
     fn parse_formal_param(&mut self) -> Result<AstNode> {
+        let start = self.current;
         let identifier = self.consume_if(TokenKind::Identifier)?.clone();
         self.consume_if(TokenKind::Colon)?;
-        let param_type = self.consume_if(TokenKind::Type)?.clone();
+        let param_type = self.parse_type()?;
 
-        let index = if let TokenKind::LBracket = self.current_token().kind {
-            self.consume_if(TokenKind::LBracket)?;
-
-            let index = self.consume_if(TokenKind::IntLiteral)?.clone();
-
-            self.consume_if(TokenKind::RBracket)?;
-
-            Some(index)
-        } else {
-            None
+        // Only arrays of the built-in types are supported
+        let dimensions = match param_type.kind {
+            TokenKind::Type => self.parse_dimensions()?,
+            _ => vec![],
         };
 
         Ok(AstNode::FormalParam {
             identifier: identifier.clone(),
             param_type,
-            length: index,
+            dimensions,
+            span: self.span_from(start),
+        })
+    }
+
+    /// Parses a built-in type, or the name of a struct.
+    fn parse_type(&mut self) -> Result<Token> {
+        self.assert_token_is_any([TokenKind::Type, TokenKind::Identifier])?;
+        Ok(self.consume().clone())
+    }
+
+    fn parse_struct_decl(&mut self) -> Result<AstNode> {
+        let start = self.current;
+        self.consume_if(TokenKind::Struct)?;
+        let identifier = self.consume_if(TokenKind::Identifier)?.clone();
+        self.consume_if(TokenKind::LBrace)?;
+
+        // At least one field, separated by commas with an optional trailing one
+        let mut fields = vec![];
+        loop {
+            let field = self.consume_if(TokenKind::Identifier)?.clone();
+            self.consume_if(TokenKind::Colon)?;
+            fields.push((field, self.parse_type()?));
+
+            if self.current_token().kind != TokenKind::Comma {
+                break;
+            }
+            self.consume();
+            if self.current_token().kind == TokenKind::RBrace {
+                break;
+            }
+        }
+
+        self.consume_if(TokenKind::RBrace)?;
+
+        Ok(AstNode::StructDecl {
+            identifier,
+            fields,
+            span: self.span_from(start),
+        })
+    }
+
+    /// Parses the `.field` path following a struct name, if there is one.
+    fn parse_fields(&mut self) -> Result<Vec<Token>> {
+        let mut fields = vec![];
+        while let TokenKind::Dot = self.current_token_kind() {
+            self.consume();
+            fields.push(self.consume_if(TokenKind::Identifier)?.clone());
+        }
+        Ok(fields)
+    }
+
+    /// Parses the `{ field: value, ... }` following a struct name in a struct
+    /// literal.
+    fn parse_struct_literal(&mut self, start: usize, identifier: Token) -> Result<AstNode> {
+        self.consume_if(TokenKind::LBrace)?;
+
+        let mut fields = vec![];
+        loop {
+            let field = self.consume_if(TokenKind::Identifier)?.clone();
+            self.consume_if(TokenKind::Colon)?;
+            fields.push((field, self.parse_expression()?));
+
+            if self.current_token().kind != TokenKind::Comma {
+                break;
+            }
+            self.consume();
+            if self.current_token().kind == TokenKind::RBrace {
+                break;
+            }
+        }
+
+        self.consume_if(TokenKind::RBrace)?;
+
+        Ok(AstNode::StructLiteral {
+            identifier,
+            fields,
+            span: self.span_from(start),
         })
     }
 
+    /// Parses the `[N]` sizes of an array type, if there are any. A size is
+    /// an int literal or the name of a constant.
+    fn parse_dimensions(&mut self) -> Result<Vec<Token>> {
+        let mut dimensions = vec![];
+        while let TokenKind::LBracket = self.current_token_kind() {
+            self.consume();
+            self.assert_token_is_any([TokenKind::IntLiteral, TokenKind::Identifier])?;
+            dimensions.push(self.consume().clone());
+            self.consume_if(TokenKind::RBracket)?;
+        }
+        Ok(dimensions)
+    }
+
+    /// Parses the `[i]` indices following an array name, one per dimension.
+    fn parse_indices(&mut self) -> Result<Vec<AstNode>> {
+        let mut indices = vec![];
+        while let TokenKind::LBracket = self.current_token_kind() {
+            self.consume();
+            indices.push(self.parse_expression()?);
+            self.consume_if(TokenKind::RBracket)?;
+        }
+        Ok(indices)
+    }
+
     fn parse_while(&mut self) -> Result<AstNode> {
+        let start = self.current;
         self.consume_if(TokenKind::While)?;
         self.consume_if(TokenKind::LParen)?;
         let condition = self.parse_expression()?;
@@ -246,10 +451,12 @@ impl Parser {
         Ok(AstNode::While {
             condition: Box::new(condition),
             body: Box::new(block),
+            span: self.span_from(start),
         })
     }
 
     fn parse_for(&mut self) -> Result<AstNode> {
+        let start = self.current;
         self.consume_if(TokenKind::For)?;
         self.consume_if(TokenKind::LParen)?;
 
@@ -259,11 +466,11 @@ impl Parser {
                 None
             }
             TokenKind::Let => Some(self.parse_var_decl()?),
-            _ => Err(Error::Parse(ParseError::UnexpectedToken {
+            _ => Err(ParseError::UnexpectedToken {
                 expected: TokenKind::Let,
                 found: self.current_token().clone(),
                 source_file: self.source_file.clone(),
-            }))?,
+            })?,
         };
 
         let condition = self.parse_expression()?;
@@ -276,11 +483,11 @@ impl Parser {
                 None
             }
             TokenKind::Identifier => Some(self.parse_assignment_statement()?),
-            _ => Err(Error::Parse(ParseError::UnexpectedToken {
+            _ => Err(ParseError::UnexpectedToken {
                 expected: TokenKind::Identifier,
                 found: self.current_token().clone(),
                 source_file: self.source_file.clone(),
-            }))?,
+            })?,
         };
 
         match increment {
@@ -295,10 +502,12 @@ impl Parser {
             condition: Box::new(condition),
             increment: increment.map(Box::new),
             body: Box::new(body),
+            span: self.span_from(start),
         })
     }
 
     fn parse_if(&mut self) -> Result<AstNode> {
+        let start = self.current;
         self.consume_if(TokenKind::If)?;
         self.consume_if(TokenKind::LParen)?;
         let condition = self.parse_expression()?;
@@ -307,11 +516,16 @@ impl Parser {
 
         if self.current_token().kind == TokenKind::Else {
             self.consume();
-            let else_block = self.parse_block()?;
+            // An `else if` is kept as an `if` in place of the else block
+            let else_block = match self.current_token().kind {
+                TokenKind::If => self.parse_if()?,
+                _ => self.parse_block()?,
+            };
             return Ok(AstNode::If {
                 condition: Box::new(condition),
                 if_true: Box::new(block),
                 if_false: Some(Box::new(else_block)),
+                span: self.span_from(start),
             });
         }
 
@@ -319,10 +533,68 @@ impl Parser {
             condition: Box::new(condition),
             if_true: Box::new(block),
             if_false: None,
+            span: self.span_from(start),
         })
     }
 
+    fn parse_match(&mut self) -> Result<AstNode> {
+        let start = self.current;
+        self.consume_if(TokenKind::Match)?;
+        self.consume_if(TokenKind::LParen)?;
+        let scrutinee = self.parse_expression()?;
+        self.consume_if(TokenKind::RParen)?;
+        self.consume_if(TokenKind::LBrace)?;
+
+        let mut arms = vec![];
+        let mut default = None;
+        while self.current_token().kind != TokenKind::RBrace {
+            // The default arm has to be the last one
+            if self.current_token().span.lexeme == "_" {
+                self.consume();
+                self.consume_if(TokenKind::FatArrow)?;
+                default = Some(Box::new(self.parse_block()?));
+                break;
+            }
+
+            let literal = self.parse_match_literal()?;
+            self.consume_if(TokenKind::FatArrow)?;
+            arms.push((literal, self.parse_block()?));
+        }
+        self.consume_if(TokenKind::RBrace)?;
+
+        Ok(AstNode::Match {
+            scrutinee: Box::new(scrutinee),
+            arms,
+            default,
+            span: self.span_from(start),
+        })
+    }
+
+    /// Parses the literal of a `match` arm, which is an integer, possibly
+    /// negated, or a colour.
+    fn parse_match_literal(&mut self) -> Result<AstNode> {
+        let start = self.current;
+        self.assert_token_is_any([
+            TokenKind::IntLiteral,
+            TokenKind::ColourLiteral,
+            TokenKind::Minus,
+        ])?;
+
+        if self.current_token().kind == TokenKind::Minus {
+            let operator = self.consume().clone();
+            let literal = self.consume_if(TokenKind::IntLiteral)?.clone();
+            return Ok(AstNode::UnaryOp {
+                operator,
+                expr: Box::new(AstNode::IntLiteral(literal)),
+                span: self.span_from(start),
+            });
+        }
+
+        self.parse_literal()
+    }
+
     fn parse_write_box(&mut self) -> Result<AstNode> {
+        let start = self.current;
         self.consume_if(TokenKind::PadWriteBox)?;
         let loc_x = self.parse_expression()?;
         self.consume_if(TokenKind::Comma)?;
@@ -342,10 +614,12 @@ impl Parser {
             width: Box::new(width),
             height: Box::new(height),
             colour: Box::new(colour),
+            span: self.span_from(start),
         })
     }
 
     fn parse_write(&mut self) -> Result<AstNode> {
+        let start = self.current;
         self.consume_if(TokenKind::PadWrite)?;
 
         let loc_x = self.parse_expression()?;
@@ -364,10 +638,12 @@ impl Parser {
             loc_x: Box::new(loc_x),
             loc_y: Box::new(loc_y),
             colour: Box::new(colour),
+            span: self.span_from(start),
         })
     }
 
     fn parse_delay(&mut self) -> Result<AstNode> {
+        let start = self.current;
         self.consume_if(TokenKind::Delay)?;
 
         let expr = self.parse_expression()?;
@@ -376,10 +652,12 @@ impl Parser {
 
         Ok(AstNode::Delay {
             expression: Box::new(expr),
+            span: self.span_from(start),
         })
     }
 
     fn parse_return(&mut self) -> Result<AstNode> {
+        let start = self.current;
         self.consume_if(TokenKind::Return)?;
 
         let expr = self.parse_expression()?;
@@ -388,138 +666,256 @@ impl Parser {
 
         Ok(AstNode::Return {
             expression: Box::new(expr),
+            span: self.span_from(start),
+        })
+    }
+
+    /// Parses a `break;` or a `continue;`.
+    fn parse_loop_control(&mut self) -> Result<AstNode> {
+        let token = self.consume().clone();
+        self.consume_if(TokenKind::Semicolon)?;
+
+        let span = token.span;
+        Ok(match token.kind {
+            TokenKind::Break => AstNode::Break { span },
+            _ => AstNode::Continue { span },
         })
     }
 
     fn parse_expression(&mut self) -> Result<AstNode> {
-        let left = self.parse_equality()?;
+        let start = self.current;
+        let expr = match self.current_token().kind {
+            TokenKind::If => self.parse_conditional()?,
+            _ => self.parse_or()?,
+        };
 
-        match self.current_token().kind {
+        let casted_type = match self.current_token().kind {
             TokenKind::As => {
                 self.consume();
-                let kind = self.consume_if(TokenKind::Type)?.clone();
-
-                Ok(AstNode::Expression {
-                    casted_type: Some(kind),
-                    expr: Box::new(left),
-                })
+                Some(self.consume_if(TokenKind::Type)?.clone())
             }
+            _ => None,
+        };
 
-            TokenKind::And | TokenKind::Or => {
-                let operator = self.consume().clone();
-                let right = self.parse_expression()?;
+        Ok(AstNode::Expression {
+            casted_type,
+            expr: Box::new(expr),
+            span: self.span_from(start),
+        })
+    }
 
-                let bin_op = AstNode::BinOp {
-                    left: Box::new(left),
-                    operator,
-                    right: Box::new(right),
-                };
+    /// Parses `if condition then a else b`. Both arms are whole expressions,
+    /// so the `else` arm extends as far to the right as it can.
+    fn parse_conditional(&mut self) -> Result<AstNode> {
+        let start = self.current;
+        self.consume_if(TokenKind::If)?;
+        let condition = self.parse_expression()?;
+        self.consume_if(TokenKind::Then)?;
+        let if_true = self.parse_expression()?;
+        self.consume_if(TokenKind::Else)?;
+        let if_false = self.parse_expression()?;
 
-                Ok(AstNode::Expression {
-                    casted_type: None,
-                    expr: Box::new(bin_op),
-                })
-            }
+        Ok(AstNode::Conditional {
+            condition: Box::new(condition),
+            if_true: Box::new(if_true),
+            if_false: Box::new(if_false),
+            span: self.span_from(start),
+        })
+    }
 
-            _ => Ok(AstNode::Expression {
-                casted_type: None,
-                expr: Box::new(left),
-            }),
+    /// Parses a chain of `or`s, which bind looser than `and`s.
+    fn parse_or(&mut self) -> Result<AstNode> {
+        let start = self.current;
+        let mut left = self.parse_and()?;
+
+        while self.current_token().kind == TokenKind::Or {
+            let operator = self.consume().clone();
+            let right = self.parse_and()?;
+            left = AstNode::BinOp {
+                left: Box::new(left),
+                operator,
+                right: Box::new(right),
+                span: self.span_from(start),
+            };
         }
-    }
 
-    fn parse_equality(&mut self) -> Result<AstNode> {
-        let left = self.parse_comparison()?;
-        let current_tok = self.current_token().clone();
+        Ok(left)
+    }
 
-        match &current_tok.kind {
-            TokenKind::EqEq | TokenKind::NotEqual => {
-                self.consume();
-                let right = self.parse_comparison()?;
-                Ok(AstNode::BinOp {
-                    left: Box::new(left),
-                    operator: current_tok,
-                    right: Box::new(right),
-                })
-            }
-            _ => Ok(left),
+    fn parse_and(&mut self) -> Result<AstNode> {
+        let start = self.current;
+        let mut left = self.parse_equality()?;
+
+        while self.current_token().kind == TokenKind::And {
+            let operator = self.consume().clone();
+            let right = self.parse_equality()?;
+            left = AstNode::BinOp {
+                left: Box::new(left),
+                operator,
+                right: Box::new(right),
+                span: self.span_from(start),
+            };
         }
+
+        Ok(left)
+    }
+
+    fn parse_equality(&mut self) -> Result<AstNode> {
+        self.parse_comparison_of(
+            Self::parse_comparison,
+            &[TokenKind::EqEq, TokenKind::NotEqual],
+        )
     }
 
     fn parse_comparison(&mut self) -> Result<AstNode> {
-        let left = self.parse_term()?;
-        let current_tok = self.current_token().clone();
-
-        match &current_tok.kind {
-            TokenKind::LessThan
-            | TokenKind::LessThanEqual
-            | TokenKind::GreaterThan
-            | TokenKind::GreaterThanEqual => {
-                self.consume();
-                let right = self.parse_term()?;
-                Ok(AstNode::BinOp {
-                    left: Box::new(left),
-                    operator: current_tok,
-                    right: Box::new(right),
-                })
-            }
-            _ => Ok(left),
+        self.parse_comparison_of(
+            Self::parse_term,
+            &[
+                TokenKind::LessThan,
+                TokenKind::LessThanEqual,
+                TokenKind::GreaterThan,
+                TokenKind::GreaterThanEqual,
+            ],
+        )
+    }
+
+    /// Parses at most one comparison with one of `operators` between two
+    /// operands parsed by `operand`. A second one, as in `a < b < c`, is
+    /// reported rather than compared against the `bool` of the first.
+    fn parse_comparison_of(
+        &mut self,
+        operand: fn(&mut Self) -> Result<AstNode>,
+        operators: &[TokenKind],
+    ) -> Result<AstNode> {
+        let start = self.current;
+        let left = operand(self)?;
+
+        if !operators.contains(&self.current_token().kind) {
+            return Ok(left);
+        }
+
+        let operator = self.consume().clone();
+        let right = operand(self)?;
+
+        if operators.contains(&self.current_token().kind) {
+            return Err(ParseError::ChainedComparison {
+                source_file: self.source_file.clone(),
+                first: operator.span.lexeme,
+                second: self.current_token(),
+            });
         }
+
+        Ok(AstNode::BinOp {
+            left: Box::new(left),
+            operator,
+            right: Box::new(right),
+            span: self.span_from(start),
+        })
     }
 
+    /// Parses a chain of `+`s and `-`s, grouped from the left so that
+    /// `10 - 3 - 2` is `(10 - 3) - 2`.
     fn parse_term(&mut self) -> Result<AstNode> {
-        let left = self.parse_factor()?;
-        let current_tok = self.current_token().clone();
-
-        match &current_tok.kind {
-            TokenKind::Plus | TokenKind::Minus => {
-                let operator = self.consume().clone();
-                let right = self.parse_term()?;
-                Ok(AstNode::BinOp {
-                    left: Box::new(left),
-                    operator,
-                    right: Box::new(right),
-                })
-            }
-            _ => Ok(left),
+        let start = self.current;
+        let mut left = self.parse_factor()?;
+
+        while matches!(
+            self.current_token().kind,
+            TokenKind::Plus | TokenKind::Minus | TokenKind::MinusMinus
+        ) {
+            let token = self.consume().clone();
+            let (operator, right) = match token.kind {
+                // `a--b` is lexed as `a`, `--`, `b` and means `a - (-b)`
+                TokenKind::MinusMinus => {
+                    (Self::split_minus(&token, 0), self.parse_negation(token, 1)?)
+                }
+                _ => (token, self.parse_factor()?),
+            };
+            left = AstNode::BinOp {
+                left: Box::new(left),
+                operator,
+                right: Box::new(right),
+                span: self.span_from(start),
+            };
         }
+
+        Ok(left)
     }
 
+    /// Parses a chain of `*`s, `/`s and `%`s, grouped from the left like
+    /// [`Parser::parse_term`].
     fn parse_factor(&mut self) -> Result<AstNode> {
-        let left = self.parse_unary()?;
-        let current_tok = self.current_token().clone();
-
-        match &current_tok.kind {
-            TokenKind::Multiply | TokenKind::Divide | TokenKind::Mod => {
-                let operator = self.consume().clone();
-                let right = self.parse_factor()?;
-                Ok(AstNode::BinOp {
-                    left: Box::new(left),
-                    operator,
-                    right: Box::new(right),
-                })
-            }
-            _ => Ok(left),
+        let start = self.current;
+        let mut left = self.parse_unary()?;
+
+        while matches!(
+            self.current_token().kind,
+            TokenKind::Multiply | TokenKind::Divide | TokenKind::Mod
+        ) {
+            let operator = self.consume().clone();
+            let right = self.parse_unary()?;
+            left = AstNode::BinOp {
+                left: Box::new(left),
+                operator,
+                right: Box::new(right),
+                span: self.span_from(start),
+            };
         }
+
+        Ok(left)
     }
 
     fn parse_unary(&mut self) -> Result<AstNode> {
+        let start = self.current;
         let curr_token = self.current_token();
 
         match curr_token.kind {
             TokenKind::Minus | TokenKind::Not => {
                 self.consume();
-                let expr = self.parse_primary()?;
+                let expr = self.parse_unary()?;
                 Ok(AstNode::UnaryOp {
                     operator: curr_token.clone(),
                     expr: Box::new(expr),
+                    span: self.span_from(start),
+                })
+            }
+            TokenKind::MinusMinus => {
+                self.consume();
+                let expr = self.parse_negation(curr_token.clone(), 1)?;
+                Ok(AstNode::UnaryOp {
+                    operator: Self::split_minus(&curr_token, 0),
+                    expr: Box::new(expr),
+                    span: self.span_from(start),
                 })
             }
             _ => self.parse_primary(),
         }
     }
 
+    /// Parses the operand of the `index`th `-` of a `--` token, which the
+    /// lexer reads as a single token but which means two negations here.
+    fn parse_negation(&mut self, minus_minus: Token, index: usize) -> Result<AstNode> {
+        let start = self.current;
+        let expr = self.parse_unary()?;
+        Ok(AstNode::UnaryOp {
+            operator: Self::split_minus(&minus_minus, index),
+            expr: Box::new(expr),
+            span: self.span_from(start),
+        })
+    }
+
+    /// The `index`th `-` of a `--` token.
+    fn split_minus(minus_minus: &Token, index: usize) -> Token {
+        let span = &minus_minus.span;
+        let col = span.from_col + index;
+        Token::new(
+            TokenKind::Minus,
+            TextSpan::new(span.from_line, span.to_line, col, col + 1, "-"),
+        )
+    }
+
     fn parse_primary(&mut self) -> Result<AstNode> {
+        let start = self.current;
         self.assert_token_is_any([
             TokenKind::Identifier,
             TokenKind::IntLiteral,
@@ -544,6 +940,7 @@ impl Parser {
                         Ok(AstNode::FunctionCall {
                             identifier: ident.clone(),
                             args: vec![],
+                            span: self.span_from(start),
                         })
                     } else {
                         let args = self.parse_actual_params()?;
@@ -551,17 +948,27 @@ impl Parser {
                         Ok(AstNode::FunctionCall {
                             identifier: ident.clone(),
                             args,
+                            span: self.span_from(start),
                         })
                     }
                 } else if let TokenKind::LBracket = self.current_token_kind() {
-                    self.consume();
-                    let index = self.parse_expression()?;
-                    self.consume_if(TokenKind::RBracket)?;
+                    let indices = self.parse_indices()?;
 
-                    return Ok(AstNode::ArrayAccess {
+                    Ok(AstNode::ArrayAccess {
                         identifier: ident.clone(),
-                        index: Box::new(index),
-                    });
+                        indices,
+                        span: self.span_from(start),
+                    })
+                } else if let TokenKind::Dot = self.current_token_kind() {
+                    let fields = self.parse_fields()?;
+
+                    Ok(AstNode::FieldAccess {
+                        identifier: ident.clone(),
+                        fields,
+                        span: self.span_from(start),
+                    })
+                } else if let TokenKind::LBrace = self.current_token_kind() {
+                    self.parse_struct_literal(start, ident)
                 } else {
                     Ok(AstNode::Identifier {
                         token: ident.clone(),
@@ -583,11 +990,13 @@ impl Parser {
     }
 
     fn parse_sub_expr(&mut self) -> Result<AstNode> {
+        let start = self.current;
         self.consume_if(TokenKind::LParen)?;
         let expr = self.parse_expression()?;
         self.consume_if(TokenKind::RParen)?;
         Ok(AstNode::SubExpression {
             bin_op: Box::new(expr),
+            span: self.span_from(start),
         })
     }
 
@@ -600,16 +1009,17 @@ impl Parser {
     }
 
     fn parse_pad_width(&mut self) -> Result<AstNode> {
-        self.consume_if(TokenKind::PadWidth)?;
-        Ok(AstNode::PadWidth)
+        let span = self.consume_if(TokenKind::PadWidth)?.span.clone();
+        Ok(AstNode::PadWidth { span })
     }
 
     fn parse_pad_height(&mut self) -> Result<AstNode> {
-        self.consume_if(TokenKind::PadHeight)?;
-        Ok(AstNode::PadHeight)
+        let span = self.consume_if(TokenKind::PadHeight)?.span.clone();
+        Ok(AstNode::PadHeight { span })
     }
 
     fn parse_pad_read(&mut self) -> Result<AstNode> {
+        let start = self.current;
         self.consume_if(TokenKind::PadRead)?;
 
         let first = self.parse_expression()?;
@@ -621,10 +1031,12 @@ impl Parser {
         Ok(AstNode::PadRead {
             x: Box::new(first),
             y: Box::new(second),
+            span: self.span_from(start),
         })
     }
 
     fn parse_pad_rand_i(&mut self) -> Result<AstNode> {
+        let start = self.current;
         self.consume_if(TokenKind::PadRandI)?;
 
         let upper_bound = self.parse_expression()?;
@@ -633,17 +1045,43 @@ impl Parser {
 
         Ok(AstNode::PadRandI {
             upper_bound: Box::new(upper_bound),
+            span: self.span_from(start),
+        })
+    }
+
+    fn parse_const_decl(&mut self) -> Result<AstNode> {
+        let start = self.current;
+        self.consume_if(TokenKind::Const)?;
+        let identifier = self.consume_if(TokenKind::Identifier)?.clone();
+        self.consume_if(TokenKind::Colon)?;
+        // Only values of the built-in types can be worked out at compile time
+        let const_type = self.consume_if(TokenKind::Type)?.clone();
+        self.consume_if(TokenKind::Equals)?;
+        let expression = self.parse_expression()?;
+        self.consume_if(TokenKind::Semicolon)?;
+
+        Ok(AstNode::ConstDec {
+            identifier,
+            const_type,
+            expression: Box::new(expression),
+            span: self.span_from(start),
         })
     }
 
-    // TODO: Add array functionality
     fn parse_var_decl(&mut self) -> Result<AstNode> {
+        let start = self.current;
         self.consume_if(TokenKind::Let)?;
         let identifier = self.consume_if(TokenKind::Identifier)?.clone();
         self.consume_if(TokenKind::Colon)?;
-        let element_type = self.consume_if(TokenKind::Type)?.clone();
+        let element_type = self.parse_type()?;
 
-        self.assert_token_is_any([TokenKind::Equals, TokenKind::LBracket])?;
+        // Only arrays of the built-in types are supported
+        match element_type.kind {
+            TokenKind::Type => {
+                self.assert_token_is_any([TokenKind::Equals, TokenKind::LBracket])?
+            }
+            _ => self.assert_token_is_any([TokenKind::Equals])?,
+        }
 
         match self.current_token_kind() {
             TokenKind::Equals => {
@@ -654,20 +1092,21 @@ impl Parser {
                     identifier: identifier.clone(),
                     var_type: element_type,
                     expression: Box::new(expression),
+                    span: self.span_from(start),
                 })
             }
             TokenKind::LBracket => {
                 self.consume();
 
-                let declared_size: usize = self
-                    .consume_if(TokenKind::IntLiteral)?
-                    .clone()
-                    .span
-                    .lexeme
-                    .parse()
-                    .unwrap();
-
+                // The first size can be left out, and is then the number of elements
+                let mut dimensions = vec![match self.current_token_kind() {
+                    TokenKind::IntLiteral | TokenKind::Identifier => Some(self.consume().clone()),
+                    _ => None,
+                }];
                 self.consume_if(TokenKind::RBracket)?;
+
+                dimensions.extend(self.parse_dimensions()?.into_iter().map(Some));
+
                 self.consume_if(TokenKind::Equals)?;
                 self.consume_if(TokenKind::LBracket)?;
 
@@ -680,21 +1119,33 @@ impl Parser {
                     identifier,
                     element_type,
                     elements,
-                    size: declared_size,
+                    dimensions,
+                    span: self.span_from(start),
                 })
             }
             _ => Err(ParseError::UnexpectedTokenList {
                 expected: vec![TokenKind::Equals, TokenKind::LBracket],
                 source_file: self.source_file.clone(),
                 found: self.current_token().clone(),
-            }
-            .into()),
+            }),
         }
     }
 
     fn parse_array_elements(&mut self) -> Result<Vec<AstNode>> {
         let mut elements = vec![];
-        let first_elem = self.parse_literal()?;
+        let first_elem = match self.current_token_kind() {
+            TokenKind::LBracket => {
+                let start = self.current;
+                self.consume();
+                let elements = self.parse_array_elements()?;
+                self.consume_if(TokenKind::RBracket)?;
+                AstNode::ArrayLiteral {
+                    elements,
+                    span: self.span_from(start),
+                }
+            }
+            _ => self.parse_expression()?,
+        };
         elements.push(first_elem);
 
         if let TokenKind::Comma = self.current_token().kind {
@@ -706,25 +1157,39 @@ impl Parser {
     }
 
     fn parse_print_statement(&mut self) -> Result<AstNode> {
+        let start = self.current;
         self.consume_if(TokenKind::Print)?;
         let expression = self.parse_expression()?;
         self.consume_if(TokenKind::Semicolon)?;
         Ok(AstNode::Print {
             expression: Box::new(expression),
+            span: self.span_from(start),
         })
     }
 
     fn parse_block(&mut self) -> Result<AstNode> {
+        let start = self.current;
         let mut statements = vec![];
-        self.consume_if(TokenKind::LBrace)?;
+        let opening = self.consume_if(TokenKind::LBrace)?.clone();
         while self.current_token().kind != TokenKind::RBrace {
             if self.current_token().kind == TokenKind::EndOfFile {
-                return Err(ParseError::UnclosedBlock.into());
+                return Err(ParseError::UnclosedBlock { opening });
+            }
+
+            let statement_start = self.current;
+            match self.parse_statement() {
+                Ok(statement) => statements.push(statement),
+                Err(e) => {
+                    self.errors.push(e);
+                    self.synchronize(statement_start);
+                }
             }
-            statements.push(self.parse_statement()?);
         }
         self.current += 1;
-        Ok(AstNode::Block { statements })
+        Ok(AstNode::Block {
+            statements,
+            span: self.span_from(start),
+        })
     }
 
     fn consume(&mut self) -> &Token {
@@ -740,50 +1205,65 @@ impl Parser {
             TokenKind::FloatLiteral => Ok(AstNode::FloatLiteral(token)),
             TokenKind::BoolLiteral => Ok(AstNode::BoolLiteral(token)),
             TokenKind::ColourLiteral => Ok(AstNode::ColourLiteral(token)),
-            TokenKind::PadHeight => Ok(AstNode::PadHeight),
-            TokenKind::PadWidth => Ok(AstNode::PadWidth),
+            TokenKind::PadHeight => Ok(AstNode::PadHeight { span: token.span }),
+            TokenKind::PadWidth => Ok(AstNode::PadWidth { span: token.span }),
             TokenKind::PadRead => self.parse_pad_read(),
             _ => Err(ParseError::UnexpectedToken {
                 expected: TokenKind::Invalid,
                 source_file: self.source_file.clone(),
                 found: token.clone(),
-            }
-            .into()),
+            }),
         }
     }
 
     fn parse_assignment_statement(&mut self) -> Result<AstNode> {
+        let start = self.current;
         let identifier = self.consume_if(TokenKind::Identifier)?.clone();
 
-        if let TokenKind::LBracket = self.current_token_kind() {
-            self.consume();
-            let index = self.parse_expression()?;
-            self.consume_if(TokenKind::RBracket)?;
-            self.consume_if(TokenKind::Equals)?;
-            let expression = self.parse_expression()?;
-            Ok(AstNode::Assignment {
-                identifier: identifier.clone(),
-                index: Some(Box::new(index)),
-                expression: Box::new(expression),
-            })
-        } else {
-            self.consume_if(TokenKind::Equals)?;
-            let expression = self.parse_expression()?;
-            Ok(AstNode::Assignment {
-                identifier: identifier.clone(),
-                index: None,
-                expression: Box::new(expression),
-            })
-        }
+        let indices = self.parse_indices()?;
+        let fields = self.parse_fields()?;
+
+        self.assert_token_is_any([
+            TokenKind::Equals,
+            TokenKind::PlusEquals,
+            TokenKind::MinusEquals,
+            TokenKind::MultiplyEquals,
+            TokenKind::DivideEquals,
+            TokenKind::ModEquals,
+            TokenKind::PlusPlus,
+            TokenKind::MinusMinus,
+        ])?;
+        let operator = self.consume().clone();
+
+        let expression = match operator.kind {
+            TokenKind::PlusPlus | TokenKind::MinusMinus => {
+                let mut one = operator.clone();
+                one.kind = TokenKind::IntLiteral;
+                one.span.lexeme = "1".to_string();
+                AstNode::IntLiteral(one)
+            }
+            _ => self.parse_expression()?,
+        };
+
+        Ok(AstNode::Assignment {
+            identifier: identifier.clone(),
+            indices,
+            fields,
+            operator,
+            expression: Box::new(expression),
+            span: self.span_from(start),
+        })
     }
 
     fn parse_clear_statement(&mut self) -> Result<AstNode> {
+        let start = self.current;
         self.consume_if(TokenKind::PadClear)?;
         let expr = self.parse_expression()?;
         self.consume_if(TokenKind::Semicolon)?;
 
         Ok(AstNode::PadClear {
             expr: Box::new(expr),
+            span: self.span_from(start),
         })
     }
 
@@ -802,3 +1282,214 @@ impl Parser {
         Ok(params)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        core::Visitor, lexing::Lexer, semantics::visitors::TreePrinter, utils::SimpleBuffer,
+    };
+
+    use super::*;
+    use assert_matches::assert_matches;
+    use rstest::rstest;
+
+    fn parse(input: &str) -> (AstNode, Vec<ParseError>) {
+        let mut lexer: Lexer<SimpleBuffer> = Lexer::new(input, Path::new(""), None);
+        let tokens = lexer.lex().unwrap();
+
+        let mut parser = Parser::new(&tokens, Path::new(""));
+        let errors = parser.parse().err().unwrap_or_default();
+
+        (parser.root().clone(), errors)
+    }
+
+    fn statement_count(ast: &AstNode) -> usize {
+        match ast {
+            AstNode::Program { statements, .. } | AstNode::Block { statements, .. } => {
+                statements.len()
+            }
+            _ => panic!("expected a program or a block"),
+        }
+    }
+
+    #[rstest]
+    fn test_valid_program() {
+        let (ast, errors) = parse("let x: int = 5; __print x;");
+
+        assert!(errors.is_empty());
+        assert_eq!(statement_count(&ast), 2);
+    }
+
+    #[rstest]
+    #[case("let x: int = ; let y: int = 2; __print y;", 1, 2)]
+    #[case("let x: int = 1 let y: int = 2; __print y;", 1, 2)]
+    #[case("let x = 1; let y: int = ; __print 3;", 2, 1)]
+    #[case("} let y: int = 2;", 1, 1)]
+    #[case("if (x <) { __print 1; } __print 2;", 1, 1)]
+    #[case("fun f(x int) -> int { return x; } __print 2;", 1, 1)]
+    fn test_recovery(#[case] input: &str, #[case] error_count: usize, #[case] statements: usize) {
+        let (ast, errors) = parse(input);
+
+        assert_eq!(errors.len(), error_count);
+        assert_eq!(statement_count(&ast), statements);
+    }
+
+    #[rstest]
+    fn test_recovery_in_block() {
+        let (ast, errors) = parse("fun f() -> int { let x: int = ; return 1; } __print 2;");
+
+        assert_eq!(errors.len(), 1);
+        assert_matches!(
+            errors[0],
+            ParseError::UnexpectedTokenList { ref found, .. } if found.kind == TokenKind::Semicolon
+        );
+
+        let AstNode::Program { statements, .. } = ast else {
+            panic!("expected a program");
+        };
+        assert_eq!(statements.len(), 2);
+        assert_matches!(&statements[0], AstNode::FunctionDecl { block, .. } if statement_count(block) == 1);
+    }
+
+    #[rstest]
+    fn test_unclosed_block() {
+        let (_, errors) = parse("let x: int = 1; if (true) { __print x;");
+
+        assert_matches!(errors.as_slice(), [ParseError::UnclosedBlock { opening }] if opening.span.from_col == 27);
+    }
+
+    #[rstest]
+    #[case("__print 0 <= x < 10;", "<=", TokenKind::LessThan)]
+    #[case("__print a == b != c;", "==", TokenKind::NotEqual)]
+    fn test_chained_comparison(
+        #[case] input: &str,
+        #[case] first_lexeme: &str,
+        #[case] second_kind: TokenKind,
+    ) {
+        let (_, errors) = parse(input);
+
+        assert_matches!(
+            errors.as_slice(),
+            [ParseError::ChainedComparison { first, second, .. }]
+                if first == first_lexeme && second.kind == second_kind
+        );
+    }
+
+    #[rstest]
+    #[case("1 + 2", true)]
+    #[case("5 as float;", true)]
+    #[case("x = 5;", false)]
+    #[case("1 + 2; 3", false)]
+    fn test_parse_bare_expression(#[case] input: &str, #[case] is_expression: bool) {
+        let mut lexer: Lexer<SimpleBuffer> = Lexer::new(input, Path::new(""), None);
+        let tokens = lexer.lex().unwrap();
+
+        let mut parser = Parser::new(&tokens, Path::new(""));
+
+        assert_eq!(parser.parse_bare_expression().is_ok(), is_expression);
+    }
+
+    /// Parses an expression and prints it with [`TreePrinter`], which puts
+    /// every binary operation in parentheses.
+    fn print_expression(input: &str) -> String {
+        let mut lexer: Lexer<SimpleBuffer> = Lexer::new(input, Path::new(""), None);
+        let tokens = lexer.lex().unwrap();
+        let mut parser = Parser::new(&tokens, Path::new(""));
+        let expression = parser.parse_bare_expression().unwrap();
+
+        let mut output = Vec::new();
+        TreePrinter::with_output(&mut output)
+            .visit(&expression)
+            .unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[rstest]
+    #[case("a or b and c", "(a or (b and c))")]
+    #[case("a and b or c", "((a and b) or c)")]
+    #[case("a and b and c", "((a and b) and c)")]
+    #[case("a or b or c and d", "((a or b) or (c and d))")]
+    #[case("a == b and c", "((a == b) and c)")]
+    fn test_logical_precedence(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(print_expression(input), expected);
+    }
+
+    #[rstest]
+    #[case("10 - 3 - 2", "((10 - 3) - 2)")]
+    #[case("8 / 4 / 2", "((8 / 4) / 2)")]
+    #[case("1 + 2 - 3 + 4", "(((1 + 2) - 3) + 4)")]
+    #[case("2 * 3 % 4 / 5", "(((2 * 3) % 4) / 5)")]
+    #[case("1 + 2 * 3 - 4", "((1 + (2 * 3)) - 4)")]
+    #[case("10 - (3 - 2)", "(10 - ((3 - 2)))")]
+    fn test_left_associative_arithmetic(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(print_expression(input), expected);
+    }
+
+    #[rstest]
+    #[case("- -x", "-(-(x))")]
+    #[case("--x", "-(-(x))")]
+    #[case("not not b", "not(not(b))")]
+    #[case("-not -x", "-(not(-(x)))")]
+    #[case("a--b", "(a - -(b))")]
+    #[case("a * --b", "(a * -(-(b)))")]
+    fn test_nested_unary(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(print_expression(input), expected);
+    }
+
+    #[rstest]
+    #[case("if a then b else c", "(if a then b else c)")]
+    #[case("if a then b else c + 1", "(if a then b else (c + 1))")]
+    #[case(
+        "if a or b then 1 else if c then 2 else 3",
+        "(if (a or b) then 1 else (if c then 2 else 3))"
+    )]
+    #[case("(if a then 1 else 2) * 3", "(((if a then 1 else 2)) * 3)")]
+    fn test_conditional(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(print_expression(input), expected);
+    }
+
+    #[rstest]
+    fn test_spans() {
+        let (ast, errors) = parse(
+            "let x: int = 1;\n\
+             if (x < 2) {\n\
+             \x20   __write_box 1, 2, 3, 4, (x + 1) as colour;\n\
+             }",
+        );
+        assert!(errors.is_empty());
+
+        let AstNode::Program { statements, span } = ast else {
+            panic!("expected a program");
+        };
+        assert_eq!(
+            (span.from_line, span.from_col, span.to_line, span.to_col),
+            (1, 1, 4, 2)
+        );
+
+        let span = |node: &AstNode| {
+            let span = node.span();
+            (span.from_line, span.from_col, span.to_line, span.to_col)
+        };
+
+        assert_eq!(span(&statements[0]), (1, 1, 1, 16));
+        assert_eq!(span(&statements[1]), (2, 1, 4, 2));
+
+        let AstNode::If {
+            condition, if_true, ..
+        } = &statements[1]
+        else {
+            panic!("expected an if statement");
+        };
+        assert_eq!(span(condition), (2, 5, 2, 10));
+        assert_eq!(span(if_true), (2, 12, 4, 2));
+
+        let AstNode::Block { statements, .. } = if_true.as_ref() else {
+            panic!("expected a block");
+        };
+        let AstNode::PadWriteBox { colour, .. } = &statements[0] else {
+            panic!("expected __write_box");
+        };
+        assert_eq!(span(&statements[0]), (3, 5, 3, 47));
+        assert_eq!(span(colour), (3, 29, 3, 46));
+    }
+}
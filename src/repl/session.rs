@@ -0,0 +1,436 @@
+use std::{
+    io::{BufRead, Write},
+    path::Path,
+};
+
+use crate::{
+    core::{AstNode, Token, TokenKind, Visitor},
+    generation::{
+        instructions::{Instruction, Program},
+        PArIRWriter,
+    },
+    lexing::Lexer,
+    parsing::Parser,
+    semantics::{
+        utils::Type,
+        visitors::{SemanticAnalyser, SemanticResult, TreePrinter},
+    },
+    utils::{diagnostics::Diagnostic, Result, SimpleBuffer},
+    vm::VirtualMachine,
+};
+
+/// The name that diagnostics refer to inputs by.
+const FILE: &str = "<repl>";
+
+const HELP: &str = "\
+Enter PArL statements to run them, or a bare expression to print its value.
+Inputs that leave a block open continue on the next line.
+
+:type <expr>  prints the type of an expression
+:ast <stmt>   prints the syntax tree of a statement or expression
+:ir <stmt>    prints the PArIR generated for a statement or expression
+:help         prints this message
+:quit         leaves the REPL";
+
+/// A parsed input, which is tried as a bare expression first.
+enum Input {
+    Expression(AstNode),
+    /// A whole `Program` node
+    Statements(AstNode),
+}
+
+impl Input {
+    fn statements(&self) -> &[AstNode] {
+        match self {
+            Input::Expression(expression) => std::slice::from_ref(expression),
+            Input::Statements(AstNode::Program { statements, .. }) => statements,
+            Input::Statements(_) => unreachable!(),
+        }
+    }
+}
+
+/// An interactive session that keeps the global scope alive between inputs,
+/// both in the analyser and in the frame of the VM that runs them.
+pub struct Repl<W: Write> {
+    analyser: SemanticAnalyser,
+    writer: PArIRWriter,
+    vm: VirtualMachine<W>,
+    /// The code of every function declared so far, which each chunk is
+    /// loaded along with
+    functions: Vec<Instruction>,
+}
+
+impl<W: Write> Repl<W> {
+    pub fn new(output: W) -> Self {
+        let empty = Program {
+            functions: vec![],
+            main: vec![],
+        };
+
+        Repl {
+            analyser: SemanticAnalyser::new(),
+            writer: PArIRWriter::new(),
+            vm: VirtualMachine::new(&empty, output),
+            functions: vec![],
+        }
+    }
+
+    /// Evaluates every input read from `input` until it ends or `:quit` is
+    /// entered, showing a prompt before each line if `prompt` is set.
+    pub fn run<R: BufRead>(&mut self, mut input: R, prompt: bool) -> Result<()> {
+        let mut source = String::new();
+
+        loop {
+            if prompt {
+                let prompt = if source.is_empty() { ">> " } else { ".. " };
+                write!(self.vm.output(), "{}", prompt)?;
+                self.vm.output().flush()?;
+            }
+
+            if input.read_line(&mut source)? == 0 {
+                if prompt {
+                    writeln!(self.vm.output())?;
+                }
+                break;
+            }
+
+            if is_incomplete(&source) {
+                continue;
+            }
+
+            if !self.eval(&std::mem::take(&mut source))? {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Evaluates a single input, returning `false` once `:quit` is entered.
+    pub fn eval(&mut self, input: &str) -> Result<bool> {
+        let input = input.trim();
+
+        match input.strip_prefix(':') {
+            Some(command) => {
+                let (name, argument) = command
+                    .split_once(char::is_whitespace)
+                    .unwrap_or((command, ""));
+
+                match name {
+                    "type" => self.show_type(argument.trim())?,
+                    "ast" => self.show_ast(argument.trim())?,
+                    "ir" => self.show_ir(argument.trim())?,
+                    "help" => writeln!(self.vm.output(), "{}", HELP)?,
+                    "quit" | "q" => return Ok(false),
+                    _ => writeln!(self.vm.output(), "Unknown command ':{}', see :help.", name)?,
+                }
+            }
+            None if input.is_empty() => {}
+            None => self.execute(input)?,
+        }
+
+        self.vm.output().flush()?;
+        Ok(true)
+    }
+
+    /// Analyses and runs an input, printing its value if it is a bare
+    /// expression.
+    fn execute(&mut self, source: &str) -> Result<()> {
+        let Some(input) = self.parse(source)? else {
+            return Ok(());
+        };
+
+        let (input_type, result) = match &input {
+            Input::Expression(expression) => {
+                let (t, result) = self.analyser.analyze_expression(expression);
+                (t, result.clone())
+            }
+            Input::Statements(_) => (
+                Type::Void,
+                self.analyser.analyze_statements(input.statements()).clone(),
+            ),
+        };
+
+        if self.report_semantic(&result, source)? {
+            return Ok(());
+        }
+
+        let chunk = self.writer.get_chunk(input.statements());
+        self.functions.extend(chunk.functions);
+
+        let program = Program {
+            functions: self.functions.clone(),
+            main: chunk.main,
+        };
+        self.vm.load_program(&program);
+
+        match self.vm.run() {
+            Ok(()) => {
+                let values = self.vm.take_operands();
+                if let Input::Expression(_) = input {
+                    writeln!(self.vm.output(), "{}", format_value(&input_type, &values))?;
+                }
+            }
+            Err(e) => {
+                self.vm.recover();
+                self.report([Diagnostic::from(&e)], source)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn show_type(&mut self, source: &str) -> Result<()> {
+        let Some(expression) = self.parse_expression(source)? else {
+            return Ok(());
+        };
+
+        let (t, result) = self.analyser.analyze_expression(&expression);
+        let result = result.clone();
+
+        if !self.report_semantic(&result, source)? {
+            writeln!(self.vm.output(), "{}", t)?;
+        }
+
+        Ok(())
+    }
+
+    fn show_ast(&mut self, source: &str) -> Result<()> {
+        let Some(input) = self.parse(source)? else {
+            return Ok(());
+        };
+
+        // The tree printer writes straight to stdout
+        self.vm.output().flush()?;
+        match input {
+            Input::Expression(expression) => {
+                TreePrinter::new().visit(&expression)?;
+                println!();
+                Ok(())
+            }
+            Input::Statements(program) => TreePrinter::new().visit(&program),
+        }
+    }
+
+    /// Prints the code that an input would run, without running it or
+    /// keeping anything it declares.
+    fn show_ir(&mut self, source: &str) -> Result<()> {
+        let Some(input) = self.parse(source)? else {
+            return Ok(());
+        };
+
+        let mut analyser = self.analyser.clone();
+        let result = match &input {
+            Input::Expression(expression) => analyser.analyze_expression(expression).1,
+            Input::Statements(_) => analyser.analyze_statements(input.statements()),
+        }
+        .clone();
+
+        if !self.report_semantic(&result, source)? {
+            let chunk = self.writer.clone().get_chunk(input.statements());
+            write!(self.vm.output(), "{}", chunk)?;
+        }
+
+        Ok(())
+    }
+
+    fn lex(&mut self, source: &str) -> Result<Option<Vec<Token>>> {
+        let mut lexer: Lexer<SimpleBuffer> = Lexer::new(source, Path::new(FILE), None);
+
+        match lexer.lex() {
+            Ok(tokens) => Ok(Some(tokens)),
+            Err(errors) => {
+                self.report(errors.iter().map(Diagnostic::from), source)?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Parses `source` as a bare expression if it is one, or as statements
+    /// otherwise, reporting any errors.
+    fn parse(&mut self, source: &str) -> Result<Option<Input>> {
+        let Some(tokens) = self.lex(source)? else {
+            return Ok(None);
+        };
+
+        if let Ok(expression) = Parser::new(&tokens, Path::new(FILE)).parse_bare_expression() {
+            return Ok(Some(Input::Expression(expression)));
+        }
+
+        match Parser::new(&tokens, Path::new(FILE)).parse() {
+            Ok(program) => Ok(Some(Input::Statements(program.clone()))),
+            Err(errors) => {
+                self.report(errors.iter().map(Diagnostic::from), source)?;
+                Ok(None)
+            }
+        }
+    }
+
+    fn parse_expression(&mut self, source: &str) -> Result<Option<AstNode>> {
+        let Some(tokens) = self.lex(source)? else {
+            return Ok(None);
+        };
+
+        match Parser::new(&tokens, Path::new(FILE)).parse_bare_expression() {
+            Ok(expression) => Ok(Some(expression)),
+            Err(e) => {
+                self.report([Diagnostic::from(&e)], source)?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Reports the warnings and errors of an analysis, returning whether
+    /// there were any errors.
+    fn report_semantic(&mut self, result: &SemanticResult, source: &str) -> Result<bool> {
+        let warnings = result
+            .warnings
+            .iter()
+            .map(|w| Diagnostic::from(w).into_warning());
+        let errors = result.errors.iter().map(Diagnostic::from);
+
+        self.report(warnings.chain(errors), source)?;
+        Ok(result.has_errors())
+    }
+
+    fn report(
+        &mut self,
+        diagnostics: impl IntoIterator<Item = Diagnostic>,
+        source: &str,
+    ) -> Result<()> {
+        for diagnostic in diagnostics {
+            writeln!(
+                self.vm.output(),
+                "{}",
+                diagnostic.render(Path::new(FILE), source)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether `source` opens more blocks than it closes, so that the input
+/// continues on the next line.
+fn is_incomplete(source: &str) -> bool {
+    let mut lexer: Lexer<SimpleBuffer> = Lexer::new(source, Path::new(FILE), None);
+
+    let Ok(tokens) = lexer.lex() else {
+        return false;
+    };
+
+    let depth = tokens.iter().fold(0, |depth, token| match token.kind {
+        TokenKind::LBrace => depth + 1,
+        TokenKind::RBrace => depth - 1,
+        _ => depth,
+    });
+
+    depth > 0
+}
+
+/// Formats the values that an expression of type `t` left on the operand
+/// stack, which the VM stores as plain numbers.
+fn format_value(t: &Type, values: &[f64]) -> String {
+    match (t, values.last()) {
+        (Type::Array(element_type, _), _) => {
+            // The first element ends up on top of the stack
+            let elements = values
+                .iter()
+                .rev()
+                .map(|value| format_value(element_type, &[*value]))
+                .collect::<Vec<_>>();
+
+            format!("[{}]", elements.join(", "))
+        }
+        (Type::Int, Some(value)) => format!("{}", *value as i64),
+        (Type::Float, Some(value)) => format!("{:?}", value),
+        (Type::Bool, Some(value)) => format!("{}", *value != 0.0),
+        (Type::Colour, Some(value)) => format!("#{:06x}", *value as u32),
+        (_, Some(value)) => format!("{}", value),
+        (_, None) => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    /// Feeds `input` to a fresh REPL and returns everything it printed.
+    fn session(input: &str) -> String {
+        let mut output = Vec::new();
+        Repl::new(&mut output).run(input.as_bytes(), false).unwrap();
+
+        console::strip_ansi_codes(&String::from_utf8(output).unwrap()).to_string()
+    }
+
+    #[rstest]
+    #[case("1 + 2", "3")]
+    #[case("7 / 2", "3.5")]
+    #[case("5 as float", "5.0")]
+    #[case("2.7 as int", "2")]
+    #[case("3 < 4 and false", "false")]
+    #[case("#ff8000", "#ff8000")]
+    fn test_bare_expressions(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(session(input), format!("{}\n", expected));
+    }
+
+    #[rstest]
+    fn test_state_is_kept() {
+        let output = session(
+            "let x: int = 4;\n\
+             let xs: float[2] = [1.5, 2.5];\n\
+             __print x * 2;\n\
+             x = x + 1;\n\
+             x\n\
+             xs\n",
+        );
+
+        assert_eq!(output, "8\n5\n[1.5, 2.5]\n");
+    }
+
+    #[rstest]
+    fn test_multiline_function() {
+        let output = session(
+            "fun double(n: int) -> int {\n\
+                 return n * 2;\n\
+             }\n\
+             let y: int = 21;\n\
+             double(y)\n",
+        );
+
+        assert_eq!(output, "42\n");
+    }
+
+    #[rstest]
+    fn test_errors_are_not_kept() {
+        let output = session(
+            "let x: int = true;\n\
+             let x: float = 1.5;\n\
+             x\n",
+        );
+
+        assert!(output.starts_with("error[E0205]: "));
+        assert!(output.ends_with("\n1.5\n"));
+    }
+
+    #[rstest]
+    #[case(":type 1 / 2", "float\n")]
+    #[case(":type 1 > 2.5", "bool\n")]
+    #[case(":ir __print 1;", ".main\npush 0\noframe\npush 1\nprint\nhalt\n")]
+    fn test_commands(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(session(input), expected);
+    }
+
+    #[rstest]
+    fn test_ir_does_not_declare() {
+        let output = session(":ir let x: int = 1;\nx\n");
+
+        assert!(output.contains("error[E0201]: "));
+    }
+
+    #[rstest]
+    fn test_quit() {
+        assert_eq!(session("1\n:quit\n2\n"), "1\n");
+    }
+}
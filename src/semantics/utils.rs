@@ -1,4 +1,6 @@
-use std::{collections::LinkedList, fmt::Display};
+use std::{cmp::Ordering, collections::LinkedList, fmt::Display};
+
+use crate::core::{AstNode, TextSpan, Token, TokenKind};
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub enum Type {
@@ -8,6 +10,9 @@ pub enum Type {
     Colour,
     Void,
     Array(Box<Type>, usize),
+    /// A user-defined struct, by name. Its fields are looked up in the
+    /// symbol table.
+    Struct(String),
     Unknown,
 }
 
@@ -18,18 +23,179 @@ impl Display for Type {
             Type::Float => write!(f, "float"),
             Type::Bool => write!(f, "bool"),
             Type::Colour => write!(f, "colour"),
-            Type::Array(t, size) => write!(f, "{}[{}]", t, size),
+            Type::Array(..) => {
+                // Nested arrays are written with their outermost dimension first
+                let mut element = self;
+                let mut dimensions = vec![];
+                while let Type::Array(inner, size) = element {
+                    dimensions.push(size);
+                    element = inner;
+                }
+
+                write!(f, "{}", element)?;
+                dimensions
+                    .iter()
+                    .try_for_each(|size| write!(f, "[{}]", size))
+            }
+            Type::Struct(name) => write!(f, "{}", name),
             Type::Void => write!(f, "void"),
             Type::Unknown => write!(f, "unknown"),
         }
     }
 }
 
+impl Type {
+    /// The type of an array of `element`s with the given dimensions, outermost
+    /// first.
+    pub fn array(element: Type, dimensions: &[usize]) -> Type {
+        dimensions
+            .iter()
+            .rev()
+            .fold(element, |t, size| Type::Array(Box::new(t), *size))
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub enum SymbolType {
     Variable(Type),
     Function(Signature),
-    Array(Type, usize),
+    /// An array of the given element type and dimensions, outermost first,
+    /// laid out in row-major order.
+    Array(Type, Vec<usize>),
+    /// A struct declaration, with its fields in the order they are laid out.
+    Struct(Vec<(String, Type)>),
+    /// A `const` of the given type, which takes up no space as its value is
+    /// pushed wherever it is used.
+    Constant(Type, ConstValue),
+}
+
+impl SymbolType {
+    /// How many values the symbol takes up in a frame. A struct variable
+    /// takes up one value per field, which needs the struct to be looked up,
+    /// so it is left to the caller.
+    pub fn size(&self) -> usize {
+        match self {
+            SymbolType::Variable(_) => 1,
+            SymbolType::Function(_) | SymbolType::Struct(_) | SymbolType::Constant(..) => 0,
+            SymbolType::Array(_, dimensions) => dimensions.iter().product(),
+        }
+    }
+}
+
+/// The value of a constant. The VM keeps every value as an `f64`, so
+/// constants are kept the same way, with bools as 0 or 1 and colours as
+/// `0xRRGGBB`.
+#[derive(Debug, Clone, Copy)]
+pub struct ConstValue(pub f64);
+
+impl PartialEq for ConstValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for ConstValue {}
+
+impl PartialOrd for ConstValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ConstValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+/// The largest size an array dimension can be declared with.
+pub const MAX_ARRAY_SIZE: usize = u32::MAX as usize;
+
+/// The array size that `value` stands for, if it is a whole number from 0 up
+/// to [`MAX_ARRAY_SIZE`].
+pub fn array_size(value: f64) -> Option<usize> {
+    (value.fract() == 0.0 && (0.0..=MAX_ARRAY_SIZE as f64).contains(&value))
+        .then_some(value as usize)
+}
+
+/// Why an expression has no value at compile time.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FoldError {
+    /// It uses something other than literals, constants, operators and casts
+    NotConstant,
+    /// It divides, or takes the remainder, by zero in the given span
+    DivisionByZero(TextSpan),
+}
+
+/// Works out the value of an expression at compile time, the same way the VM
+/// would at run time. Casts only change the type, so `5.7 as int` is still
+/// 5.7. `constant` gives the value of a named constant. Anything else that is
+/// not a literal, like a variable or a call, has no value here.
+pub fn fold_constant(
+    node: &AstNode,
+    constant: &impl Fn(&Token) -> Option<f64>,
+) -> Result<f64, FoldError> {
+    let truth = |b: bool| b as u8 as f64;
+
+    match node {
+        AstNode::Expression { expr, .. } | AstNode::SubExpression { bin_op: expr, .. } => {
+            fold_constant(expr, constant)
+        }
+        AstNode::IntLiteral(token) | AstNode::FloatLiteral(token) => {
+            token.span.lexeme.parse().or(Err(FoldError::NotConstant))
+        }
+        AstNode::BoolLiteral(token) => Ok(truth(token.span.lexeme == "true")),
+        AstNode::ColourLiteral(token) => u32::from_str_radix(&token.span.lexeme[1..], 16)
+            .map(f64::from)
+            .or(Err(FoldError::NotConstant)),
+        AstNode::Identifier { token } => constant(token).ok_or(FoldError::NotConstant),
+        AstNode::UnaryOp { operator, expr, .. } => {
+            let value = fold_constant(expr, constant)?;
+            match operator.kind {
+                TokenKind::Minus => Ok(-value),
+                TokenKind::Not => Ok(truth(value == 0.0)),
+                _ => Err(FoldError::NotConstant),
+            }
+        }
+        AstNode::BinOp {
+            left,
+            operator,
+            right,
+            span,
+        } => {
+            let a = fold_constant(left, constant)?;
+            let b = fold_constant(right, constant)?;
+            match operator.kind {
+                TokenKind::Divide | TokenKind::Mod if b == 0.0 => {
+                    Err(FoldError::DivisionByZero(span.clone()))
+                }
+                TokenKind::Plus => Ok(a + b),
+                TokenKind::Minus => Ok(a - b),
+                TokenKind::Multiply => Ok(a * b),
+                TokenKind::Divide => Ok(a / b),
+                TokenKind::Mod => Ok(a % b),
+                TokenKind::And => Ok(truth(a != 0.0 && b != 0.0)),
+                TokenKind::Or => Ok(truth(a != 0.0 || b != 0.0)),
+                TokenKind::EqEq => Ok(truth(a == b)),
+                TokenKind::NotEqual => Ok(truth(a != b)),
+                TokenKind::LessThan => Ok(truth(a < b)),
+                TokenKind::LessThanEqual => Ok(truth(a <= b)),
+                TokenKind::GreaterThan => Ok(truth(a > b)),
+                TokenKind::GreaterThanEqual => Ok(truth(a >= b)),
+                _ => Err(FoldError::NotConstant),
+            }
+        }
+        AstNode::Conditional {
+            condition,
+            if_true,
+            if_false,
+            ..
+        } => match fold_constant(condition, constant)? != 0.0 {
+            true => fold_constant(if_true, constant),
+            false => fold_constant(if_false, constant),
+        },
+        _ => Err(FoldError::NotConstant),
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
@@ -49,7 +215,7 @@ impl Signature {
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct MemoryLocation {
     pub stack_level: usize,
     pub frame_index: usize,
@@ -60,6 +226,8 @@ pub struct Symbol {
     pub lexeme: String,
     pub symbol_type: SymbolType,
     pub memory_location: Option<MemoryLocation>,
+    /// Where the symbol was declared, if it came from the source
+    pub declaration: Option<TextSpan>,
 }
 
 impl PartialEq for Symbol {
@@ -72,7 +240,7 @@ impl Eq for Symbol {}
 
 impl PartialOrd for Symbol {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.lexeme.cmp(&other.lexeme))
+        Some(self.cmp(other))
     }
 }
 
@@ -88,11 +256,12 @@ impl Symbol {
             lexeme: lexeme.to_string(),
             symbol_type,
             memory_location: mem_loc,
+            declaration: None,
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct SymbolTable {
     pub symbols: LinkedList<Symbol>,
 }
@@ -110,7 +279,7 @@ impl SymbolTable {
             "float" => Type::Float,
             "bool" => Type::Bool,
             "colour" => Type::Colour,
-            _ => unreachable!(),
+            name => Type::Struct(name.to_string()),
         }
     }
 
@@ -177,7 +346,7 @@ mod tests {
         symbol_table.add_symbol("b", &SymbolType::Variable(Type::Float), None);
         symbol_table.add_symbol(
             "e",
-            &SymbolType::Array(Type::Int, 10),
+            &SymbolType::Array(Type::Int, vec![10]),
             Some(MemoryLocation {
                 stack_level: 0,
                 frame_index: 0,
@@ -214,4 +383,16 @@ mod tests {
         assert_eq!(symbol_table.symbols.len(), 6);
         assert_eq!(symbol_table.symbols.iter().nth(2).unwrap().lexeme, "f");
     }
+
+    #[rstest]
+    fn test_array_types() {
+        let grid = Type::array(Type::Int, &[2, 3]);
+
+        assert_eq!(
+            grid,
+            Type::Array(Box::new(Type::Array(Box::new(Type::Int), 3)), 2)
+        );
+        assert_eq!(grid.to_string(), "int[2][3]");
+        assert_eq!(SymbolType::Array(Type::Int, vec![2, 3]).size(), 6);
+    }
 }
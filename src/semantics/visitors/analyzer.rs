@@ -1,12 +1,25 @@
 use crate::core::TokenKind;
-use crate::core::{AstNode, Token, Visitor};
-use crate::semantics::utils::{Signature, Symbol, SymbolTable, SymbolType, Type};
+use crate::core::{AstNode, TextSpan, Token, Visitor};
+use crate::semantics::utils::{
+    array_size, fold_constant, ConstValue, FoldError, Signature, Symbol, SymbolTable, SymbolType,
+    Type,
+};
 use crate::utils::errors::SemanticError;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct SemanticResult {
     pub errors: Vec<SemanticError>,
     pub warnings: Vec<SemanticError>,
+    /// Every declaration and use of a symbol, in the order they were visited
+    pub references: Vec<Reference>,
+}
+
+/// A declaration or use of a symbol in the source.
+#[derive(Debug, Clone)]
+pub struct Reference {
+    pub span: TextSpan,
+    pub symbol_type: SymbolType,
+    pub declaration: Option<TextSpan>,
 }
 
 impl SemanticResult {
@@ -14,6 +27,7 @@ impl SemanticResult {
         SemanticResult {
             errors: Vec::new(),
             warnings: Vec::new(),
+            references: Vec::new(),
         }
     }
 
@@ -34,7 +48,7 @@ impl SemanticResult {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct SemanticAnalyser {
     /// Stack of symbol tables, each representing a scope
     symbol_table: Vec<SymbolTable>,
@@ -43,6 +57,8 @@ pub struct SemanticAnalyser {
     /// If this is 0, we can check for the existence of the symbol in any
     /// scope, up to the global scope.
     scope_peek_limit: usize,
+    /// How many loops the node being visited is nested in
+    loop_depth: usize,
     /// The results of the semantic analysis
     results: SemanticResult,
 }
@@ -53,6 +69,7 @@ impl SemanticAnalyser {
             symbol_table: Vec::new(),
             inside_function: false,
             scope_peek_limit: 0,
+            loop_depth: 0,
             results: SemanticResult::new(),
         }
     }
@@ -62,6 +79,39 @@ impl SemanticAnalyser {
         &self.results
     }
 
+    /// Analyses statements entered one at a time, as in the REPL. The global
+    /// scope outlives each call, but whatever an input with errors declared
+    /// is dropped again.
+    pub fn analyze_statements(&mut self, statements: &[AstNode]) -> &SemanticResult {
+        self.results = SemanticResult::new();
+        if self.symbol_table.is_empty() {
+            self.push_scope();
+        }
+
+        let global_scope = self.current_scope().clone();
+        for statement in statements {
+            self.visit(statement);
+        }
+
+        if self.results.has_errors() {
+            *self.mut_current_scope() = global_scope;
+        }
+
+        &self.results
+    }
+
+    /// Returns the type of `expression` in the global scope kept by
+    /// [`SemanticAnalyser::analyze_statements`].
+    pub fn analyze_expression(&mut self, expression: &AstNode) -> (Type, &SemanticResult) {
+        self.results = SemanticResult::new();
+        if self.symbol_table.is_empty() {
+            self.push_scope();
+        }
+
+        let expression_type = self.visit(expression);
+        (expression_type, &self.results)
+    }
+
     fn find_symbol(&self, symbol: &Token) -> Option<&Symbol> {
         self.symbol_table
             .iter()
@@ -85,8 +135,24 @@ impl SemanticAnalyser {
     }
 
     fn add_symbol(&mut self, symbol: &Token, symbol_type: &SymbolType) {
-        self.mut_current_scope()
-            .add_symbol(&symbol.span.lexeme, symbol_type, None);
+        let scope = self.mut_current_scope();
+        scope.add_symbol(&symbol.span.lexeme, symbol_type, None);
+
+        if let Some(added) = scope.find_symbol_mut(&symbol.span.lexeme) {
+            added.declaration = Some(symbol.span.clone());
+        }
+    }
+
+    /// Records that `token` refers to whichever symbol it currently resolves to.
+    fn add_reference(&mut self, token: &Token) {
+        if let Some(symbol) = self.find_symbol(token) {
+            let reference = Reference {
+                span: token.span.clone(),
+                symbol_type: symbol.symbol_type.clone(),
+                declaration: symbol.declaration.clone(),
+            };
+            self.results.references.push(reference);
+        }
     }
 
     fn get_symbol_type(&self, symbol: &Token) -> Type {
@@ -95,17 +161,106 @@ impl SemanticAnalyser {
                 SymbolType::Variable(t) => t,
                 SymbolType::Function(signature) => signature.return_type,
                 SymbolType::Array(t, _) => t,
+                SymbolType::Struct(_) => Type::Unknown,
+                SymbolType::Constant(t, _) => t,
             })
             .unwrap_or(Type::Unknown)
     }
 
-    fn get_signature(&self, symbol: &Token) -> Signature {
-        self.find_symbol(symbol)
-            .map(|s| match &s.symbol_type {
-                SymbolType::Function(signature) => signature.clone(),
-                _ => unreachable!(),
+    /// The value of the constant named by `token`, if it names one.
+    fn constant_value(&self, token: &Token) -> Option<f64> {
+        match self.find_symbol(token).map(|s| &s.symbol_type) {
+            Some(SymbolType::Constant(_, value)) => Some(value.0),
+            _ => None,
+        }
+    }
+
+    /// The size of an array dimension, given as an int literal or as the name
+    /// of an `int` constant. Either has to be a whole number that fits
+    /// [`array_size`].
+    fn dimension_size(&mut self, size: &Token) -> Option<usize> {
+        let value = match size.kind {
+            TokenKind::IntLiteral => size.span.lexeme.parse().ok(),
+            _ => {
+                self.add_reference(size);
+                match self.find_symbol(size).map(|s| &s.symbol_type) {
+                    Some(SymbolType::Constant(Type::Int, value)) => Some(value.0),
+                    _ => None,
+                }
+            }
+        };
+
+        let size_value = value.and_then(array_size);
+        if size_value.is_none() {
+            self.results
+                .add_error(SemanticError::InvalidArraySize(size.clone()));
+        }
+        size_value
+    }
+
+    /// The fields of the struct named `name`, if one is declared.
+    fn find_struct(&self, name: &str) -> Option<&Vec<(String, Type)>> {
+        self.symbol_table
+            .iter()
+            .rev()
+            .find_map(|table| match table.find_symbol(name) {
+                Some(Symbol {
+                    symbol_type: SymbolType::Struct(fields),
+                    ..
+                }) => Some(fields),
+                _ => None,
             })
-            .unwrap_or(Signature::new(Type::Unknown))
+    }
+
+    /// The type named by `token`, which is either a built-in type or a struct
+    /// that must have been declared.
+    fn resolve_type(&mut self, token: &Token) -> Type {
+        let resolved = self.current_scope().token_to_type(&token.span.lexeme);
+
+        if let Type::Struct(name) = &resolved {
+            if self.find_struct(name).is_none() {
+                self.results
+                    .add_error(SemanticError::UndefinedStruct(token.clone()));
+            }
+        }
+
+        resolved
+    }
+
+    /// The type of the field that `fields` leads to in a value of type `base`.
+    fn field_type(&mut self, base: Type, fields: &[Token]) -> Type {
+        let mut current = base;
+
+        for field in fields {
+            let found = match &current {
+                Type::Struct(name) => self
+                    .find_struct(name)
+                    .and_then(|fields| fields.iter().find(|(f, _)| *f == field.span.lexeme))
+                    .map(|(_, t)| t.clone()),
+                _ => None,
+            };
+
+            match found {
+                Some(field_type) => current = field_type,
+                None => {
+                    // Accessing a field of something already broken says nothing new
+                    if current != Type::Unknown {
+                        self.results
+                            .add_error(SemanticError::UnknownField(field.clone(), current));
+                    }
+                    return Type::Unknown;
+                }
+            }
+        }
+
+        current
+    }
+
+    fn get_signature(&self, symbol: &Token) -> Signature {
+        match self.find_symbol(symbol).map(|s| &s.symbol_type) {
+            Some(SymbolType::Function(signature)) => signature.clone(),
+            _ => Signature::new(Type::Unknown),
+        }
     }
 
     fn check_scope(&self, symbol: &Token) -> bool {
@@ -224,18 +379,84 @@ impl SemanticAnalyser {
         }
     }
 
-    fn assert_type(&mut self, token: &String, expected: &Type, found: &Type) -> Type {
+    fn assert_type(&mut self, name: &str, span: &TextSpan, expected: &Type, found: &Type) -> Type {
         if expected != found {
             self.results.add_error(SemanticError::TypeMismatch(
-                token.to_string(),
+                name.to_string(),
                 found.clone(),
                 expected.clone(),
+                span.clone(),
             ));
         }
 
         found.clone()
     }
 
+    /// Checks the elements of an array initialiser against its element type and
+    /// its dimensions. A single element, or a single list, fills a whole
+    /// dimension, and so does an element given in place of a list.
+    fn check_array_elements(
+        &mut self,
+        identifier: &Token,
+        element_type: &Type,
+        dimensions: &[usize],
+        elements: &[AstNode],
+    ) {
+        let size = dimensions[0];
+
+        if elements.len() != size && elements.len() != 1 {
+            self.results.add_error(SemanticError::ArraySizeMismatch(
+                identifier.clone(),
+                size,
+                elements.len(),
+            ));
+        }
+
+        for element in elements {
+            match element {
+                AstNode::ArrayLiteral { elements, .. } if dimensions.len() > 1 => {
+                    self.check_array_elements(identifier, element_type, &dimensions[1..], elements)
+                }
+                _ => {
+                    let found = self.visit(element);
+                    self.assert_type("element", element.span(), element_type, &found);
+                }
+            }
+        }
+    }
+
+    /// Checks that an array is indexed once per dimension, and that the
+    /// indices known at compile time are in range.
+    fn check_indices(&mut self, identifier: &Token, indices: &[AstNode]) {
+        let dimensions = match self.find_symbol(identifier).map(|s| &s.symbol_type) {
+            Some(SymbolType::Array(_, dimensions)) => dimensions.clone(),
+            Some(SymbolType::Variable(_)) => vec![],
+            _ => return,
+        };
+
+        if dimensions.len() != indices.len() {
+            self.results.add_error(SemanticError::ArrayIndexCount(
+                identifier.clone(),
+                dimensions.len(),
+                indices.len(),
+            ));
+        }
+
+        for (index, size) in indices.iter().zip(dimensions) {
+            match constant_index(index, &|token| self.constant_value(token)) {
+                Some(value) if value < 0 || value >= size as i64 => {
+                    self.results.add_error(SemanticError::ArrayOverflow(
+                        identifier.clone(),
+                        value,
+                        size,
+                        index.span().clone(),
+                    ))
+                }
+                _ => {}
+            }
+        }
+    }
+
     fn check_up_to_scope(&self, symbol: &Token) -> bool {
         self.symbol_table
             .iter()
@@ -246,7 +467,7 @@ impl SemanticAnalyser {
 
     fn visit_unscoped_block(&mut self, block: &AstNode) -> Type {
         match block {
-            AstNode::Block { statements } => {
+            AstNode::Block { statements, .. } => {
                 let last = Type::Void;
                 for statement in statements {
                     let last = self.visit(statement);
@@ -267,8 +488,8 @@ impl SemanticAnalyser {
         self.symbol_table.pop();
     }
 
-    fn check_cast(&mut self, to: &Token, from: Type) -> Type {
-        let to = self.current_scope().token_to_type(&to.span.lexeme);
+    fn check_cast(&mut self, token: &Token, from: Type) -> Type {
+        let to = self.current_scope().token_to_type(&token.span.lexeme);
 
         if from == to {
             return from;
@@ -282,17 +503,36 @@ impl SemanticAnalyser {
             (Type::Int, Type::Colour) => Type::Colour, // 0xRR + 0xGG + 0xBB -> 0xRRGGBB
 
             _ => {
-                self.results.add_error(SemanticError::InvalidCast(from, to));
+                self.results
+                    .add_error(SemanticError::InvalidCast(token.clone(), from, to));
                 Type::Unknown
             }
         }
     }
 }
 
+/// The value of an index that is known at compile time, like a literal or a
+/// constant, if it is a whole number. `constant` gives the value of a named
+/// constant.
+fn constant_index(node: &AstNode, constant: &impl Fn(&Token) -> Option<f64>) -> Option<i64> {
+    fold_constant(node, constant)
+        .ok()
+        .filter(|value| value.fract() == 0.0)
+        .map(|value| value as i64)
+}
+
+/// The value a `match` arm is taken for. Colours are compared as integers.
+fn arm_value(node: &AstNode) -> Option<i64> {
+    match node {
+        AstNode::ColourLiteral(token) => i64::from_str_radix(&token.span.lexeme[1..], 16).ok(),
+        _ => constant_index(node, &|_| None),
+    }
+}
+
 impl Visitor<Type> for SemanticAnalyser {
     fn visit(&mut self, node: &AstNode) -> Type {
         match node {
-            AstNode::Program { statements } => {
+            AstNode::Program { statements, .. } => {
                 self.push_scope();
                 for statement in statements {
                     self.visit(statement);
@@ -301,33 +541,42 @@ impl Visitor<Type> for SemanticAnalyser {
 
                 Type::Void
             }
-            AstNode::ArrayAccess { identifier, index } => {
+            AstNode::ArrayAccess {
+                identifier,
+                indices,
+                ..
+            } => {
+                self.add_reference(identifier);
                 let identifier_type = self.get_symbol_type(identifier);
-                let index_type = self.visit(index);
 
                 if self.find_symbol(identifier).is_none() {
                     self.results
                         .add_error(SemanticError::UndefinedVariable(identifier.clone()));
                 }
 
-                if index_type != Type::Int {
-                    self.results.add_error(SemanticError::TypeMismatch(
-                        "index".to_string(),
-                        index_type,
-                        Type::Int,
-                    ));
+                for index in indices {
+                    let index_type = self.visit(index);
+                    if index_type != Type::Int {
+                        self.results.add_error(SemanticError::TypeMismatch(
+                            "index".to_string(),
+                            index_type,
+                            Type::Int,
+                            index.span().clone(),
+                        ));
+                    }
                 }
+                self.check_indices(identifier, indices);
 
                 identifier_type
             }
 
-            AstNode::Block { statements } => {
+            AstNode::Block { statements, .. } => {
                 self.push_scope();
                 let mut last = Type::Void;
                 for statement in statements {
                     // if the statement is a return statement, we don't need to
                     // check the rest of the block
-                    if let AstNode::Return { expression } = statement {
+                    if let AstNode::Return { expression, .. } = statement {
                         last = self.visit(expression);
                         break;
                     } else {
@@ -342,10 +591,13 @@ impl Visitor<Type> for SemanticAnalyser {
                 identifier,
                 params,
                 return_type,
+                return_dimensions,
                 block,
+                ..
             } => {
                 // Check that function name isn't already defined
-                if self.check_scope(identifier) {
+                let already_defined = self.check_scope(identifier);
+                if already_defined {
                     self.results
                         .add_error(SemanticError::FunctionAlreadyDefined(identifier.clone()));
                 } else {
@@ -358,6 +610,13 @@ impl Visitor<Type> for SemanticAnalyser {
 
                 self.push_scope();
 
+                let return_type = self.resolve_type(return_type);
+                let return_dimensions = return_dimensions
+                    .iter()
+                    .map(|size| self.dimension_size(size).unwrap_or(0))
+                    .collect::<Vec<_>>();
+                let return_type = Type::array(return_type, &return_dimensions);
+
                 // all the parameters are added to the symbol table
                 // now we add them to the function signature
                 let mut signature = Signature::new(return_type.clone());
@@ -375,12 +634,19 @@ impl Visitor<Type> for SemanticAnalyser {
                     }
                 }
 
-                self.find_symbol_mut(identifier).unwrap().symbol_type =
-                    SymbolType::Function(signature.clone());
+                // A symbol of the same name that was already there is kept
+                if !already_defined {
+                    self.find_symbol_mut(identifier).unwrap().symbol_type =
+                        SymbolType::Function(signature.clone());
+                }
+                self.add_reference(identifier);
 
                 self.inside_function = true;
                 self.scope_peek_limit = self.symbol_table.len() - 1;
+                // A loop around the declaration cannot be left from its body
+                let loop_depth = std::mem::take(&mut self.loop_depth);
                 let block_return_type = self.visit_unscoped_block(block);
+                self.loop_depth = loop_depth;
                 self.inside_function = false;
 
                 if signature.return_type != block_return_type {
@@ -398,7 +664,16 @@ impl Visitor<Type> for SemanticAnalyser {
             }
 
             AstNode::Identifier { token } => {
-                if self.inside_function {
+                self.add_reference(token);
+
+                if let Some(SymbolType::Struct(_)) = self.find_symbol(token).map(|s| &s.symbol_type)
+                {
+                    self.results
+                        .add_error(SemanticError::UndefinedVariable(token.clone()));
+                }
+
+                // Constants are inlined, so functions can use them too
+                if self.inside_function && self.constant_value(token).is_none() {
                     if !self.check_up_to_scope(token) {
                         self.results
                             .add_error(SemanticError::VarUndefinedInFunc(token.clone()));
@@ -412,17 +687,108 @@ impl Visitor<Type> for SemanticAnalyser {
                     .map(|s| match s.symbol_type.clone() {
                         SymbolType::Variable(t) => t,
                         SymbolType::Function(signature) => signature.return_type,
-                        SymbolType::Array(t, s) => Type::Array(Box::new(t), s),
+                        SymbolType::Array(t, dimensions) => Type::array(t, &dimensions),
+                        SymbolType::Struct(_) => Type::Unknown,
+                        SymbolType::Constant(t, _) => t,
                     })
                     .unwrap_or(Type::Unknown)
             }
 
+            AstNode::FieldAccess {
+                identifier, fields, ..
+            } => {
+                let base = self.visit(&AstNode::Identifier {
+                    token: identifier.clone(),
+                });
+
+                self.field_type(base, fields)
+            }
+
+            AstNode::StructDecl {
+                identifier, fields, ..
+            } => {
+                let mut resolved: Vec<(String, Type)> = vec![];
+
+                // The struct is declared after its fields, so that it cannot
+                // contain itself
+                for (field, field_type) in fields {
+                    if resolved.iter().any(|(f, _)| *f == field.span.lexeme) {
+                        self.results
+                            .add_error(SemanticError::DuplicateField(field.clone()));
+                    }
+
+                    let field_type = self.resolve_type(field_type);
+                    resolved.push((field.span.lexeme.clone(), field_type));
+                }
+
+                if self.check_scope(identifier) {
+                    self.results
+                        .add_error(SemanticError::VariableRedeclaration(identifier.clone()));
+                } else {
+                    self.add_symbol(identifier, &SymbolType::Struct(resolved));
+                }
+                self.add_reference(identifier);
+
+                Type::Void
+            }
+
+            AstNode::StructLiteral {
+                identifier, fields, ..
+            } => {
+                self.add_reference(identifier);
+
+                let declared = self.find_struct(&identifier.span.lexeme).cloned();
+                if declared.is_none() {
+                    self.results
+                        .add_error(SemanticError::UndefinedStruct(identifier.clone()));
+                }
+                let declared = declared.unwrap_or_default();
+
+                for (i, (field, value)) in fields.iter().enumerate() {
+                    let found = self.visit(value);
+
+                    if fields[..i]
+                        .iter()
+                        .any(|(f, _)| f.span.lexeme == field.span.lexeme)
+                    {
+                        self.results
+                            .add_error(SemanticError::DuplicateField(field.clone()));
+                    }
+
+                    match declared.iter().find(|(f, _)| *f == field.span.lexeme) {
+                        Some((_, expected)) => {
+                            self.assert_type(&field.span.lexeme, value.span(), expected, &found);
+                        }
+                        None if !declared.is_empty() => {
+                            self.results.add_error(SemanticError::UnknownField(
+                                field.clone(),
+                                Type::Struct(identifier.span.lexeme.clone()),
+                            ));
+                        }
+                        None => {}
+                    }
+                }
+
+                for (name, _) in &declared {
+                    if !fields.iter().any(|(f, _)| f.span.lexeme == *name) {
+                        self.results.add_error(SemanticError::MissingField(
+                            identifier.clone(),
+                            name.clone(),
+                        ));
+                    }
+                }
+
+                Type::Struct(identifier.span.lexeme.clone())
+            }
+
             AstNode::VarDec {
                 identifier,
                 var_type,
                 expression,
+                ..
             } => {
                 let expr_type = self.visit(expression);
+                let var_type = self.resolve_type(var_type);
 
                 if self.check_scope(identifier) {
                     self.results
@@ -434,17 +800,67 @@ impl Visitor<Type> for SemanticAnalyser {
                             .add_warning(SemanticError::VariableShadowing(identifier.clone()));
                     }
 
-                    self.add_symbol(
-                        identifier,
-                        &SymbolType::Variable(
-                            self.current_scope().token_to_type(&var_type.span.lexeme),
-                        ),
-                    );
+                    self.add_symbol(identifier, &SymbolType::Variable(var_type.clone()));
+                }
+                self.add_reference(identifier);
+
+                self.assert_type(
+                    &identifier.span.lexeme,
+                    expression.span(),
+                    &var_type,
+                    &expr_type,
+                );
+
+                Type::Void
+            }
+
+            AstNode::ConstDec {
+                identifier,
+                const_type,
+                expression,
+                ..
+            } => {
+                let errors = self.results.errors.len();
+                let expr_type = self.visit(expression);
+                let const_type = self.resolve_type(const_type);
+
+                let value = fold_constant(expression, &|token| self.constant_value(token));
+                match &value {
+                    Err(FoldError::DivisionByZero(span)) => {
+                        self.results
+                            .add_error(SemanticError::ConstantDivisionByZero(
+                                identifier.clone(),
+                                span.clone(),
+                            ))
+                    }
+                    // An initialiser that is already wrong is not reported again
+                    Err(FoldError::NotConstant) if self.results.errors.len() == errors => {
+                        self.results.add_error(SemanticError::NotConstant(
+                            identifier.clone(),
+                            expression.span().clone(),
+                        ))
+                    }
+                    _ => {}
                 }
 
+                if self.check_scope(identifier) {
+                    self.results
+                        .add_error(SemanticError::VariableRedeclaration(identifier.clone()));
+                } else {
+                    if self.find_symbol(identifier).is_some() {
+                        self.results
+                            .add_warning(SemanticError::VariableShadowing(identifier.clone()));
+                    }
+
+                    let value = ConstValue(value.unwrap_or_default());
+                    self.add_symbol(identifier, &SymbolType::Constant(const_type.clone(), value));
+                }
+                self.add_reference(identifier);
+
                 self.assert_type(
                     &identifier.span.lexeme,
-                    &self.current_scope().token_to_type(&var_type.span.lexeme),
+                    expression.span(),
+                    &const_type,
                     &expr_type,
                 );
 
@@ -454,46 +870,56 @@ impl Visitor<Type> for SemanticAnalyser {
             AstNode::VarDecArray {
                 identifier,
                 element_type,
-                size,
+                dimensions,
                 elements,
+                ..
             } => {
                 let element_type = self
                     .current_scope()
                     .token_to_type(&element_type.span.lexeme);
+                let dimensions = dimensions
+                    .iter()
+                    .map(|size| {
+                        size.as_ref()
+                            .and_then(|size| self.dimension_size(size))
+                            .unwrap_or(elements.len())
+                    })
+                    .collect::<Vec<_>>();
+
+                // The elements are checked before the array is declared, so
+                // they cannot refer to it
+                self.check_array_elements(identifier, &element_type, &dimensions, elements);
 
                 if self.check_scope(identifier) {
                     self.results
                         .add_error(SemanticError::VariableRedeclaration(identifier.clone()));
                 } else {
-                    self.add_symbol(identifier, &SymbolType::Array(element_type.clone(), *size));
-                }
-
-                if elements.len() > *size {
-                    self.results.add_error(SemanticError::ArrayOverflow(
-                        identifier.clone(),
-                        element_type.clone(),
-                        *size,
-                        elements.len(),
-                    ));
-                }
-
-                for element in elements {
-                    let current_element_type = self.visit(element);
-
-                    if element_type != current_element_type {
-                        self.results.add_error(SemanticError::TypeMismatch(
-                            "element".to_string(),
-                            element_type.clone(),
-                            element_type.clone(),
-                        ));
-                    }
+                    self.add_symbol(identifier, &SymbolType::Array(element_type, dimensions));
                 }
+                self.add_reference(identifier);
 
                 Type::Void
             }
 
-            AstNode::FunctionCall { identifier, args } => {
-                if self.find_symbol(identifier).is_none() {
+            AstNode::ArrayLiteral { elements, .. } => {
+                // Only reached when a list is given where a single element is
+                // expected, so that the mismatch can name the list's type
+                let element_type = elements
+                    .iter()
+                    .fold(Type::Unknown, |_, element| self.visit(element));
+                Type::Array(Box::new(element_type), elements.len())
+            }
+
+            AstNode::FunctionCall {
+                identifier,
+                args,
+                span,
+            } => {
+                self.add_reference(identifier);
+
+                // A variable of the same name cannot be called either
+                let symbol_type = self.find_symbol(identifier).map(|s| &s.symbol_type);
+                if !matches!(symbol_type, Some(SymbolType::Function(_))) {
                     self.results
                         .add_error(SemanticError::UndefinedFunction(identifier.clone()))
                 }
@@ -510,21 +936,33 @@ impl Visitor<Type> for SemanticAnalyser {
                     .collect::<Vec<Type>>();
 
                 if signature.parameters.is_empty() && !arg_types.is_empty() {
+                    let arguments = args[0].span().merge(args[args.len() - 1].span());
+
                     self.results.add_error(SemanticError::FunctionCallNoParams(
-                        identifier.span.lexeme.clone(),
+                        identifier.clone(),
                         arg_types,
+                        arguments,
                     ));
+                    return self.get_symbol_type(identifier);
                 }
 
-                // Make sure each argument is of the correct type
-                for (idx, b) in args.iter().rev().enumerate() {
-                    let arg_type = self.visit(b);
+                if signature.parameters.len() != arg_types.len() {
+                    self.results.add_error(SemanticError::FunctionCallArity(
+                        identifier.clone(),
+                        signature.parameters.len(),
+                        arg_types.len(),
+                        span.clone(),
+                    ));
+                    return self.get_symbol_type(identifier);
+                }
 
-                    self.assert_type(
-                        &signature.parameters[idx].1,
-                        &signature.parameters[idx].0,
-                        &arg_type,
-                    );
+                // Make sure each argument is of the correct type. The
+                // parameters are stored in reverse.
+                let params = signature.parameters.iter().rev();
+                for ((arg, arg_type), (param_type, param_name)) in
+                    args.iter().zip(&arg_types).zip(params)
+                {
+                    self.assert_type(param_name, arg.span(), param_type, arg_type);
                 }
 
                 self.get_symbol_type(identifier)
@@ -533,30 +971,32 @@ impl Visitor<Type> for SemanticAnalyser {
             AstNode::FormalParam {
                 identifier,
                 param_type,
-                length: index,
-            } => match index {
-                None => {
-                    let param_type = self.current_scope().token_to_type(&param_type.span.lexeme);
+                dimensions,
+                ..
+            } => {
+                let param_type = self.resolve_type(param_type);
+
+                if dimensions.is_empty() {
                     self.add_symbol(identifier, &SymbolType::Variable(param_type.clone()));
+                    self.add_reference(identifier);
                     param_type
-                }
-                Some(index) => {
-                    let size: usize = index.span.lexeme.parse().unwrap();
+                } else {
+                    let dimensions = dimensions
+                        .iter()
+                        .map(|size| self.dimension_size(size).unwrap_or(0))
+                        .collect::<Vec<usize>>();
                     self.add_symbol(
                         identifier,
-                        &SymbolType::Array(
-                            self.current_scope().token_to_type(&param_type.span.lexeme),
-                            size,
-                        ),
+                        &SymbolType::Array(param_type.clone(), dimensions.clone()),
                     );
-                    Type::Array(
-                        Box::new(self.current_scope().token_to_type(&param_type.span.lexeme)),
-                        size,
-                    )
+                    self.add_reference(identifier);
+                    Type::array(param_type, &dimensions)
                 }
-            },
+            }
 
-            AstNode::Expression { casted_type, expr } => {
+            AstNode::Expression {
+                casted_type, expr, ..
+            } => {
                 let expr_type = self.visit(expr);
 
                 match casted_type {
@@ -565,14 +1005,22 @@ impl Visitor<Type> for SemanticAnalyser {
                 }
             }
 
-            AstNode::SubExpression { bin_op } => self.visit(bin_op),
+            AstNode::SubExpression { bin_op, .. } => self.visit(bin_op),
 
             AstNode::Assignment {
                 identifier,
-                index,
+                indices,
+                fields,
+                operator,
                 expression,
+                ..
             } => {
-                if self.inside_function {
+                self.add_reference(identifier);
+
+                if self.constant_value(identifier).is_some() {
+                    self.results
+                        .add_error(SemanticError::ConstantAssignment(identifier.clone()));
+                } else if self.inside_function {
                     if !self.check_up_to_scope(identifier) {
                         self.results
                             .add_error(SemanticError::VarUndefinedInFunc(identifier.clone()));
@@ -583,8 +1031,9 @@ impl Visitor<Type> for SemanticAnalyser {
                 }
 
                 let identifier_type = self.get_symbol_type(identifier);
+                let identifier_type = self.field_type(identifier_type, fields);
 
-                if let Some(index) = index {
+                for index in indices {
                     let index_type = self.visit(index);
 
                     if index_type != Type::Int {
@@ -594,17 +1043,48 @@ impl Visitor<Type> for SemanticAnalyser {
                         ));
                     }
                 }
+                if !indices.is_empty() {
+                    self.check_indices(identifier, indices);
+                }
+
+                let mut expression_type = self.visit(expression);
+
+                // `x += e` has to type check as `x = x + e` would
+                if let Some(kind) = operator.kind.compound_operator() {
+                    // A whole array has no single value to operate on
+                    let whole_array = matches!(
+                        self.find_symbol(identifier).map(|s| &s.symbol_type),
+                        Some(SymbolType::Array(..))
+                    );
+                    if whole_array && indices.is_empty() {
+                        self.results
+                            .add_error(SemanticError::InvalidOperation(operator.clone()));
+                        return Type::Unknown;
+                    }
 
-                let expression_type = self.visit(expression);
+                    let mut bin_op = operator.clone();
+                    bin_op.kind = kind;
+                    expression_type =
+                        self.get_bin_op_type(&bin_op, &identifier_type, &expression_type);
+                    if expression_type == Type::Unknown {
+                        return Type::Unknown;
+                    }
+                }
 
-                self.assert_type(&identifier.span.lexeme, &identifier_type, &expression_type)
-                    .clone()
+                self.assert_type(
+                    &identifier.span.lexeme,
+                    expression.span(),
+                    &identifier_type,
+                    &expression_type,
+                )
+                .clone()
             }
 
             AstNode::BinOp {
                 left,
                 operator,
                 right,
+                ..
             } => {
                 let left_type = self.visit(left);
                 let right_type = self.visit(right);
@@ -612,15 +1092,31 @@ impl Visitor<Type> for SemanticAnalyser {
                 self.get_bin_op_type(operator, &left_type, &right_type)
             }
 
-            AstNode::UnaryOp { operator, expr } => {
+            AstNode::Conditional {
+                condition,
+                if_true,
+                if_false,
+                ..
+            } => {
+                let condition_type = self.visit(condition);
+                self.assert_type("condition", condition.span(), &Type::Bool, &condition_type);
+
+                let true_type = self.visit(if_true);
+                let false_type = self.visit(if_false);
+                self.assert_type("else", if_false.span(), &true_type, &false_type);
+
+                true_type
+            }
+
+            AstNode::UnaryOp { operator, expr, .. } => {
                 let expr_type = self.visit(expr);
 
                 self.get_unary_op_type(operator, &expr_type)
             }
 
-            AstNode::PadWidth => Type::Int,
+            AstNode::PadWidth { .. } => Type::Int,
 
-            AstNode::PadRandI { upper_bound } => {
+            AstNode::PadRandI { upper_bound, .. } => {
                 let upper_bound_type = self.visit(upper_bound);
 
                 if upper_bound_type != Type::Int {
@@ -628,15 +1124,16 @@ impl Visitor<Type> for SemanticAnalyser {
                         "upper_bound".to_string(),
                         upper_bound_type,
                         Type::Int,
+                        upper_bound.span().clone(),
                     ));
                 }
 
                 Type::Int
             }
 
-            AstNode::PadHeight => Type::Int,
+            AstNode::PadHeight { .. } => Type::Int,
 
-            AstNode::PadRead { x, y } => {
+            AstNode::PadRead { x, y, .. } => {
                 let x_type = self.visit(x);
                 let y_type = self.visit(y);
 
@@ -645,6 +1142,7 @@ impl Visitor<Type> for SemanticAnalyser {
                         "__read <x>, y".to_string(),
                         x_type,
                         Type::Int,
+                        x.span().clone(),
                     ));
                 }
 
@@ -653,6 +1151,7 @@ impl Visitor<Type> for SemanticAnalyser {
                         "__read x, <y>".to_string(),
                         y_type,
                         Type::Int,
+                        y.span().clone(),
                     ));
                 }
 
@@ -667,7 +1166,7 @@ impl Visitor<Type> for SemanticAnalyser {
 
             AstNode::ColourLiteral(_) => Type::Colour,
 
-            AstNode::Delay { expression } => {
+            AstNode::Delay { expression, .. } => {
                 let delay_ms_type = self.visit(expression);
 
                 if delay_ms_type != Type::Int {
@@ -675,13 +1174,23 @@ impl Visitor<Type> for SemanticAnalyser {
                         "delay".to_string(),
                         delay_ms_type,
                         Type::Int,
+                        expression.span().clone(),
                     ));
                 }
 
                 Type::Void
             }
 
-            AstNode::Return { expression } => self.visit(expression),
+            AstNode::Return { expression, .. } => self.visit(expression),
+
+            AstNode::Break { span } | AstNode::Continue { span } => {
+                if self.loop_depth == 0 {
+                    self.results
+                        .add_error(SemanticError::OutsideLoop(span.clone()));
+                }
+
+                Type::Void
+            }
 
             AstNode::PadWriteBox {
                 loc_x,
@@ -689,6 +1198,7 @@ impl Visitor<Type> for SemanticAnalyser {
                 width,
                 height,
                 colour,
+                ..
             } => {
                 let loc_x_type = self.visit(loc_x);
                 let loc_y_type = self.visit(loc_y);
@@ -701,6 +1211,7 @@ impl Visitor<Type> for SemanticAnalyser {
                         "loc_x".to_string(),
                         loc_x_type,
                         Type::Int,
+                        loc_x.span().clone(),
                     ));
                 }
 
@@ -709,6 +1220,7 @@ impl Visitor<Type> for SemanticAnalyser {
                         "loc_y".to_string(),
                         loc_y_type,
                         Type::Int,
+                        loc_y.span().clone(),
                     ));
                 }
 
@@ -717,6 +1229,7 @@ impl Visitor<Type> for SemanticAnalyser {
                         "width".to_string(),
                         width_type,
                         Type::Int,
+                        width.span().clone(),
                     ));
                 }
 
@@ -725,6 +1238,7 @@ impl Visitor<Type> for SemanticAnalyser {
                         "height".to_string(),
                         height_type,
                         Type::Int,
+                        height.span().clone(),
                     ));
                 }
 
@@ -733,6 +1247,7 @@ impl Visitor<Type> for SemanticAnalyser {
                         "colour".to_string(),
                         colour_type,
                         Type::Colour,
+                        colour.span().clone(),
                     ));
                 }
 
@@ -743,6 +1258,7 @@ impl Visitor<Type> for SemanticAnalyser {
                 loc_x,
                 loc_y,
                 colour,
+                ..
             } => {
                 let loc_x_type = self.visit(loc_x);
                 let loc_y_type = self.visit(loc_y);
@@ -753,6 +1269,7 @@ impl Visitor<Type> for SemanticAnalyser {
                         "loc_x".to_string(),
                         loc_x_type,
                         Type::Int,
+                        loc_x.span().clone(),
                     ));
                 }
 
@@ -761,6 +1278,7 @@ impl Visitor<Type> for SemanticAnalyser {
                         "loc_y".to_string(),
                         loc_y_type,
                         Type::Int,
+                        loc_y.span().clone(),
                     ));
                 }
 
@@ -769,6 +1287,7 @@ impl Visitor<Type> for SemanticAnalyser {
                         "colour".to_string(),
                         colour_type,
                         Type::Colour,
+                        colour.span().clone(),
                     ));
                 }
 
@@ -779,17 +1298,22 @@ impl Visitor<Type> for SemanticAnalyser {
                 condition,
                 if_true,
                 if_false,
+                ..
             } => {
                 self.visit(condition);
                 let true_branch_return_type = self.visit_unscoped_block(if_true);
                 if let Some(if_false) = if_false {
-                    let false_branch_return_type = self.visit_unscoped_block(if_false);
+                    let false_branch_return_type = match if_false.as_ref() {
+                        AstNode::If { .. } => self.visit(if_false),
+                        _ => self.visit_unscoped_block(if_false),
+                    };
 
                     if true_branch_return_type != false_branch_return_type {
                         self.results.add_error(SemanticError::TypeMismatch(
                             "if".to_string(),
                             true_branch_return_type.clone(),
                             false_branch_return_type,
+                            if_false.span().clone(),
                         ));
                     }
                 }
@@ -797,11 +1321,75 @@ impl Visitor<Type> for SemanticAnalyser {
                 true_branch_return_type
             }
 
+            AstNode::Match {
+                scrutinee,
+                arms,
+                default,
+                ..
+            } => {
+                let scrutinee_type = self.visit(scrutinee);
+                if !matches!(scrutinee_type, Type::Int | Type::Colour | Type::Unknown) {
+                    self.results.add_error(SemanticError::TypeMismatchUnion(
+                        "match".to_string(),
+                        scrutinee_type.clone(),
+                        vec![Type::Int, Type::Colour],
+                        scrutinee.span().clone(),
+                    ));
+                }
+
+                let mut values = vec![];
+                let mut return_types = vec![];
+                for (literal, block) in arms {
+                    let literal_type = self.visit(literal);
+                    if literal_type != scrutinee_type && scrutinee_type != Type::Unknown {
+                        self.results.add_error(SemanticError::TypeMismatch(
+                            "match arm".to_string(),
+                            literal_type,
+                            scrutinee_type.clone(),
+                            literal.span().clone(),
+                        ));
+                    } else if let Some(value) = arm_value(literal) {
+                        if values.contains(&value) {
+                            let text = match literal {
+                                AstNode::ColourLiteral(token) => token.span.lexeme.clone(),
+                                _ => value.to_string(),
+                            };
+                            self.results.add_error(SemanticError::DuplicateMatchArm(
+                                text,
+                                literal.span().clone(),
+                            ));
+                        }
+                        values.push(value);
+                    }
+
+                    return_types.push((self.visit_unscoped_block(block), block.span()));
+                }
+                if let Some(default) = default {
+                    return_types.push((self.visit_unscoped_block(default), default.span()));
+                }
+
+                // As with `if`, every arm has to return the same type as the first
+                let first = return_types.first().map(|(t, _)| t.clone());
+                for (return_type, span) in return_types.iter().skip(1) {
+                    if Some(return_type) != first.as_ref() {
+                        self.results.add_error(SemanticError::TypeMismatch(
+                            "match".to_string(),
+                            first.clone().unwrap(),
+                            return_type.clone(),
+                            (*span).clone(),
+                        ));
+                    }
+                }
+
+                first.unwrap_or(Type::Void)
+            }
+
             AstNode::For {
                 initializer,
                 condition,
                 increment,
                 body,
+                ..
             } => {
                 self.push_scope();
 
@@ -816,6 +1404,7 @@ impl Visitor<Type> for SemanticAnalyser {
                         "for condition".to_string(),
                         condition_type,
                         Type::Bool,
+                        condition.span().clone(),
                     ));
                 }
 
@@ -823,13 +1412,17 @@ impl Visitor<Type> for SemanticAnalyser {
                     self.visit(increment);
                 }
 
+                self.loop_depth += 1;
                 let body_type = self.visit_unscoped_block(body);
+                self.loop_depth -= 1;
                 self.symbol_table.pop();
 
                 body_type
             }
 
-            AstNode::While { condition, body } => {
+            AstNode::While {
+                condition, body, ..
+            } => {
                 self.push_scope();
                 let condition_type = self.visit(condition);
                 if condition_type != Type::Bool {
@@ -837,15 +1430,18 @@ impl Visitor<Type> for SemanticAnalyser {
                         "while".to_string(),
                         condition_type,
                         Type::Bool,
+                        condition.span().clone(),
                     ));
                 }
+                self.loop_depth += 1;
                 let body_return_type = self.visit_unscoped_block(body);
+                self.loop_depth -= 1;
                 self.pop_scope();
 
                 body_return_type
             }
 
-            AstNode::Print { expression } => {
+            AstNode::Print { expression, .. } => {
                 let print_expr_type = self.visit(expression);
 
                 if print_expr_type == Type::Void || print_expr_type == Type::Unknown {
@@ -853,13 +1449,14 @@ impl Visitor<Type> for SemanticAnalyser {
                         "__print <expr>".to_string(),
                         print_expr_type,
                         vec![Type::Int, Type::Float, Type::Bool, Type::Colour],
+                        expression.span().clone(),
                     ));
                 }
 
                 Type::Void
             }
 
-            AstNode::PadClear { expr } => {
+            AstNode::PadClear { expr, .. } => {
                 let clear_expr_type = self.visit(expr);
 
                 if clear_expr_type != Type::Colour {
@@ -867,13 +1464,14 @@ impl Visitor<Type> for SemanticAnalyser {
                         "__clear <expr>".to_string(),
                         clear_expr_type,
                         Type::Colour,
+                        expr.span().clone(),
                     ));
                 }
 
                 Type::Void
             }
 
-            AstNode::EndOfFile => Type::Void,
+            AstNode::EndOfFile { .. } => Type::Void,
         }
     }
 }
@@ -898,7 +1496,7 @@ mod tests {
         let tokens = lexer.lex().unwrap();
 
         let mut parser = Parser::new(&tokens, Path::new(""));
-        let ast = parser.parse()?;
+        let ast = parser.parse().unwrap();
 
         let mut scope_checker = SemanticAnalyser::new();
         scope_checker.visit(ast);
@@ -906,6 +1504,24 @@ mod tests {
         Ok(())
     }
 
+    /// Analyses `input` and returns the codes of the errors it reports.
+    fn error_codes(input: &str) -> Vec<&'static str> {
+        let mut lexer: Lexer<SimpleBuffer> = Lexer::new(input, Path::new(""), None);
+        let tokens = lexer.lex().unwrap();
+        let mut parser = Parser::new(&tokens, Path::new(""));
+        let ast = parser.parse().unwrap();
+
+        let mut analyser = SemanticAnalyser::new();
+        let result = analyser.analyze(ast);
+
+        result.errors.iter().map(SemanticError::code).collect()
+    }
+
+    /// Analyses `input` and returns the code of the first error it reports.
+    fn first_error_code(input: &str) -> Option<&str> {
+        error_codes(input).first().copied()
+    }
+
     #[rstest]
     fn test_symbol_table() {
         let mut symbol_table = SymbolTable::new();
@@ -950,4 +1566,240 @@ mod tests {
 
         assert!(run_scope_checker(input).is_ok());
     }
+
+    #[rstest]
+    #[case::extra_arguments("fun f() -> int { return 1; } __print f(2);", Some("E0210"))]
+    #[case::exact(
+        "fun f(a: int, b: float) -> int { return a; } __print f(1, 2.0);",
+        None
+    )]
+    #[case::too_many(
+        "fun f(a: int, b: int) -> int { return a; } __print f(1, 2, 3);",
+        Some("E0226")
+    )]
+    #[case::too_few(
+        "fun f(a: int, b: int) -> int { return a; } __print f(1);",
+        Some("E0226")
+    )]
+    #[case::mistyped(
+        "fun f(a: int, b: float) -> int { return a; } __print f(1, 2);",
+        Some("E0205")
+    )]
+    fn test_call_with_extra_arguments(#[case] input: &str, #[case] code: Option<&str>) {
+        assert_eq!(first_error_code(input), code);
+    }
+
+    #[rstest]
+    fn test_function_redefinition_keeps_the_first_symbol() {
+        let input =
+            "struct P { x: int } fun P() -> int { return 1; } let p: P = P { x: 1 }; __print p.x;";
+
+        assert_eq!(error_codes(input), ["E0204"]);
+    }
+
+    #[rstest]
+    fn test_argument_errors_are_reported_once() {
+        let input = "fun f(a: int) -> int { return a; } __print f(y);";
+
+        let codes = error_codes(input);
+        assert_eq!(codes.iter().filter(|code| **code == "E0201").count(), 1);
+    }
+
+    #[rstest]
+    fn test_call_variable() {
+        assert_eq!(
+            first_error_code("let f: int = 1; __print f();"),
+            Some("E0203")
+        );
+    }
+
+    #[rstest]
+    #[case::inferred("let a: int[] = [1, 2, 3]; __print a[2];", None)]
+    #[case::fill("let a: int[8] = [0];", None)]
+    #[case::exact("let a: int[3] = [1, 2, 3];", None)]
+    #[case::too_few("let a: int[4] = [1, 2, 3];", Some("E0214"))]
+    #[case::too_many("let a: int[2] = [1, 2, 3];", Some("E0214"))]
+    #[case::grid("let g: int[2][3] = [[1, 2, 3], [4, 5, 6]]; __print g[1][2];", None)]
+    #[case::grid_fill("let g: int[2][3] = [[1, 2, 3], 0]; g[0][1] = 4;", None)]
+    #[case::grid_flat("let m: int[2][3] = [1, 2, 3, 4, 5, 6];", Some("E0214"))]
+    #[case::grid_row_too_long("let g: int[2][3] = [[1, 2, 3, 4], 0];", Some("E0214"))]
+    #[case::grid_row_too_short("let g: int[2][3] = [[1, 2], [4, 5, 6]];", Some("E0214"))]
+    #[case::grid_missing_index("let g: int[2][3] = [0]; __print g[1];", Some("E0215"))]
+    #[case::grid_extra_index("let a: int[3] = [0]; a[0][1] = 2;", Some("E0215"))]
+    #[case::last_index("let a: int[3] = [0]; __print a[2];", None)]
+    #[case::past_the_end("let a: int[3] = [0]; __print a[3];", Some("E0212"))]
+    #[case::negative_index("let a: int[3] = [0]; a[-1] = 2;", Some("E0212"))]
+    #[case::constant_index("const N: int = 10; let a: int[3] = [0]; __print a[N];", Some("E0212"))]
+    #[case::folded_index("const N: int = 3; let a: int[3] = [0]; __print a[N - 1];", None)]
+    #[case::grid_out_of_bounds("let g: int[2][3] = [0]; __print g[0][3];", Some("E0212"))]
+    #[case::dynamic_index("let a: int[3] = [0]; let i: int = 5; __print a[i];", None)]
+    #[case::expressions("let x: int = 1; let a: int[] = [x, x * 2, -x];", None)]
+    #[case::mistyped("let a: int[] = [1, 2.0 * 3.0];", Some("E0205"))]
+    #[case::self_reference("let a: int[] = [1, a[0]];", Some("E0201"))]
+    fn test_array_declarations(#[case] input: &str, #[case] code: Option<&str>) {
+        assert_eq!(first_error_code(input), code);
+    }
+
+    #[rstest]
+    #[case::valid(
+        "struct P { x: int, y: int } let p: P = P { y: 1, x: 2 }; p.x = p.y;",
+        None
+    )]
+    #[case::nested(
+        "struct P { x: int } struct R { p: P } let r: R = R { p: P { x: 1 } }; __print r.p.x;",
+        None
+    )]
+    #[case::param(
+        "struct P { x: int } fun f(p: P) -> P { return p; } let q: P = f(P { x: 1 });",
+        None
+    )]
+    #[case::undefined("let p: P = 1;", Some("E0217"))]
+    #[case::before_declaration("struct R { p: P } struct P { x: int }", Some("E0217"))]
+    #[case::unknown_field(
+        "struct P { x: int } let p: P = P { x: 1 }; __print p.y;",
+        Some("E0218")
+    )]
+    #[case::field_of_int("let x: int = 1; __print x.y;", Some("E0218"))]
+    #[case::missing_field("struct P { x: int, y: int } let p: P = P { x: 1 };", Some("E0219"))]
+    #[case::duplicate_field("struct P { x: int, x: int }", Some("E0220"))]
+    #[case::mistyped_field("struct P { x: int } let p: P = P { x: 1.0 };", Some("E0205"))]
+    #[case::mistyped_assignment(
+        "struct P { x: int } let p: P = P { x: 1 }; p.x = true;",
+        Some("E0205")
+    )]
+    fn test_structs(#[case] input: &str, #[case] code: Option<&str>) {
+        assert_eq!(first_error_code(input), code);
+    }
+
+    #[rstest]
+    #[case::ints("let x: int = 1; x += 2; x -= 1; x *= 3; x %= 2; x++; x--;", None)]
+    #[case::floats("let x: float = 1.0; x += 2; x /= 2; x++;", None)]
+    #[case::elements("let a: int[2] = [0]; a[1] += 2; a[0]++;", None)]
+    #[case::int_division("let x: int = 1; x /= 2;", Some("E0205"))]
+    #[case::bool("let b: bool = true; b++;", Some("E0207"))]
+    #[case::undefined("x += 1;", Some("E0201"))]
+    #[case::whole_array("let a: int[2] = [1, 2]; a += 10;", Some("E0207"))]
+    #[case::whole_array_increment("let a: int[2] = [1, 2]; a++;", Some("E0207"))]
+    fn test_compound_assignment(#[case] input: &str, #[case] code: Option<&str>) {
+        assert_eq!(first_error_code(input), code);
+    }
+
+    #[rstest]
+    #[case::ints("let x: int = 1; match (x) { -1 => {} 0 => {} 1 => {} _ => {} }", None)]
+    #[case::colours(
+        "let c: colour = #ff0000; match (c) { #ff0000 => {} #00ff00 => {} }",
+        None
+    )]
+    #[case::else_if(
+        "let x: int = 1; if (x < 0) { x = 0; } else if (x > 1) { x = 1; } else { x = 2; }",
+        None
+    )]
+    #[case::float_scrutinee("match (1.0) { _ => {} }", Some("E0206"))]
+    #[case::mistyped_arm("match (1) { #ff0000 => {} }", Some("E0205"))]
+    #[case::duplicate_arm("match (1) { 1 => {} 2 => {} 1 => {} }", Some("E0222"))]
+    #[case::duplicate_colour("match (#ff0000) { #ff0000 => {} #FF0000 => {} }", Some("E0222"))]
+    #[case::undefined_in_arm("match (1) { 1 => { __print y; } }", Some("E0201"))]
+    fn test_branches(#[case] input: &str, #[case] code: Option<&str>) {
+        assert_eq!(first_error_code(input), code);
+    }
+
+    #[rstest]
+    #[case::in_while("while (true) { break; }", None)]
+    #[case::in_for(
+        "for (let i: int = 0; i < 3; i = i + 1) { if (i == 1) { continue; } }",
+        None
+    )]
+    #[case::top_level("break;", Some("E0221"))]
+    #[case::in_if("if (true) { continue; }", Some("E0221"))]
+    #[case::in_function_in_loop(
+        "while (true) { fun f() -> int { break; return 0; } }",
+        Some("E0221")
+    )]
+    fn test_loop_control(#[case] input: &str, #[case] code: Option<&str>) {
+        assert_eq!(first_error_code(input), code);
+    }
+
+    #[rstest]
+    #[case::colours("let c: colour = if true then #ff0000 else #0000ff;", None)]
+    #[case::nested("let x: int = if true then 1 else if false then 2 else 3;", None)]
+    #[case::int_condition("let x: int = if 1 then 1 else 2;", Some("E0205"))]
+    #[case::arm_types("let x: int = if true then 1 else 2.0;", Some("E0205"))]
+    fn test_conditional(#[case] input: &str, #[case] code: Option<&str>) {
+        assert_eq!(first_error_code(input), code);
+    }
+
+    #[rstest]
+    #[case::arithmetic(
+        "const W: int = 4; const H: int = W * 2 - 1; let a: int[H] = [0];",
+        None
+    )]
+    #[case::casts("const T: int = 7.9 as int; const C: colour = 255 as colour;", None)]
+    #[case::cast_size("const T: int = 2.5 as int; let a: int[T] = [0];", Some("E0225"))]
+    #[case::division_by_zero("const M: int = 7 % 0;", Some("E0227"))]
+    #[case::division_by_zero_constant("const Z: int = 0; const D: float = 1.0 / Z;", Some("E0227"))]
+    #[case::untaken_division("const D: int = if true then 1 else 1 % 0;", None)]
+    #[case::in_function("const W: int = 4; fun f(a: int[W]) -> int { return a[W - 1]; }", None)]
+    #[case::in_return_type(
+        "const N: int = 2; fun f() -> int[N] { let a: int[N] = [0]; return a; }",
+        None
+    )]
+    #[case::variable_return_size(
+        "let n: int = 2; fun f() -> int[n] { let a: int[2] = [0]; return a; }",
+        Some("E0225")
+    )]
+    #[case::assignment("const W: int = 4; W = 5;", Some("E0224"))]
+    #[case::compound_assignment("const W: int = 4; W++;", Some("E0224"))]
+    #[case::variable("let w: int = 4; const W: int = w;", Some("E0223"))]
+    #[case::call("fun f() -> int { return 1; } const W: int = f();", Some("E0223"))]
+    #[case::invalid_cast("const B: bool = 1 as bool;", Some("E0208"))]
+    #[case::type_mismatch("const W: int = 1.5;", Some("E0205"))]
+    #[case::variable_size("let n: int = 2; let a: int[n] = [0];", Some("E0225"))]
+    #[case::negative_size("const N: int = -2; let a: int[N] = [0];", Some("E0225"))]
+    #[case::huge_size(
+        "const A: int = 99999999999999999999; let b: int[A] = [0];",
+        Some("E0225")
+    )]
+    #[case::huge_literal_size("let b: int[99999999999999999999] = [0];", Some("E0225"))]
+    fn test_constants(#[case] input: &str, #[case] code: Option<&str>) {
+        assert_eq!(first_error_code(input), code);
+    }
+
+    #[rstest]
+    fn test_analyze_statements() {
+        fn statements(input: &str) -> Vec<AstNode> {
+            let mut lexer: Lexer<SimpleBuffer> = Lexer::new(input, Path::new(""), None);
+            let tokens = lexer.lex().unwrap();
+            let mut parser = Parser::new(&tokens, Path::new(""));
+
+            match parser.parse().unwrap() {
+                AstNode::Program { statements, .. } => statements.clone(),
+                _ => unreachable!(),
+            }
+        }
+
+        let mut analyser = SemanticAnalyser::new();
+
+        assert!(!analyser
+            .analyze_statements(&statements("let x: int = 1;"))
+            .has_errors());
+        assert!(analyser
+            .analyze_statements(&statements("let y: int = x; let z: int = true;"))
+            .has_errors());
+
+        // `y` was declared by an input with errors, so it is gone again
+        assert!(!analyser
+            .analyze_statements(&statements("let y: float = x / 2;"))
+            .has_errors());
+
+        let mut lexer: Lexer<SimpleBuffer> = Lexer::new("y * 2", Path::new(""), None);
+        let tokens = lexer.lex().unwrap();
+        let expression = Parser::new(&tokens, Path::new(""))
+            .parse_bare_expression()
+            .unwrap();
+
+        assert_matches!(
+            analyser.analyze_expression(&expression),
+            (Type::Float, result) if !result.has_errors()
+        );
+    }
 }
@@ -1,74 +1,407 @@
 use std::{
-    fs::File,
-    io::{BufWriter, Write},
+    collections::{HashSet, VecDeque},
+    fmt::{self, Write},
     path::Path,
 };
 
-use crate::core::{AstNode, Visitor};
-use crate::utils::Result;
+use serde::Deserialize;
 
+use crate::core::{AstNode, TextSpan, Token, TokenKind, Visitor};
+
+/// A position in the source, as a `(line, column)` pair.
+type Position = (usize, usize);
+
+/// The name of the file the formatter style is read from.
+pub const STYLE_FILE: &str = "parlfmt.toml";
+
+/// How the formatter lays programs out.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(from = "StyleFile")]
+pub struct Style {
+    /// Indent with tabs rather than spaces.
+    pub hard_tabs: bool,
+    /// The width of one indent level, in spaces. A style file that sets it
+    /// without setting `hard_tabs` indents with spaces.
+    pub indent_width: usize,
+    /// Statements wider than this have their argument lists wrapped.
+    pub max_width: usize,
+    /// Put spaces around symbolic binary operators such as `+` and `<`.
+    pub spaces_around_operators: bool,
+    /// The blank lines around top-level function declarations, or `None` to
+    /// keep the ones in the source.
+    pub blank_lines_between_functions: Option<usize>,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            hard_tabs: true,
+            indent_width: 4,
+            max_width: 100,
+            spaces_around_operators: true,
+            blank_lines_between_functions: None,
+        }
+    }
+}
+
+/// The options set in a style file, laid over the default style.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct StyleFile {
+    hard_tabs: Option<bool>,
+    indent_width: Option<usize>,
+    max_width: Option<usize>,
+    spaces_around_operators: Option<bool>,
+    blank_lines_between_functions: Option<usize>,
+}
+
+impl From<StyleFile> for Style {
+    fn from(file: StyleFile) -> Self {
+        let default = Style::default();
+        Style {
+            // An indent width only has an effect when indenting with spaces
+            hard_tabs: file.hard_tabs.unwrap_or(file.indent_width.is_none()),
+            indent_width: file.indent_width.unwrap_or(default.indent_width),
+            max_width: file.max_width.unwrap_or(default.max_width),
+            spaces_around_operators: file
+                .spaces_around_operators
+                .unwrap_or(default.spaces_around_operators),
+            blank_lines_between_functions: file
+                .blank_lines_between_functions
+                .or(default.blank_lines_between_functions),
+        }
+    }
+}
+
+impl Style {
+    /// Finds the style file closest to `file`, in its directory or the ones
+    /// above, and reads it. The default style is used if there is none.
+    pub fn find(file: &Path) -> crate::utils::Result<Self> {
+        let file = std::path::absolute(file)?;
+        for dir in file.ancestors().skip(1) {
+            let path = dir.join(STYLE_FILE);
+            if path.is_file() {
+                let style = toml::from_str(&std::fs::read_to_string(&path)?)?;
+                return Ok(style);
+            }
+        }
+        Ok(Self::default())
+    }
+}
+
+/// Lays a program out again from its AST. Comments and blank lines are not
+/// part of the AST, so they are taken from the token stream the program was
+/// parsed from, which must have been lexed with its trivia.
 pub struct Formatter {
+    style: Style,
     tab_level: usize,
-    buff: BufWriter<File>,
+    buff: String,
+    /// Whether the next argument list is put one argument per line.
+    wrap: bool,
+    /// Comments not written yet, in source order.
+    comments: VecDeque<Token>,
+    /// Lines holding anything other than whitespace.
+    occupied: HashSet<usize>,
+    /// The last source line written, or `None` at the start of a block.
+    last_line: Option<usize>,
 }
 
 impl Formatter {
-    pub fn new(file: &Path) -> Self {
-        let file = File::create(file).unwrap();
+    pub fn new(tokens: &[Token], style: Style) -> Self {
+        let mut comments = VecDeque::new();
+        let mut occupied = HashSet::new();
+
+        for token in tokens {
+            match token.kind {
+                TokenKind::Whitespace | TokenKind::Newline => continue,
+                TokenKind::Comment => comments.push_back(token.clone()),
+                _ => {}
+            }
+            occupied.extend(token.span.from_line..=end_line(token));
+        }
 
         Self {
+            style,
             tab_level: 0,
-            buff: BufWriter::new(file),
+            buff: String::new(),
+            wrap: false,
+            comments,
+            occupied,
+            last_line: None,
         }
     }
-}
 
-impl Visitor<Result<()>> for Formatter {
-    fn visit(&mut self, node: &AstNode) -> Result<()> {
-        match node {
-            AstNode::Program { statements } => {
-                for statement in statements {
-                    self.visit(statement)?;
-                    if let AstNode::Assignment { .. } = statement {
-                        writeln!(self.buff, ";")?;
+    /// Formats the program, returning the new source.
+    pub fn format(mut self, program: &AstNode) -> Result<String, fmt::Error> {
+        self.visit(program)?;
+        Ok(self.buff)
+    }
+
+    fn indent(&mut self) -> fmt::Result {
+        match self.style.hard_tabs {
+            true => write!(self.buff, "{}", "\t".repeat(self.tab_level)),
+            false => write!(
+                self.buff,
+                "{}",
+                " ".repeat(self.tab_level * self.style.indent_width)
+            ),
+        }
+    }
+
+    /// Whether any line written since `mark` is wider than the style allows.
+    fn too_wide(&self, mark: usize) -> bool {
+        self.buff[mark..].lines().any(|line| {
+            let width: usize = line
+                .chars()
+                .map(|c| {
+                    if c == '\t' {
+                        self.style.indent_width
+                    } else {
+                        1
                     }
-                    writeln!(self.buff, "\n")?;
+                })
+                .sum();
+            width > self.style.max_width
+        })
+    }
+
+    /// Writes a blank line if there was at least one between the last line
+    /// written and `line`. Runs of blank lines are collapsed into one.
+    fn separate(&mut self, line: usize) -> fmt::Result {
+        if let Some(last_line) = self.last_line {
+            if (last_line + 1..line).any(|line| !self.occupied.contains(&line)) {
+                writeln!(self.buff)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the comments starting before `position`, each on its own line.
+    fn leading_comments(&mut self, position: Position) -> fmt::Result {
+        while let Some(comment) = self.comments.front() {
+            if start(&comment.span) >= position {
+                break;
+            }
+            let comment = self.comments.pop_front().unwrap();
+            self.separate(comment.span.from_line)?;
+            self.indent()?;
+            writeln!(self.buff, "{}", comment.span.lexeme.trim_end())?;
+            self.last_line = Some(end_line(&comment));
+        }
+        Ok(())
+    }
+
+    /// Writes the comments on `line` starting before `limit` at the end of
+    /// the current line.
+    fn trailing_comments(&mut self, line: usize, limit: Position) -> fmt::Result {
+        let (trailing, rest) = self.comments.drain(..).partition::<Vec<_>, _>(|comment| {
+            comment.span.from_line == line && start(&comment.span) < limit
+        });
+        self.comments = rest.into();
+
+        for comment in trailing {
+            write!(self.buff, " {}", comment.span.lexeme.trim_end())?;
+        }
+        Ok(())
+    }
+
+    /// Writes a list of statements one per line, followed by the comments
+    /// left before `end`.
+    fn statements(&mut self, statements: &[AstNode], end: Position) -> fmt::Result {
+        let top_level = self.tab_level == 0;
+
+        for (i, statement) in statements.iter().enumerate() {
+            let span = statement.span();
+
+            if let Some(blank_lines) = self.style.blank_lines_between_functions {
+                let is_function = |node: &AstNode| matches!(node, AstNode::FunctionDecl { .. });
+                if top_level && i > 0 && (is_function(statement) || is_function(&statements[i - 1]))
+                {
+                    write!(self.buff, "{}", "\n".repeat(blank_lines))?;
+                    self.last_line = None;
                 }
-                Ok(())
             }
 
-            AstNode::ArrayAccess { identifier, index } => {
-                write!(self.buff, "{}[", identifier.span.lexeme)?;
-                self.visit(index)?;
-                write!(self.buff, "]")?;
-                Ok(())
+            self.leading_comments(start(span))?;
+            self.separate(span.from_line)?;
+
+            let mark = self.buff.len();
+            self.statement(statement)?;
+
+            // Statements holding blocks are never laid out again, as their
+            // blocks have already taken the comments inside them
+            let simple = !matches!(
+                statement,
+                AstNode::If { .. }
+                    | AstNode::Match { .. }
+                    | AstNode::For { .. }
+                    | AstNode::While { .. }
+                    | AstNode::FunctionDecl { .. }
+                    | AstNode::Block { .. }
+            );
+            if simple && self.too_wide(mark) {
+                self.buff.truncate(mark);
+                self.wrap = true;
+                self.statement(statement)?;
+                self.wrap = false;
+            }
+
+            let next = statements.get(i + 1).map_or(end, |next| start(next.span()));
+            self.trailing_comments(span.to_line, next)?;
+            writeln!(self.buff)?;
+            self.last_line = Some(span.to_line);
+        }
+
+        self.leading_comments(end)
+    }
+
+    fn statement(&mut self, statement: &AstNode) -> fmt::Result {
+        self.indent()?;
+        self.visit(statement)?;
+        if let AstNode::Assignment { .. } = statement {
+            write!(self.buff, ";")?;
+        }
+        Ok(())
+    }
+
+    fn separated(&mut self, nodes: &[AstNode]) -> fmt::Result {
+        for (i, node) in nodes.iter().enumerate() {
+            if i > 0 {
+                write!(self.buff, ", ")?;
+            }
+            self.visit(node)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the parameters of a function, which are between `from` and the
+    /// start of its body at `end`. If any comments are among them, the
+    /// parameters are put one per line, each followed by the comments after
+    /// it on its line.
+    fn parameters(&mut self, params: &[AstNode], from: Position, end: Position) -> fmt::Result {
+        let commented = self
+            .comments
+            .front()
+            .is_some_and(|comment| (from..end).contains(&start(&comment.span)));
+        if params.is_empty() || !commented {
+            return self.separated(params);
+        }
+
+        self.tab_level += 1;
+        self.last_line = None;
+        for (i, param) in params.iter().enumerate() {
+            writeln!(self.buff)?;
+            self.leading_comments(start(param.span()))?;
+            self.indent()?;
+            self.visit(param)?;
+            if i + 1 < params.len() {
+                write!(self.buff, ",")?;
+            }
+
+            let next = params.get(i + 1).map_or(end, |next| start(next.span()));
+            self.trailing_comments(param.span().to_line, next)?;
+            self.last_line = Some(param.span().to_line);
+        }
+        self.tab_level -= 1;
+
+        writeln!(self.buff)?;
+        self.indent()
+    }
+
+    /// Writes the `[i]` indices of an array element, one per dimension.
+    fn indices(&mut self, indices: &[AstNode]) -> fmt::Result {
+        for index in indices {
+            write!(self.buff, "[")?;
+            self.visit(index)?;
+            write!(self.buff, "]")?;
+        }
+        Ok(())
+    }
+
+    /// Writes an argument list, one argument per line if it is to be wrapped.
+    /// Only the outermost list of a statement is wrapped.
+    fn arguments(&mut self, args: &[AstNode]) -> fmt::Result {
+        if !std::mem::take(&mut self.wrap) {
+            return self.separated(args);
+        }
+
+        self.tab_level += 1;
+        for (i, arg) in args.iter().enumerate() {
+            if i > 0 {
+                write!(self.buff, ",")?;
+            }
+            writeln!(self.buff)?;
+            self.indent()?;
+            self.visit(arg)?;
+        }
+        self.tab_level -= 1;
+        Ok(())
+    }
+}
+
+fn start(span: &TextSpan) -> Position {
+    (span.from_line, span.from_col)
+}
+
+/// The line a token ends on. Line comments take their newline with them, which
+/// must not count as part of the comment.
+fn end_line(token: &Token) -> usize {
+    token.span.from_line + token.span.lexeme.trim_end().matches('\n').count()
+}
+
+impl Visitor<fmt::Result> for Formatter {
+    fn visit(&mut self, node: &AstNode) -> fmt::Result {
+        match node {
+            AstNode::Program { statements, .. } => {
+                self.last_line = None;
+                self.statements(statements, (usize::MAX, usize::MAX))
+            }
+
+            AstNode::ArrayAccess {
+                identifier,
+                indices,
+                ..
+            } => {
+                write!(self.buff, "{}", identifier.span.lexeme)?;
+                self.indices(indices)
             }
 
             AstNode::VarDecArray {
                 identifier,
                 element_type,
-                size: _,
+                dimensions,
                 elements,
+                ..
             } => {
                 write!(
                     self.buff,
-                    "let {}: {}[] = [",
+                    "let {}: {}",
                     identifier.span.lexeme, element_type.span.lexeme
                 )?;
-                for (i, element) in elements.iter().enumerate() {
-                    self.visit(element)?;
-                    if i != elements.len() - 1 {
-                        write!(self.buff, ", ")?;
+                for size in dimensions {
+                    match size {
+                        Some(size) => write!(self.buff, "[{}]", size)?,
+                        None => write!(self.buff, "[]")?,
                     }
                 }
+                write!(self.buff, " = [")?;
+                self.separated(elements)?;
                 write!(self.buff, "];")?;
                 Ok(())
             }
 
+            AstNode::ArrayLiteral { elements, .. } => {
+                write!(self.buff, "[")?;
+                self.separated(elements)?;
+                write!(self.buff, "]")?;
+                Ok(())
+            }
+
             AstNode::VarDec {
                 identifier,
                 var_type,
                 expression,
+                ..
             } => {
                 write!(self.buff, "let {}", identifier.span.lexeme)?;
                 write!(self.buff, ": {}", var_type.span.lexeme)?;
@@ -78,14 +411,28 @@ impl Visitor<Result<()>> for Formatter {
                 Ok(())
             }
 
-            AstNode::Delay { expression } => {
+            AstNode::ConstDec {
+                identifier,
+                const_type,
+                expression,
+                ..
+            } => {
+                write!(self.buff, "const {}", identifier.span.lexeme)?;
+                write!(self.buff, ": {}", const_type.span.lexeme)?;
+                write!(self.buff, " = ")?;
+                self.visit(expression)?;
+                write!(self.buff, ";")?;
+                Ok(())
+            }
+
+            AstNode::Delay { expression, .. } => {
                 write!(self.buff, "__delay ")?;
                 self.visit(expression)?;
                 write!(self.buff, ";")?;
                 Ok(())
             }
 
-            AstNode::Print { expression } => {
+            AstNode::Print { expression, .. } => {
                 write!(self.buff, "__print ")?;
                 self.visit(expression)?;
                 write!(self.buff, ";")?;
@@ -96,37 +443,91 @@ impl Visitor<Result<()>> for Formatter {
                 condition,
                 if_true,
                 if_false,
+                ..
             } => {
                 write!(self.buff, "if (")?;
                 self.visit(condition)?;
                 write!(self.buff, ")")?;
-                let tmp_tab_level = self.tab_level;
                 self.visit(if_true)?;
-                self.tab_level = tmp_tab_level;
                 if let Some(if_false) = if_false {
-                    write!(self.buff, " else")?;
+                    // A comment after the `}` stays with it, so `else` has to
+                    // go on the next line
+                    let mark = self.buff.len();
+                    self.trailing_comments(if_true.span().to_line, start(if_false.span()))?;
+                    if self.buff.len() > mark {
+                        writeln!(self.buff)?;
+                        self.indent()?;
+                        write!(self.buff, "else")?;
+                    } else {
+                        write!(self.buff, " else")?;
+                    }
+
+                    if let AstNode::If { .. } = if_false.as_ref() {
+                        write!(self.buff, " ")?;
+                    }
                     self.visit(if_false)?;
                 }
 
                 Ok(())
             }
 
+            AstNode::Match {
+                scrutinee,
+                arms,
+                default,
+                span,
+            } => {
+                write!(self.buff, "match (")?;
+                self.visit(scrutinee)?;
+                writeln!(self.buff, ") {{")?;
+
+                self.tab_level += 1;
+                for (literal, block) in arms {
+                    self.leading_comments(start(literal.span()))?;
+                    self.indent()?;
+                    self.visit(literal)?;
+                    write!(self.buff, " =>")?;
+                    self.visit(block)?;
+                    writeln!(self.buff)?;
+                }
+                if let Some(default) = default {
+                    self.leading_comments(start(default.span()))?;
+                    self.indent()?;
+                    write!(self.buff, "_ =>")?;
+                    self.visit(default)?;
+                    writeln!(self.buff)?;
+                }
+                self.leading_comments((span.to_line, span.to_col - 1))?;
+                self.tab_level -= 1;
+
+                self.indent()?;
+                write!(self.buff, "}}")?;
+                Ok(())
+            }
+
             AstNode::Assignment {
                 identifier,
-                index,
+                indices,
+                fields,
+                operator,
                 expression,
+                ..
             } => {
                 write!(self.buff, "{}", identifier.span.lexeme)?;
-                if let Some(index) = index {
-                    write!(self.buff, "[")?;
-                    self.visit(index)?;
-                    write!(self.buff, "]")?;
+                self.indices(indices)?;
+                for field in fields {
+                    write!(self.buff, ".{}", field.span.lexeme)?;
                 }
 
-                write!(self.buff, " = ")?;
-
-                self.visit(expression)?;
-                Ok(())
+                match operator.kind {
+                    TokenKind::PlusPlus | TokenKind::MinusMinus => {
+                        write!(self.buff, "{}", operator.span.lexeme)
+                    }
+                    _ => {
+                        write!(self.buff, " {} ", operator.span.lexeme)?;
+                        self.visit(expression)
+                    }
+                }
             }
 
             AstNode::For {
@@ -134,6 +535,7 @@ impl Visitor<Result<()>> for Formatter {
                 condition,
                 increment,
                 body,
+                ..
             } => {
                 write!(self.buff, "for (")?;
 
@@ -143,6 +545,7 @@ impl Visitor<Result<()>> for Formatter {
                         identifier,
                         var_type,
                         expression,
+                        ..
                     } = node.as_ref()
                     {
                         write!(self.buff, "let {}", identifier.span.lexeme)?;
@@ -164,46 +567,49 @@ impl Visitor<Result<()>> for Formatter {
                 Ok(())
             }
 
-            AstNode::Return { expression } => {
+            AstNode::Break { .. } => write!(self.buff, "break;"),
+
+            AstNode::Continue { .. } => write!(self.buff, "continue;"),
+
+            AstNode::Return { expression, .. } => {
                 write!(self.buff, "return ")?;
                 self.visit(expression)?;
                 write!(self.buff, ";")?;
                 Ok(())
             }
 
-            AstNode::Block { statements } => {
-                writeln!(self.buff, " {{")?;
+            AstNode::Block { statements, span } => {
+                // The position of the closing brace
+                let end = (span.to_line, span.to_col - 1);
+                let first = statements.first().map_or(end, |first| start(first.span()));
+
+                write!(self.buff, " {{")?;
+                self.trailing_comments(span.from_line, first)?;
+                writeln!(self.buff)?;
+
                 self.tab_level += 1;
-                for statement in statements {
-                    write!(self.buff, "{}", "\t".repeat(self.tab_level))?;
-                    self.visit(statement)?;
-                    if let AstNode::Assignment { .. } = statement {
-                        writeln!(self.buff, ";")?;
-                    } else {
-                        writeln!(self.buff)?;
-                    }
-                }
+                self.last_line = None;
+                self.statements(statements, end)?;
                 self.tab_level -= 1;
 
-                write!(self.buff, "{}}}", "\t".repeat(self.tab_level))?;
+                self.indent()?;
+                write!(self.buff, "}}")?;
                 Ok(())
             }
 
             AstNode::Expression {
                 casted_type,
                 expr: bin_op,
+                ..
             } => {
                 self.visit(bin_op)?;
-                match casted_type {
-                    Some(casted_type) => {
-                        write!(self.buff, " as {}", casted_type.span.lexeme)?;
-                    }
-                    None => {}
+                if let Some(casted_type) = casted_type {
+                    write!(self.buff, " as {}", casted_type.span.lexeme)?;
                 }
                 Ok(())
             }
 
-            AstNode::SubExpression { bin_op } => {
+            AstNode::SubExpression { bin_op, .. } => {
                 write!(self.buff, "(")?;
                 self.visit(bin_op)?;
                 write!(self.buff, ")")?;
@@ -214,43 +620,27 @@ impl Visitor<Result<()>> for Formatter {
                 identifier,
                 params,
                 return_type,
+                return_dimensions,
                 block,
+                ..
             } => {
                 write!(self.buff, "fun {}", identifier)?;
                 write!(self.buff, "(")?;
-
-                if params.is_empty() {
-                    write!(self.buff, ") -> {}", return_type)?;
-                    self.visit(block)?;
-                    return Ok(());
-                } else {
-                    for param in params.iter().take(params.len() - 1) {
-                        self.visit(param)?;
-                        write!(self.buff, ", ")?;
-                    }
-
-                    if let Some(last) = params.last() {
-                        self.visit(last)?;
-                    }
-                }
-
+                self.parameters(params, start(&identifier.span), start(block.span()))?;
                 write!(self.buff, ") -> {}", return_type)?;
-
-                // if let Some(return_type_size) = return_type_size {
-                //     write!(self.buff, "[{}]", return_type_size)?;
-                // }
-
+                for size in return_dimensions {
+                    write!(self.buff, "[{}]", size)?;
+                }
                 self.visit(block)?;
                 Ok(())
             }
 
-            AstNode::While { condition, body } => {
+            AstNode::While {
+                condition, body, ..
+            } => {
                 write!(self.buff, "while (")?;
-                let prev_tab_level = self.tab_level;
-                self.tab_level = 0;
                 self.visit(condition)?;
-                self.tab_level = prev_tab_level;
-                write!(self.buff, ") ")?;
+                write!(self.buff, ")")?;
                 self.visit(body)?;
                 Ok(())
             }
@@ -259,18 +649,20 @@ impl Visitor<Result<()>> for Formatter {
                 loc_x,
                 loc_y,
                 colour,
+                ..
             } => {
-                write!(self.buff, "__write ")?;
-                self.visit(loc_x)?;
-                write!(self.buff, ", ")?;
-                self.visit(loc_y)?;
-                write!(self.buff, ", ")?;
-                self.visit(colour)?;
+                write!(self.buff, "__write")?;
+                if !self.wrap {
+                    write!(self.buff, " ")?;
+                }
+                // The arguments are not kept in a list, so one is made here
+                let args = [loc_x, loc_y, colour].map(|arg| arg.as_ref().clone());
+                self.arguments(&args)?;
                 write!(self.buff, ";")?;
                 Ok(())
             }
 
-            AstNode::PadClear { expr } => {
+            AstNode::PadClear { expr, .. } => {
                 write!(self.buff, "__clear ")?;
 
                 self.visit(expr)?;
@@ -286,17 +678,14 @@ impl Visitor<Result<()>> for Formatter {
                 width,
                 height,
                 colour,
+                ..
             } => {
-                write!(self.buff, "__write_box ")?;
-                self.visit(loc_x)?;
-                write!(self.buff, ", ")?;
-                self.visit(loc_y)?;
-                write!(self.buff, ", ")?;
-                self.visit(width)?;
-                write!(self.buff, ", ")?;
-                self.visit(height)?;
-                write!(self.buff, ", ")?;
-                self.visit(colour)?;
+                write!(self.buff, "__write_box")?;
+                if !self.wrap {
+                    write!(self.buff, " ")?;
+                }
+                let args = [loc_x, loc_y, width, height, colour].map(|arg| arg.as_ref().clone());
+                self.arguments(&args)?;
                 write!(self.buff, ";")?;
                 Ok(())
             }
@@ -305,20 +694,45 @@ impl Visitor<Result<()>> for Formatter {
                 left,
                 operator,
                 right,
+                ..
             } => {
                 self.visit(left)?;
-                write!(self.buff, " {} ", operator.span.lexeme)?;
+                // `and` and `or` cannot be written next to their operands
+                let word = operator.span.lexeme.chars().all(char::is_alphabetic);
+                match self.style.spaces_around_operators || word {
+                    true => write!(self.buff, " {} ", operator.span.lexeme)?,
+                    false => write!(self.buff, "{}", operator.span.lexeme)?,
+                }
                 self.visit(right)?;
                 Ok(())
             }
 
-            AstNode::UnaryOp { operator, expr } => {
-                write!(self.buff, "{} ", operator.span.lexeme)?;
+            AstNode::Conditional {
+                condition,
+                if_true,
+                if_false,
+                ..
+            } => {
+                write!(self.buff, "if ")?;
+                self.visit(condition)?;
+                write!(self.buff, " then ")?;
+                self.visit(if_true)?;
+                write!(self.buff, " else ")?;
+                self.visit(if_false)?;
+                Ok(())
+            }
+
+            AstNode::UnaryOp { operator, expr, .. } => {
+                write!(self.buff, "{}", operator.span.lexeme)?;
+                // `not` needs a space before its operand, `-` does not
+                if operator.kind == TokenKind::Not {
+                    write!(self.buff, " ")?;
+                }
                 self.visit(expr)?;
                 Ok(())
             }
 
-            AstNode::PadRead { x, y } => {
+            AstNode::PadRead { x, y, .. } => {
                 write!(self.buff, "__read ")?;
                 self.visit(x)?;
                 write!(self.buff, ", ")?;
@@ -329,7 +743,8 @@ impl Visitor<Result<()>> for Formatter {
             AstNode::FormalParam {
                 identifier,
                 param_type,
-                length: index,
+                dimensions,
+                ..
             } => {
                 write!(
                     self.buff,
@@ -337,33 +752,29 @@ impl Visitor<Result<()>> for Formatter {
                     identifier.span.lexeme, param_type.span.lexeme
                 )?;
 
-                if let Some(index) = index {
-                    write!(self.buff, "[{}]", index)?;
+                for size in dimensions {
+                    write!(self.buff, "[{}]", size)?;
                 }
 
                 Ok(())
             }
 
-            AstNode::PadRandI { upper_bound } => {
+            AstNode::PadRandI { upper_bound, .. } => {
                 write!(self.buff, "__randi ")?;
                 self.visit(upper_bound)?;
                 Ok(())
             }
 
-            AstNode::FunctionCall { identifier, args } => {
+            AstNode::FunctionCall {
+                identifier, args, ..
+            } => {
                 write!(self.buff, "{}(", identifier.span.lexeme)?;
-
-                if !args.is_empty() {
-                    for arg in args.iter().take(args.len() - 1) {
-                        self.visit(arg)?;
-                        write!(self.buff, ", ")?;
-                    }
-
-                    if let Some(last) = args.last() {
-                        self.visit(last)?;
-                    }
+                let wrapped = self.wrap && !args.is_empty();
+                self.arguments(args)?;
+                if wrapped {
+                    writeln!(self.buff)?;
+                    self.indent()?;
                 }
-
                 write!(self.buff, ")")?;
 
                 Ok(())
@@ -374,6 +785,66 @@ impl Visitor<Result<()>> for Formatter {
                 Ok(())
             }
 
+            AstNode::StructDecl {
+                identifier, fields, ..
+            } => {
+                write!(self.buff, "struct {} {{", identifier.span.lexeme)?;
+                // A struct too wide for one line gets a field per line
+                if std::mem::take(&mut self.wrap) {
+                    self.tab_level += 1;
+                    for (name, field_type) in fields {
+                        writeln!(self.buff)?;
+                        self.indent()?;
+                        write!(
+                            self.buff,
+                            "{}: {},",
+                            name.span.lexeme, field_type.span.lexeme
+                        )?;
+                    }
+                    self.tab_level -= 1;
+                    writeln!(self.buff)?;
+                    self.indent()?;
+                    return write!(self.buff, "}}");
+                }
+                for (i, (name, field_type)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(self.buff, ",")?;
+                    }
+                    write!(
+                        self.buff,
+                        " {}: {}",
+                        name.span.lexeme, field_type.span.lexeme
+                    )?;
+                }
+                write!(self.buff, " }}")?;
+                Ok(())
+            }
+
+            AstNode::StructLiteral {
+                identifier, fields, ..
+            } => {
+                write!(self.buff, "{} {{", identifier.span.lexeme)?;
+                for (i, (name, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(self.buff, ",")?;
+                    }
+                    write!(self.buff, " {}: ", name.span.lexeme)?;
+                    self.visit(value)?;
+                }
+                write!(self.buff, " }}")?;
+                Ok(())
+            }
+
+            AstNode::FieldAccess {
+                identifier, fields, ..
+            } => {
+                write!(self.buff, "{}", identifier.span.lexeme)?;
+                for field in fields {
+                    write!(self.buff, ".{}", field.span.lexeme)?;
+                }
+                Ok(())
+            }
+
             AstNode::IntLiteral(token) => {
                 write!(self.buff, "{}", token.span.lexeme)?;
                 Ok(())
@@ -393,16 +864,181 @@ impl Visitor<Result<()>> for Formatter {
                 write!(self.buff, "{}", token.span.lexeme)?;
                 Ok(())
             }
-            AstNode::PadWidth => {
+            AstNode::PadWidth { .. } => {
                 write!(self.buff, "__width")?;
                 Ok(())
             }
-            AstNode::PadHeight => {
+            AstNode::PadHeight { .. } => {
                 write!(self.buff, "__height")?;
                 Ok(())
             }
 
-            AstNode::EndOfFile => Ok(()),
+            AstNode::EndOfFile { .. } => Ok(()),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::{lexing::Lexer, parsing::Parser, utils::SimpleBuffer};
+
+    use super::*;
+    use rstest::rstest;
+
+    fn format(input: &str) -> String {
+        format_with(input, Style::default())
+    }
+
+    fn format_with(input: &str, style: Style) -> String {
+        let mut lexer: Lexer<SimpleBuffer> = Lexer::new(input, Path::new(""), None);
+        let tokens = lexer.lex_with_trivia().unwrap();
+        let program = tokens
+            .iter()
+            .filter(|token| !token.kind.is_trivia())
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut parser = Parser::new(&program, Path::new(""));
+        let ast = parser.parse().unwrap();
+        Formatter::new(&tokens, style).format(ast).unwrap()
+    }
+
+    #[rstest]
+    #[case::layout(
+        "let x:int=-1;\nif (not true) { x = 2; } else { x = 3; }",
+        "let x: int = -1;\nif (not true) {\n\tx = 2;\n} else {\n\tx = 3;\n}\n"
+    )]
+    #[case::comments(
+        "// head\nlet x: int = 1; // one\nfun f() -> int { // open\n\t// body\n\treturn 1;\n\t// close\n}\n/* tail */",
+        "// head\nlet x: int = 1; // one\nfun f() -> int { // open\n\t// body\n\treturn 1;\n\t// close\n}\n/* tail */\n"
+    )]
+    #[case::blank_lines(
+        "\n\nlet x: int = 1;\n\n\n\nlet y: int = 2;\nlet z: int = 3;\n",
+        "let x: int = 1;\n\nlet y: int = 2;\nlet z: int = 3;\n"
+    )]
+    #[case::parameter_comment(
+        "fun f(a: int, // first\n      b: int) -> int {\n\treturn a;\n}",
+        "fun f(\n\ta: int, // first\n\tb: int\n) -> int {\n\treturn a;\n}\n"
+    )]
+    #[case::leading_parameter_comment(
+        "fun f(\n\t// lead\n\ta: int) -> int { return a; }",
+        "fun f(\n\t// lead\n\ta: int\n) -> int {\n\treturn a;\n}\n"
+    )]
+    #[case::comment_before_else(
+        "if (a) {\n\t__print 1;\n} // done\nelse {\n\t__print 2;\n}",
+        "if (a) {\n\t__print 1;\n} // done\nelse {\n\t__print 2;\n}\n"
+    )]
+    #[case::comment_before_else_if(
+        "if (a) { } /* one */ else if (b) { }",
+        "if (a) {\n} /* one */\nelse if (b) {\n}\n"
+    )]
+    #[case::array("let a: int[3] = [1,2,3];", "let a: int[3] = [1, 2, 3];\n")]
+    #[case::inferred_array("let a: int[ ] = [1,2];", "let a: int[] = [1, 2];\n")]
+    #[case::compound_assignment(
+        "for (let i: int = 0; i < 3; i ++) { a[i]*=2; p.x -= 1; }",
+        "for (let i: int = 0; i < 3; i++) {\n\ta[i] *= 2;\n\tp.x -= 1;\n}\n"
+    )]
+    #[case::else_if(
+        "if (a) { } else if (b) { } else { }",
+        "if (a) {\n} else if (b) {\n} else {\n}\n"
+    )]
+    #[case::match_arms(
+        "match (x) { -1 => { __print 1; } #ff0000 => {} _ => {} }",
+        "match (x) {\n\t-1 => {\n\t\t__print 1;\n\t}\n\t#ff0000 => {\n\t}\n\t_ => {\n\t}\n}\n"
+    )]
+    #[case::loop_control(
+        "while (true) { if (x) { continue ; } break; }",
+        "while (true) {\n\tif (x) {\n\t\tcontinue;\n\t}\n\tbreak;\n}\n"
+    )]
+    #[case::structs(
+        "struct P{x:int,y:int,} let p: P = P{x:1,y:2}; p.x = p.y;",
+        "struct P { x: int, y: int }\nlet p: P = P { x: 1, y: 2 };\np.x = p.y;\n"
+    )]
+    #[case::constants(
+        "const W:int=4; let a: int[W] = [0];",
+        "const W: int = 4;\nlet a: int[W] = [0];\n"
+    )]
+    #[case::array_return_type(
+        "fun f() -> int[N][2] { return g(); }",
+        "fun f() -> int[N][2] {\n\treturn g();\n}\n"
+    )]
+    #[case::conditional(
+        "let c: colour = if x>1 then #ff0000 else(if y then #00ff00 else #0000ff);",
+        "let c: colour = if x > 1 then #ff0000 else (if y then #00ff00 else #0000ff);\n"
+    )]
+    #[case::grid(
+        "let g: int[][2] = [[1,2],[3,4]]; g[1][0] = g [0][1];",
+        "let g: int[][2] = [[1, 2], [3, 4]];\ng[1][0] = g[0][1];\n"
+    )]
+    fn test_format(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(format(input), expected);
+    }
+
+    #[rstest]
+    fn test_format_is_idempotent() {
+        let input = "/* a\n   b */\nfun f(a: int) -> int {\n\n\twhile (a < 3) { a = a + 1; } // loop\n\n\n\tfor (let i: int = 0; i < 3; i = i + 1) {\n\t\t__print i;\n\t}\n\treturn a;\n}\n__write_box 1, 2, 3, 4, #ffffff;\n// end";
+        let once = format(input);
+        assert_eq!(format(&once), once);
+    }
+
+    #[rstest]
+    fn test_style_file() {
+        let style: Style = toml::from_str("indent_width = 2\nhard_tabs = false").unwrap();
+        assert_eq!(
+            style,
+            Style {
+                indent_width: 2,
+                hard_tabs: false,
+                ..Style::default()
+            }
+        );
+        assert!(toml::from_str::<Style>("tab_width = 2").is_err());
+    }
+
+    #[rstest]
+    fn test_style_file_indent_width_implies_spaces() {
+        let style: Style = toml::from_str("indent_width = 2").unwrap();
+        assert!(!style.hard_tabs);
+        assert_eq!(
+            format_with("while (true) { __print 1; }", style),
+            "while (true) {\n  __print 1;\n}\n"
+        );
+
+        let style: Style = toml::from_str("indent_width = 2\nhard_tabs = true").unwrap();
+        assert!(style.hard_tabs);
+    }
+
+    #[rstest]
+    #[case::indent(
+        Style { hard_tabs: false, indent_width: 2, ..Style::default() },
+        "while (true) { __print 1; }",
+        "while (true) {\n  __print 1;\n}\n"
+    )]
+    #[case::operators(
+        Style { spaces_around_operators: false, ..Style::default() },
+        "let x: bool = 1 + 2 < 4 and true;",
+        "let x: bool = 1+2<4 and true;\n"
+    )]
+    #[case::blank_lines(
+        Style { blank_lines_between_functions: Some(1), ..Style::default() },
+        "fun f() -> int { return 1; }\n\n\nfun g() -> int { return 2; }\n__print 1;\n__print 2;",
+        "fun f() -> int {\n\treturn 1;\n}\n\nfun g() -> int {\n\treturn 2;\n}\n\n__print 1;\n__print 2;\n"
+    )]
+    #[case::wrap_call(
+        Style { max_width: 20, ..Style::default() },
+        "let x: int = f(1, g(2, 3));",
+        "let x: int = f(\n\t1,\n\tg(2, 3)\n);\n"
+    )]
+    #[case::wrap_box(
+        Style { max_width: 20, ..Style::default() },
+        "__write_box 1, 2, 3, 4, #ffffff;",
+        "__write_box\n\t1,\n\t2,\n\t3,\n\t4,\n\t#ffffff;\n"
+    )]
+    fn test_format_style(#[case] style: Style, #[case] input: &str, #[case] expected: &str) {
+        let formatted = format_with(input, style.clone());
+        assert_eq!(formatted, expected);
+        assert_eq!(format_with(&formatted, style), formatted);
+    }
+}
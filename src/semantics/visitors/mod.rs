@@ -2,6 +2,6 @@ mod analyzer;
 mod formatter;
 mod tree_printer;
 
-pub use analyzer::SemanticAnalyser;
-pub use formatter::Formatter;
+pub use analyzer::{Reference, SemanticAnalyser, SemanticResult};
+pub use formatter::{Formatter, Style};
 pub use tree_printer::TreePrinter;
@@ -1,47 +1,65 @@
+use std::io::{self, Stdout, Write};
+
 use crate::core::{AstNode, Visitor};
 use crate::utils::Result;
 
-pub struct TreePrinter {
+pub struct TreePrinter<W: Write = Stdout> {
     tab_level: usize,
+    out: W,
 }
 
 impl TreePrinter {
     pub fn new() -> Self {
-        Self { tab_level: 0 }
+        Self::with_output(io::stdout())
+    }
+}
+
+impl<W: Write> TreePrinter<W> {
+    /// Prints the tree to `out` instead of stdout.
+    pub fn with_output(out: W) -> Self {
+        Self { tab_level: 0, out }
     }
 
     fn tab(&self) -> String {
         "  ".repeat(self.tab_level)
     }
-    fn print_tab(&self) {
-        print!("{}", self.tab());
+    fn print_tab(&mut self) -> io::Result<()> {
+        let tab = self.tab();
+        write!(self.out, "{}", tab)
     }
 }
 
-impl Visitor<Result<()>> for TreePrinter {
+impl<W: Write> Visitor<Result<()>> for TreePrinter<W> {
     fn visit(&mut self, node: &AstNode) -> Result<()> {
         match node {
-            AstNode::Program { statements } => {
-                println!("Program");
+            AstNode::Program { statements, .. } => {
+                writeln!(self.out, "Program")?;
                 self.tab_level += 1;
                 for statement in statements {
-                    self.print_tab();
+                    self.print_tab()?;
                     self.visit(statement)?;
-                    println!();
+                    writeln!(self.out)?;
                 }
                 self.tab_level -= 1;
-                println!();
+                writeln!(self.out)?;
                 Ok(())
             }
 
-            AstNode::ArrayAccess { identifier, index } => {
-                println!("ArrayAccess");
+            AstNode::ArrayAccess {
+                identifier,
+                indices,
+                ..
+            } => {
+                writeln!(self.out, "ArrayAccess")?;
                 self.tab_level += 1;
-                self.print_tab();
-                println!("Identifier: {}", identifier);
-                self.print_tab();
-                print!("Index: ");
-                self.visit(index)?;
+                self.print_tab()?;
+                writeln!(self.out, "Identifier: {}", identifier)?;
+                for index in indices {
+                    self.print_tab()?;
+                    write!(self.out, "Index: ")?;
+                    self.visit(index)?;
+                    writeln!(self.out)?;
+                }
                 self.tab_level -= 1;
                 Ok(())
             }
@@ -49,63 +67,139 @@ impl Visitor<Result<()>> for TreePrinter {
             AstNode::VarDecArray {
                 identifier,
                 element_type,
-                size,
+                dimensions,
                 elements,
+                ..
             } => {
-                println!("VarDecArray");
+                writeln!(self.out, "VarDecArray")?;
                 self.tab_level += 1;
-                self.print_tab();
-                println!("Identifier: {}", identifier);
-                self.print_tab();
-                println!("Element Type: {}", element_type);
-                self.print_tab();
-                println!("Size: {}", size);
-                self.print_tab();
-                println!("Elements: ");
+                self.print_tab()?;
+                writeln!(self.out, "Identifier: {}", identifier)?;
+                self.print_tab()?;
+                writeln!(self.out, "Element Type: {}", element_type)?;
+                self.print_tab()?;
+                let sizes = dimensions
+                    .iter()
+                    .map(|size| match size {
+                        Some(size) => size.to_string(),
+                        None => "inferred".to_string(),
+                    })
+                    .collect::<Vec<_>>();
+                writeln!(self.out, "Size: {}", sizes.join(" x "))?;
+                self.print_tab()?;
+                writeln!(self.out, "Elements: ")?;
                 self.tab_level += 1;
-                self.print_tab();
+                self.print_tab()?;
                 for element in elements {
                     self.visit(element)?;
-                    print!(", ");
+                    write!(self.out, ", ")?;
                 }
                 self.tab_level -= 1;
                 self.tab_level -= 1;
                 Ok(())
             }
 
+            AstNode::StructDecl {
+                identifier, fields, ..
+            } => {
+                writeln!(self.out, "StructDecl")?;
+                self.tab_level += 1;
+                self.print_tab()?;
+                writeln!(self.out, "Identifier: {}", identifier)?;
+                for (name, field_type) in fields {
+                    self.print_tab()?;
+                    writeln!(self.out, "Field: {}: {}", name, field_type)?;
+                }
+                self.tab_level -= 1;
+                Ok(())
+            }
+
+            AstNode::StructLiteral {
+                identifier, fields, ..
+            } => {
+                write!(self.out, "{} {{ ", identifier)?;
+                for (name, value) in fields {
+                    write!(self.out, "{}: ", name)?;
+                    self.visit(value)?;
+                    write!(self.out, ", ")?;
+                }
+                write!(self.out, "}}")?;
+                Ok(())
+            }
+
+            AstNode::FieldAccess {
+                identifier, fields, ..
+            } => {
+                write!(self.out, "{}", identifier)?;
+                for field in fields {
+                    write!(self.out, ".{}", field)?;
+                }
+                Ok(())
+            }
+
+            AstNode::ArrayLiteral { elements, .. } => {
+                write!(self.out, "[")?;
+                for element in elements {
+                    self.visit(element)?;
+                    write!(self.out, ", ")?;
+                }
+                write!(self.out, "]")?;
+                Ok(())
+            }
+
             AstNode::VarDec {
                 identifier,
                 var_type,
                 expression,
+                ..
             } => {
-                println!("VarDec");
+                writeln!(self.out, "VarDec")?;
                 self.tab_level += 1;
-                self.print_tab();
-                println!("Identifier: {}", identifier);
-                self.print_tab();
-                println!("Type: {}", var_type);
-                self.print_tab();
-                print!("Expression: ");
+                self.print_tab()?;
+                writeln!(self.out, "Identifier: {}", identifier)?;
+                self.print_tab()?;
+                writeln!(self.out, "Type: {}", var_type)?;
+                self.print_tab()?;
+                write!(self.out, "Expression: ")?;
                 self.visit(expression)?;
                 self.tab_level -= 1;
                 Ok(())
             }
 
-            AstNode::Delay { expression } => {
-                println!("Delay");
+            AstNode::ConstDec {
+                identifier,
+                const_type,
+                expression,
+                ..
+            } => {
+                writeln!(self.out, "ConstDec")?;
                 self.tab_level += 1;
-                self.print_tab();
-                print!("Expression: ");
+                self.print_tab()?;
+                writeln!(self.out, "Identifier: {}", identifier)?;
+                self.print_tab()?;
+                writeln!(self.out, "Type: {}", const_type)?;
+                self.print_tab()?;
+                write!(self.out, "Expression: ")?;
                 self.visit(expression)?;
                 self.tab_level -= 1;
                 Ok(())
             }
 
-            AstNode::Print { expression } => {
-                println!("Print");
+            AstNode::Delay { expression, .. } => {
+                writeln!(self.out, "Delay")?;
                 self.tab_level += 1;
-                self.print_tab();
-                print!("Expression: ");
+                self.print_tab()?;
+                write!(self.out, "Expression: ")?;
+                self.visit(expression)?;
+                self.tab_level -= 1;
+                Ok(())
+            }
+
+            AstNode::Print { expression, .. } => {
+                writeln!(self.out, "Print")?;
+                self.tab_level += 1;
+                self.print_tab()?;
+                write!(self.out, "Expression: ")?;
                 self.visit(expression)?;
                 self.tab_level -= 1;
                 Ok(())
@@ -115,19 +209,20 @@ impl Visitor<Result<()>> for TreePrinter {
                 condition,
                 if_true,
                 if_false,
+                ..
             } => {
-                println!("If");
+                writeln!(self.out, "If")?;
                 self.tab_level += 1;
-                self.print_tab();
-                print!("Condition: ");
+                self.print_tab()?;
+                write!(self.out, "Condition: ")?;
                 self.visit(condition)?;
-                println!();
-                self.print_tab();
-                print!("If True: ");
+                writeln!(self.out)?;
+                self.print_tab()?;
+                write!(self.out, "If True: ")?;
                 self.visit(if_true)?;
                 if let Some(if_false) = if_false {
-                    self.print_tab();
-                    print!("If False: ");
+                    self.print_tab()?;
+                    write!(self.out, "If False: ")?;
                     self.visit(if_false)?;
                 }
                 self.tab_level -= 1;
@@ -136,22 +231,61 @@ impl Visitor<Result<()>> for TreePrinter {
 
             AstNode::Assignment {
                 identifier,
-                index,
+                indices,
+                fields,
+                operator,
                 expression,
+                ..
             } => {
-                println!("Assignment");
+                writeln!(self.out, "Assignment")?;
                 self.tab_level += 1;
-                self.print_tab();
-                println!("Identifier: {}", identifier);
-                self.print_tab();
-                print!("Expression: ");
+                self.print_tab()?;
+                writeln!(self.out, "Identifier: {}", identifier)?;
+                self.print_tab()?;
+                writeln!(self.out, "Operator: {}", operator)?;
+                for field in fields {
+                    self.print_tab()?;
+                    writeln!(self.out, "Field: {}", field)?;
+                }
+                self.print_tab()?;
+                write!(self.out, "Expression: ")?;
                 self.visit(expression)?;
-                println!();
-                if let Some(index) = index {
-                    self.print_tab();
-                    print!("Index: ");
+                writeln!(self.out)?;
+                for index in indices {
+                    self.print_tab()?;
+                    write!(self.out, "Index: ")?;
                     self.visit(index)?;
-                    println!();
+                    writeln!(self.out)?;
+                }
+                self.tab_level -= 1;
+                Ok(())
+            }
+
+            AstNode::Match {
+                scrutinee,
+                arms,
+                default,
+                ..
+            } => {
+                writeln!(self.out, "Match")?;
+                self.tab_level += 1;
+                self.print_tab()?;
+                write!(self.out, "Scrutinee: ")?;
+                self.visit(scrutinee)?;
+                writeln!(self.out)?;
+                for (literal, block) in arms {
+                    self.print_tab()?;
+                    write!(self.out, "Arm ")?;
+                    self.visit(literal)?;
+                    write!(self.out, ": ")?;
+                    self.visit(block)?;
+                    writeln!(self.out)?;
+                }
+                if let Some(default) = default {
+                    self.print_tab()?;
+                    write!(self.out, "Default: ")?;
+                    self.visit(default)?;
+                    writeln!(self.out)?;
                 }
                 self.tab_level -= 1;
                 Ok(())
@@ -162,57 +296,68 @@ impl Visitor<Result<()>> for TreePrinter {
                 condition,
                 increment,
                 body,
+                ..
             } => {
-                println!("For");
+                writeln!(self.out, "For")?;
                 self.tab_level += 1;
-                self.print_tab();
-                print!("Initializer: ");
+                self.print_tab()?;
+                write!(self.out, "Initializer: ")?;
                 if let Some(initializer) = initializer {
                     self.visit(initializer)?;
-                    println!();
+                    writeln!(self.out)?;
                 } else {
-                    println!("None");
+                    writeln!(self.out, "None")?;
                 }
-                self.print_tab();
-                print!("Condition: ");
+                self.print_tab()?;
+                write!(self.out, "Condition: ")?;
                 self.visit(condition)?;
-                println!();
-                self.print_tab();
-                print!("Increment: ");
+                writeln!(self.out)?;
+                self.print_tab()?;
+                write!(self.out, "Increment: ")?;
                 if let Some(increment) = increment {
                     self.tab_level += 1;
-                    println!();
-                    self.print_tab();
+                    writeln!(self.out)?;
+                    self.print_tab()?;
                     self.visit(increment)?;
                     self.tab_level -= 1;
                 } else {
-                    print!("None");
+                    write!(self.out, "None")?;
                 }
-                println!();
-                self.print_tab();
-                print!("Body: ");
+                writeln!(self.out)?;
+                self.print_tab()?;
+                write!(self.out, "Body: ")?;
                 self.visit(body)?;
                 self.tab_level -= 1;
                 Ok(())
             }
 
-            AstNode::Return { expression } => {
-                println!("Return");
+            AstNode::Break { .. } => {
+                writeln!(self.out, "Break")?;
+                Ok(())
+            }
+
+            AstNode::Continue { .. } => {
+                writeln!(self.out, "Continue")?;
+                Ok(())
+            }
+
+            AstNode::Return { expression, .. } => {
+                writeln!(self.out, "Return")?;
                 self.tab_level += 1;
-                self.print_tab();
-                print!("Expression: ");
+                self.print_tab()?;
+                write!(self.out, "Expression: ")?;
                 self.visit(expression)?;
                 self.tab_level -= 1;
                 Ok(())
             }
 
-            AstNode::Block { statements } => {
-                println!("Block");
+            AstNode::Block { statements, .. } => {
+                writeln!(self.out, "Block")?;
                 self.tab_level += 1;
                 for statement in statements {
-                    self.print_tab();
+                    self.print_tab()?;
                     self.visit(statement)?;
-                    println!();
+                    writeln!(self.out)?;
                 }
                 self.tab_level -= 1;
                 Ok(())
@@ -221,20 +366,21 @@ impl Visitor<Result<()>> for TreePrinter {
             AstNode::Expression {
                 casted_type,
                 expr: bin_op,
+                ..
             } => {
                 self.tab_level += 1;
                 self.visit(bin_op)?;
                 if let Some(casted_type) = casted_type {
-                    print!(" as {}", casted_type);
+                    write!(self.out, " as {}", casted_type)?;
                 }
                 self.tab_level -= 1;
                 Ok(())
             }
 
-            AstNode::SubExpression { bin_op } => {
-                print!("(");
+            AstNode::SubExpression { bin_op, .. } => {
+                write!(self.out, "(")?;
                 self.visit(bin_op)?;
-                print!(")");
+                write!(self.out, ")")?;
                 Ok(())
             }
 
@@ -242,36 +388,44 @@ impl Visitor<Result<()>> for TreePrinter {
                 identifier,
                 params,
                 return_type,
+                return_dimensions,
                 block,
+                ..
             } => {
-                println!("FunctionDecl");
+                writeln!(self.out, "FunctionDecl")?;
                 self.tab_level += 1;
-                self.print_tab();
-                println!("Identifier: {}", identifier.span.lexeme);
-                self.print_tab();
-                print!("Params: ");
+                self.print_tab()?;
+                writeln!(self.out, "Identifier: {}", identifier.span.lexeme)?;
+                self.print_tab()?;
+                write!(self.out, "Params: ")?;
                 for param in params {
                     self.visit(param)?;
-                    print!(", ");
+                    write!(self.out, ", ")?;
+                }
+                writeln!(self.out)?;
+                self.print_tab()?;
+                write!(self.out, "Return Type: {}", return_type)?;
+                for size in return_dimensions {
+                    write!(self.out, "[{}]", size)?;
                 }
-                println!();
-                self.print_tab();
-                println!("Return Type: {}", return_type);
-                self.print_tab();
-                print!("Block: ");
+                writeln!(self.out)?;
+                self.print_tab()?;
+                write!(self.out, "Block: ")?;
                 self.visit(block)?;
                 self.tab_level -= 1;
                 Ok(())
             }
 
-            AstNode::While { condition, body } => {
-                println!("While");
+            AstNode::While {
+                condition, body, ..
+            } => {
+                writeln!(self.out, "While")?;
                 self.tab_level += 1;
-                self.print_tab();
-                print!("Condition: ");
+                self.print_tab()?;
+                write!(self.out, "Condition: ")?;
                 self.visit(condition)?;
-                self.print_tab();
-                print!("Body: ");
+                self.print_tab()?;
+                write!(self.out, "Body: ")?;
                 self.visit(body)?;
                 self.tab_level -= 1;
                 Ok(())
@@ -281,21 +435,22 @@ impl Visitor<Result<()>> for TreePrinter {
                 loc_x,
                 loc_y,
                 colour,
+                ..
             } => {
-                print!("__write ");
+                write!(self.out, "__write ")?;
                 self.visit(loc_x)?;
-                print!(", ");
+                write!(self.out, ", ")?;
                 self.visit(loc_y)?;
-                print!(", ");
+                write!(self.out, ", ")?;
                 self.visit(colour)?;
-                print!(";");
+                write!(self.out, ";")?;
                 Ok(())
             }
 
-            AstNode::PadClear { expr } => {
-                print!("__clear ");
+            AstNode::PadClear { expr, .. } => {
+                write!(self.out, "__clear ")?;
                 self.visit(expr)?;
-                print!(";");
+                write!(self.out, ";")?;
                 Ok(())
             }
 
@@ -305,18 +460,19 @@ impl Visitor<Result<()>> for TreePrinter {
                 width,
                 height,
                 colour,
+                ..
             } => {
-                print!("__write_box ");
+                write!(self.out, "__write_box ")?;
                 self.visit(loc_x)?;
-                print!(", ");
+                write!(self.out, ", ")?;
                 self.visit(loc_y)?;
-                print!(", ");
+                write!(self.out, ", ")?;
                 self.visit(width)?;
-                print!(", ");
+                write!(self.out, ", ")?;
                 self.visit(height)?;
-                print!(", ");
+                write!(self.out, ", ")?;
                 self.visit(colour)?;
-                print!(";");
+                write!(self.out, ";")?;
                 Ok(())
             }
 
@@ -324,26 +480,43 @@ impl Visitor<Result<()>> for TreePrinter {
                 left,
                 operator,
                 right,
+                ..
             } => {
-                print!("(");
+                write!(self.out, "(")?;
                 self.visit(left)?;
-                print!(" {} ", operator);
+                write!(self.out, " {} ", operator)?;
                 self.visit(right)?;
-                print!(")");
+                write!(self.out, ")")?;
                 Ok(())
             }
 
-            AstNode::UnaryOp { operator, expr } => {
-                print!("{}(", operator);
+            AstNode::Conditional {
+                condition,
+                if_true,
+                if_false,
+                ..
+            } => {
+                write!(self.out, "(if ")?;
+                self.visit(condition)?;
+                write!(self.out, " then ")?;
+                self.visit(if_true)?;
+                write!(self.out, " else ")?;
+                self.visit(if_false)?;
+                write!(self.out, ")")?;
+                Ok(())
+            }
+
+            AstNode::UnaryOp { operator, expr, .. } => {
+                write!(self.out, "{}(", operator)?;
                 self.visit(expr)?;
-                print!(")");
+                write!(self.out, ")")?;
                 Ok(())
             }
 
-            AstNode::PadRead { x, y } => {
-                print!("__read ");
+            AstNode::PadRead { x, y, .. } => {
+                write!(self.out, "__read ")?;
                 self.visit(x)?;
-                print!(", ");
+                write!(self.out, ", ")?;
                 self.visit(y)?;
                 Ok(())
             }
@@ -351,30 +524,37 @@ impl Visitor<Result<()>> for TreePrinter {
             AstNode::FormalParam {
                 identifier,
                 param_type,
-                length: index,
+                dimensions,
+                ..
             } => {
-                print!("{}: {}", identifier.span.lexeme, param_type.span.lexeme);
-                if let Some(index) = index {
-                    print!("[{}]", index);
+                write!(
+                    self.out,
+                    "{}: {}",
+                    identifier.span.lexeme, param_type.span.lexeme
+                )?;
+                for size in dimensions {
+                    write!(self.out, "[{}]", size)?;
                 }
                 Ok(())
             }
 
-            AstNode::PadRandI { upper_bound } => {
-                print!("__randi ");
+            AstNode::PadRandI { upper_bound, .. } => {
+                write!(self.out, "__randi ")?;
                 self.visit(upper_bound)?;
                 Ok(())
             }
 
-            AstNode::FunctionCall { identifier, args } => {
-                print!("{}(", identifier.span.lexeme);
+            AstNode::FunctionCall {
+                identifier, args, ..
+            } => {
+                write!(self.out, "{}(", identifier.span.lexeme)?;
 
                 if !args.is_empty() {
                     let (args, last) = args.split_at(args.len() - 1);
 
                     for arg in args {
                         self.visit(arg)?;
-                        print!(", ");
+                        write!(self.out, ", ")?;
                     }
 
                     if let Some(last) = last.first() {
@@ -382,44 +562,71 @@ impl Visitor<Result<()>> for TreePrinter {
                     }
                 }
 
-                print!(")");
+                write!(self.out, ")")?;
                 Ok(())
             }
 
             AstNode::Identifier { token } => {
-                print!("{}", token.span.lexeme);
+                write!(self.out, "{}", token.span.lexeme)?;
                 Ok(())
             }
 
             AstNode::IntLiteral(token) => {
-                print!("{}", token.span.lexeme);
+                write!(self.out, "{}", token.span.lexeme)?;
                 Ok(())
             }
 
             AstNode::FloatLiteral(token) => {
-                print!("{}", token.span.lexeme);
+                write!(self.out, "{}", token.span.lexeme)?;
                 Ok(())
             }
 
             AstNode::BoolLiteral(token) => {
-                print!("{}", token.span.lexeme);
+                write!(self.out, "{}", token.span.lexeme)?;
                 Ok(())
             }
 
             AstNode::ColourLiteral(token) => {
-                print!("{}", token.span.lexeme);
+                write!(self.out, "{}", token.span.lexeme)?;
                 Ok(())
             }
-            AstNode::PadWidth => {
-                print!("__width");
+            AstNode::PadWidth { .. } => {
+                write!(self.out, "__width")?;
                 Ok(())
             }
-            AstNode::PadHeight => {
-                print!("__height");
+            AstNode::PadHeight { .. } => {
+                write!(self.out, "__height")?;
                 Ok(())
             }
 
-            AstNode::EndOfFile => Ok(()),
+            AstNode::EndOfFile { .. } => Ok(()),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::{lexing::Lexer, parsing::Parser, utils::SimpleBuffer};
+
+    #[test]
+    fn test_multi_dimensional_access() {
+        let mut lexer: Lexer<SimpleBuffer> = Lexer::new("m[1][0]", Path::new(""), None);
+        let tokens = lexer.lex().unwrap();
+        let expression = Parser::new(&tokens, Path::new(""))
+            .parse_bare_expression()
+            .unwrap();
+
+        let mut output = Vec::new();
+        TreePrinter::with_output(&mut output)
+            .visit(&expression)
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "ArrayAccess\n    Identifier: m\n    Index: 1\n    Index: 0\n"
+        );
+    }
+}
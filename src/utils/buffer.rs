@@ -5,6 +5,7 @@ const EOF: char = 0 as char;
 // const N2: usize = BUFFER_SIZE;
 // const N: usize = N2 / 2;
 
+#[allow(dead_code)]
 pub trait Stream {
     fn new(input: &str, path: &Path) -> Self;
     fn rollback(&mut self);
@@ -21,6 +22,7 @@ pub trait Stream {
 pub struct SimpleBuffer {
     input: String,
     input_pointer: usize,
+    #[allow(dead_code)]
     file: PathBuf,
     line: usize,
     col: usize,
@@ -53,7 +55,19 @@ impl Stream for SimpleBuffer {
             .unwrap_or(EOF)
             == '\n'
         {
+            // The column was reset by the newline, so count it again from
+            // the start of the previous line
             self.line -= 1;
+            self.col = self
+                .input
+                .chars()
+                .take(self.input_pointer - 1)
+                .collect::<Vec<_>>()
+                .iter()
+                .rev()
+                .take_while(|c| **c != '\n')
+                .count()
+                + 1;
         } else if self.col > 1 {
             self.col -= 1;
         } else {
@@ -0,0 +1,313 @@
+use std::path::Path;
+
+use console::{style, Style};
+use serde_json::json;
+
+use crate::{
+    core::TextSpan,
+    utils::errors::{Error, LexicalError, ParseError, SemanticError},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+/// How diagnostics are reported to the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum MessageFormat {
+    /// Coloured text with source snippets
+    Human,
+    /// One JSON object per line
+    Json,
+}
+
+/// An error or warning about a source file, ready to be shown to the user.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// The offending part of the source, if it is known
+    pub span: Option<TextSpan>,
+    pub help: Option<String>,
+    /// The stable code of the diagnostic, e.g. `E0101`
+    pub code: Option<&'static str>,
+}
+
+impl Diagnostic {
+    pub fn into_warning(mut self) -> Self {
+        self.severity = Severity::Warning;
+        self
+    }
+
+    /// Renders the diagnostic along with the source line it points at, with
+    /// the offending span underlined:
+    ///
+    /// ```text
+    /// error[E0201]: Variable 'y' is not defined.
+    ///  --> main.parl:2:9
+    ///   |
+    /// 2 | __print y;
+    ///   |         ^
+    ///   = help: declare it first, e.g. `let y: int = 0;`
+    /// ```
+    pub fn render(&self, file: &Path, source: &str) -> String {
+        let highlight = match self.severity {
+            Severity::Error => Style::new().red().bold(),
+            Severity::Warning => Style::new().yellow().bold(),
+        }
+        .for_stderr();
+        let gutter_style = Style::new().blue().bold().for_stderr();
+
+        let label = match self.code {
+            Some(code) => format!("{}[{}]", self.severity.as_str(), code),
+            None => self.severity.as_str().to_string(),
+        };
+
+        let mut rendered = format!(
+            "{}: {}\n",
+            highlight.apply_to(label),
+            style(&self.message).bold().for_stderr()
+        );
+
+        let line = self
+            .span
+            .as_ref()
+            .and_then(|span| span.from_line.checked_sub(1))
+            .and_then(|index| source.lines().nth(index));
+
+        let gutter = match (&self.span, line) {
+            (Some(span), Some(line)) => {
+                let number = span.from_line.to_string();
+                let gutter = " ".repeat(number.len());
+
+                // Columns are 1-based and `to_col` points just past the span
+                let start = span.from_col.saturating_sub(1);
+                let end = match span.to_line == span.from_line {
+                    true => span.to_col.saturating_sub(1),
+                    false => line.chars().count(),
+                };
+
+                // Keep tabs so that the carets line up with the source line
+                let padding: String = line
+                    .chars()
+                    .take(start)
+                    .map(|c| if c == '\t' { '\t' } else { ' ' })
+                    .collect();
+
+                rendered += &format!(
+                    "{gutter}{} {}:{}:{}\n",
+                    gutter_style.apply_to("-->"),
+                    file.display(),
+                    span.from_line,
+                    span.from_col
+                );
+                rendered += &format!("{gutter} {}\n", gutter_style.apply_to("|"));
+                rendered += &format!("{} {}\n", gutter_style.apply_to(number + " |"), line);
+                rendered += &format!(
+                    "{gutter} {} {padding}{}\n",
+                    gutter_style.apply_to("|"),
+                    highlight.apply_to("^".repeat(end.saturating_sub(start).max(1)))
+                );
+                gutter
+            }
+            (Some(span), None) => {
+                rendered += &format!(
+                    "{} {}:{}:{}\n",
+                    gutter_style.apply_to("-->"),
+                    file.display(),
+                    span.from_line,
+                    span.from_col
+                );
+                String::new()
+            }
+            (None, _) => {
+                rendered += &format!("{} {}\n", gutter_style.apply_to("-->"), file.display());
+                String::new()
+            }
+        };
+
+        if let Some(help) = &self.help {
+            rendered += &format!("{gutter} {} {}\n", gutter_style.apply_to("= help:"), help);
+        }
+
+        rendered.pop();
+        rendered
+    }
+
+    /// Serializes the diagnostic as a single line JSON object, with the span
+    /// given as the full `TextSpan` range:
+    ///
+    /// ```text
+    /// {"code":"E0201","file":"main.parl","help":null,"message":"...","severity":"error",
+    ///  "span":{"from_col":9,"from_line":2,"lexeme":"y","to_col":10,"to_line":2}}
+    /// ```
+    pub fn to_json(&self, file: &Path) -> String {
+        let span = self.span.as_ref().map(|span| {
+            json!({
+                "from_line": span.from_line,
+                "to_line": span.to_line,
+                "from_col": span.from_col,
+                "to_col": span.to_col,
+                "lexeme": span.lexeme,
+            })
+        });
+
+        json!({
+            "severity": self.severity.as_str(),
+            "code": self.code,
+            "message": self.message,
+            "file": file.display().to_string(),
+            "span": span,
+            "help": self.help,
+        })
+        .to_string()
+    }
+}
+
+impl From<&LexicalError> for Diagnostic {
+    fn from(error: &LexicalError) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            message: error.to_string(),
+            span: Some(error.span().clone()),
+            help: None,
+            code: Some(error.code()),
+        }
+    }
+}
+
+impl From<&ParseError> for Diagnostic {
+    fn from(error: &ParseError) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            message: error.to_string(),
+            span: Some(error.span().clone()),
+            help: error.help(),
+            code: Some(error.code()),
+        }
+    }
+}
+
+impl From<&SemanticError> for Diagnostic {
+    fn from(error: &SemanticError) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            message: error.to_string(),
+            span: Some(error.span().clone()),
+            help: error.help(),
+            code: Some(error.code()),
+        }
+    }
+}
+
+impl From<&Error> for Diagnostic {
+    fn from(error: &Error) -> Self {
+        match error {
+            Error::Lexical(e) => e.into(),
+            Error::Parse(e) => e.into(),
+            Error::Semantic(e) => e.as_ref().into(),
+            _ => Diagnostic {
+                severity: Severity::Error,
+                message: error.to_string(),
+                span: None,
+                help: None,
+                code: None,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{Token, TokenKind};
+    use rstest::rstest;
+
+    fn render(diagnostic: &Diagnostic, source: &str) -> String {
+        console::strip_ansi_codes(&diagnostic.render(Path::new("main.parl"), source)).to_string()
+    }
+
+    #[rstest]
+    fn test_render_semantic_error() {
+        let token = Token::new(TokenKind::Identifier, TextSpan::new(2, 2, 9, 10, "y"));
+        let diagnostic = Diagnostic::from(&SemanticError::UndefinedVariable(token));
+
+        assert_eq!(
+            render(&diagnostic, "let x: int = 1;\n__print y;\n"),
+            "error[E0201]: Variable 'y' is not defined.\n \
+             --> main.parl:2:9\n  \
+             |\n\
+             2 | __print y;\n  \
+             |         ^\n  \
+             = help: declare it first, e.g. `let y: int = 0;`"
+        );
+    }
+
+    #[rstest]
+    fn test_render_warning_with_tabs() {
+        let token = Token::new(TokenKind::Identifier, TextSpan::new(1, 1, 6, 9, "abc"));
+        let diagnostic = Diagnostic::from(&SemanticError::VariableShadowing(token)).into_warning();
+
+        let rendered = render(&diagnostic, "\tlet abc: int = 1;");
+
+        assert!(rendered.starts_with("warning[W0001]: "));
+        assert!(rendered.contains("\n  | \t    ^^^\n"));
+    }
+
+    #[rstest]
+    fn test_json() {
+        let token = Token::new(TokenKind::Identifier, TextSpan::new(2, 2, 9, 10, "y"));
+        let diagnostic = Diagnostic::from(&SemanticError::VariableShadowing(token)).into_warning();
+
+        assert_eq!(
+            diagnostic.to_json(Path::new("dir\\main.parl")),
+            "{\"code\":\"W0001\",\"file\":\"dir\\\\main.parl\",\
+             \"help\":\"rename the variable if the shadowing is not intended\",\
+             \"message\":\"Variable 'y' was already defined in a parent scope. Are you trying to shadow it?\",\
+             \"severity\":\"warning\",\
+             \"span\":{\"from_col\":9,\"from_line\":2,\"lexeme\":\"y\",\"to_col\":10,\"to_line\":2}}"
+        );
+    }
+
+    #[rstest]
+    #[case("plain")]
+    #[case("say \"hi\"\n")]
+    #[case("\0")]
+    fn test_json_escapes_lexeme(#[case] lexeme: &str) {
+        let span = TextSpan::new(1, 1, 1, 2, lexeme);
+        let diagnostic = Diagnostic::from(&LexicalError::InvalidCharacter(span));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&diagnostic.to_json(Path::new("main.parl"))).unwrap();
+
+        assert_eq!(json["span"]["lexeme"], lexeme);
+        assert_eq!(json["help"], serde_json::Value::Null);
+    }
+
+    #[rstest]
+    #[case(TextSpan::new(0, 0, 0, 0, "\0"))]
+    #[case(TextSpan::new(5, 5, 1, 1, "\0"))]
+    fn test_render_outside_source(#[case] span: TextSpan) {
+        let diagnostic = Diagnostic::from(&LexicalError::InvalidCharacter(span));
+
+        let rendered = render(&diagnostic, "let x: int = 1;");
+
+        assert_eq!(rendered.lines().count(), 2);
+        assert!(rendered
+            .lines()
+            .nth(1)
+            .unwrap()
+            .starts_with("--> main.parl:"));
+    }
+}
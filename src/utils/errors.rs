@@ -4,7 +4,7 @@ use thiserror::Error;
 
 use crate::{
     core::{TextSpan, Token, TokenKind},
-    semantics::utils::Type,
+    semantics::utils::{Type, MAX_ARRAY_SIZE},
 };
 
 #[derive(Debug, Error)]
@@ -14,36 +14,102 @@ pub enum Error {
     #[error("Parse error: {0}")]
     Parse(#[from] ParseError),
     #[error("Semantic error: {0}")]
-    Semantic(#[from] SemanticError),
+    Semantic(#[from] Box<SemanticError>),
+    #[error("Runtime error: {0}")]
+    Runtime(#[from] RuntimeError),
+    #[error("PArIR error: {0}")]
+    Assembly(#[from] AssemblyError),
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
+    #[error("Invalid formatter configuration: {0}")]
+    Config(#[from] toml::de::Error),
 }
 
 #[derive(Debug, Error)]
 pub enum LexicalError {
-    #[error("Unrecognized character '{}' found at {}:{}", .0.lexeme, .0.from_line, .0.from_col)]
+    #[error("Unrecognized character '{}'.", .0.lexeme)]
     InvalidCharacter(TextSpan),
 }
 
+impl LexicalError {
+    /// The stable code of the error, which `parlc explain` describes.
+    pub fn code(&self) -> &'static str {
+        match self {
+            LexicalError::InvalidCharacter(_) => "E0001",
+        }
+    }
+
+    pub fn span(&self) -> &TextSpan {
+        match self {
+            LexicalError::InvalidCharacter(span) => span,
+        }
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum ParseError {
-    #[error("Unexpected token found at {}:{}:{} \nExpected {expected:?}, found {found}", .source_file.display(), .found.span.from_line, .found.span.from_col)]
+    #[error("Expected {expected:?}, found '{found}'.")]
     UnexpectedToken {
         expected: TokenKind,
         found: Token,
         source_file: PathBuf,
     },
-    #[error("Unexpected token found at {}:{}:{} \nExpected one of these types: {:?}\nFound: {found}", .source_file.display(), .found.span.from_line, .found.span.from_col, .expected)]
+    #[error("Expected one of these types: {expected:?}, found '{found}'.")]
     UnexpectedTokenList {
         source_file: PathBuf,
         found: Token,
         expected: Vec<TokenKind>,
     },
     #[error("Unclosed block.")]
-    UnclosedBlock,
+    UnclosedBlock { opening: Token },
+    #[error("Comparisons cannot be chained, found '{second}' after '{first}'.")]
+    /// `first` is the lexeme of the comparison before `second`
+    ChainedComparison {
+        source_file: PathBuf,
+        first: String,
+        second: Token,
+    },
 }
 
-#[derive(Debug, Error)]
+impl ParseError {
+    /// The stable code of the error, which `parlc explain` describes.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParseError::UnexpectedToken { .. } => "E0101",
+            ParseError::UnexpectedTokenList { .. } => "E0102",
+            ParseError::UnclosedBlock { .. } => "E0103",
+            ParseError::ChainedComparison { .. } => "E0104",
+        }
+    }
+
+    pub fn span(&self) -> &TextSpan {
+        match self {
+            ParseError::UnexpectedToken { found, .. }
+            | ParseError::UnexpectedTokenList { found, .. } => &found.span,
+            ParseError::UnclosedBlock { opening } => &opening.span,
+            ParseError::ChainedComparison { second, .. } => &second.span,
+        }
+    }
+
+    pub fn help(&self) -> Option<String> {
+        match self {
+            ParseError::UnexpectedToken {
+                expected: TokenKind::Semicolon,
+                ..
+            } => Some("statements must end with a ';'".to_string()),
+            ParseError::UnclosedBlock { .. } => {
+                Some("this block is missing its closing '}'".to_string())
+            }
+            ParseError::ChainedComparison { first, second, .. } => Some(format!(
+                "compare twice and join the comparisons with `and`, e.g. `a {} b and b {} c`",
+                first, second.span.lexeme
+            )),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Error)]
 pub enum SemanticError {
     #[error("Variable '{}' is not defined.", .0.span.lexeme)]
     UndefinedVariable(Token),
@@ -58,23 +124,198 @@ pub enum SemanticError {
     // #[error("Variable '{}' is redeclared.", .0.span.lexeme)]
     // RedeclaredVariable(Token),
     #[error("'{}' is of type {:?}, expected {:?}.", .0, .1, .2)]
-    TypeMismatch(String, Type, Type),
+    TypeMismatch(String, Type, Type, TextSpan),
     #[error("Union type '{}' is of type {:?}, expected one of these types: {:?}.", .0, .1, .2)]
-    TypeMismatchUnion(String, Type, Vec<Type>),
+    TypeMismatchUnion(String, Type, Vec<Type>, TextSpan),
     #[error("Invalid operation: {:?}", .0)]
     InvalidOperation(Token),
-    #[error("Couldn't cast {:?} to {:?}.", .0, .1)]
-    InvalidCast(Type, Type),
+    #[error("Couldn't cast {:?} to {:?}.", .1, .2)]
+    InvalidCast(Token, Type, Type),
     #[error("Function '{}' has a return type of: {:?}, got: {:?}.", .0.span.lexeme, .1, .2)]
     FunctionReturnTypeMismatch(Token, Type, Type),
-    #[error("Function '{}' takes no parameters, supplied {:?}.", .0, .1)]
-    FunctionCallNoParams(String, Vec<Type>),
+    #[error("Function '{}' takes no parameters, supplied {:?}.", .0.span.lexeme, .1)]
+    FunctionCallNoParams(Token, Vec<Type>, TextSpan),
+    #[error("Function '{}' takes {} arguments, but was given {}.", .0.span.lexeme, .1, .2)]
+    FunctionCallArity(Token, usize, usize, TextSpan),
+    #[error("The value of constant '{}' divides by zero.", .0.span.lexeme)]
+    ConstantDivisionByZero(Token, TextSpan),
     #[error("Variable '{}' was not defined in the current scope.", .0.span.lexeme)]
     VarUndefinedInFunc(Token),
     #[error("Array access out of bounds: '{}[{}]' has a size of {}.", .0.span.lexeme, .1, .2)]
-    ArrayOverflow(Token, Type, usize, usize),
+    ArrayOverflow(Token, i64, usize, TextSpan),
     #[error("Array {} has a non-integer index. Found: {:?}.", .0.span.lexeme, .1)]
     ArrayIndexNotInt(Token, Type),
+    #[error("Array '{}' has a size of {}, but was given {} elements.", .0.span.lexeme, .1, .2)]
+    ArraySizeMismatch(Token, usize, usize),
+    #[error("'{}' has {} dimensions, but was given {} indices.", .0.span.lexeme, .1, .2)]
+    ArrayIndexCount(Token, usize, usize),
+    #[error("Struct '{}' is not defined.", .0.span.lexeme)]
+    UndefinedStruct(Token),
+    #[error("'{}' has no field '{}'.", .1, .0.span.lexeme)]
+    UnknownField(Token, Type),
+    #[error("Struct '{}' is missing the field '{}'.", .0.span.lexeme, .1)]
+    MissingField(Token, String),
+    #[error("Field '{}' is given more than once.", .0.span.lexeme)]
+    DuplicateField(Token),
+    #[error("'{}' can only be used inside a loop.", .0.lexeme)]
+    OutsideLoop(TextSpan),
+    #[error("'{}' is already matched by an earlier arm.", .0)]
+    DuplicateMatchArm(String, TextSpan),
+    #[error("The value of constant '{}' cannot be worked out at compile time.", .0.span.lexeme)]
+    NotConstant(Token, TextSpan),
+    #[error("Cannot assign to constant '{}'.", .0.span.lexeme)]
+    ConstantAssignment(Token),
+    #[error("Array size '{}' is not a whole number from 0 to {}.", .0.span.lexeme, MAX_ARRAY_SIZE)]
+    InvalidArraySize(Token),
+}
+
+impl SemanticError {
+    /// The stable code of the error, which `parlc explain` describes. Codes
+    /// of errors that are only ever reported as warnings start with a `W`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            SemanticError::UndefinedVariable(_) => "E0201",
+            SemanticError::VariableRedeclaration(_) => "E0202",
+            SemanticError::VariableShadowing(_) => "W0001",
+            SemanticError::UndefinedFunction(_) => "E0203",
+            SemanticError::FunctionAlreadyDefined(_) => "E0204",
+            SemanticError::TypeMismatch(..) => "E0205",
+            SemanticError::TypeMismatchUnion(..) => "E0206",
+            SemanticError::InvalidOperation(_) => "E0207",
+            SemanticError::InvalidCast(..) => "E0208",
+            SemanticError::FunctionReturnTypeMismatch(..) => "E0209",
+            SemanticError::FunctionCallNoParams(..) => "E0210",
+            SemanticError::VarUndefinedInFunc(_) => "E0211",
+            SemanticError::ArrayOverflow(..) => "E0212",
+            SemanticError::ArrayIndexNotInt(..) => "E0213",
+            SemanticError::ArraySizeMismatch(..) => "E0214",
+            SemanticError::ArrayIndexCount(..) => "E0215",
+            SemanticError::UndefinedStruct(_) => "E0217",
+            SemanticError::UnknownField(..) => "E0218",
+            SemanticError::MissingField(..) => "E0219",
+            SemanticError::DuplicateField(_) => "E0220",
+            SemanticError::OutsideLoop(_) => "E0221",
+            SemanticError::DuplicateMatchArm(..) => "E0222",
+            SemanticError::NotConstant(..) => "E0223",
+            SemanticError::ConstantAssignment(_) => "E0224",
+            SemanticError::InvalidArraySize(_) => "E0225",
+            SemanticError::FunctionCallArity(..) => "E0226",
+            SemanticError::ConstantDivisionByZero(..) => "E0227",
+        }
+    }
+
+    pub fn span(&self) -> &TextSpan {
+        match self {
+            SemanticError::UndefinedVariable(token)
+            | SemanticError::VariableRedeclaration(token)
+            | SemanticError::VariableShadowing(token)
+            | SemanticError::UndefinedFunction(token)
+            | SemanticError::FunctionAlreadyDefined(token)
+            | SemanticError::InvalidOperation(token)
+            | SemanticError::InvalidCast(token, ..)
+            | SemanticError::FunctionReturnTypeMismatch(token, ..)
+            | SemanticError::VarUndefinedInFunc(token)
+            | SemanticError::ArrayIndexNotInt(token, _)
+            | SemanticError::ArraySizeMismatch(token, ..)
+            | SemanticError::ArrayIndexCount(token, ..)
+            | SemanticError::UndefinedStruct(token)
+            | SemanticError::UnknownField(token, _)
+            | SemanticError::MissingField(token, _)
+            | SemanticError::DuplicateField(token)
+            | SemanticError::ConstantAssignment(token)
+            | SemanticError::InvalidArraySize(token) => &token.span,
+            SemanticError::TypeMismatch(.., span)
+            | SemanticError::TypeMismatchUnion(.., span)
+            | SemanticError::FunctionCallNoParams(.., span)
+            | SemanticError::FunctionCallArity(.., span)
+            | SemanticError::ArrayOverflow(.., span)
+            | SemanticError::OutsideLoop(span)
+            | SemanticError::DuplicateMatchArm(_, span)
+            | SemanticError::NotConstant(_, span)
+            | SemanticError::ConstantDivisionByZero(_, span) => span,
+        }
+    }
+
+    pub fn help(&self) -> Option<String> {
+        match self {
+            SemanticError::UndefinedVariable(token) => Some(format!(
+                "declare it first, e.g. `let {}: int = 0;`",
+                token.span.lexeme
+            )),
+            SemanticError::VariableShadowing(_) => {
+                Some("rename the variable if the shadowing is not intended".to_string())
+            }
+            SemanticError::InvalidCast(..) => Some(
+                "only int to float, float to int, colour to int, bool to int and int to colour casts are allowed"
+                    .to_string(),
+            ),
+            SemanticError::VarUndefinedInFunc(_) => Some(
+                "functions can only use their own parameters and the variables declared inside them"
+                    .to_string(),
+            ),
+            SemanticError::ArrayIndexNotInt(..) => {
+                Some("array indices must be of type int".to_string())
+            }
+            SemanticError::ArrayIndexCount(..) => {
+                Some("index arrays once per dimension, e.g. `grid[y][x]`".to_string())
+            }
+            SemanticError::ArrayOverflow(_, _, size, _) => Some(format!(
+                "indices start at 0, so the last element is at {}",
+                size.saturating_sub(1)
+            )),
+            SemanticError::UndefinedStruct(token) => Some(format!(
+                "declare it first, e.g. `struct {} {{ x: int }}`",
+                token.span.lexeme
+            )),
+            SemanticError::MissingField(_, field) => {
+                Some(format!("give every field a value, including `{}`", field))
+            }
+            SemanticError::NotConstant(..) => Some(
+                "constants can only be made of literals, other constants, operators and casts"
+                    .to_string(),
+            ),
+            SemanticError::ConstantAssignment(token) => Some(format!(
+                "declare it with `let` if it needs to change, e.g. `let {}: int = 0;`",
+                token.span.lexeme
+            )),
+            SemanticError::ArraySizeMismatch(..) => Some(
+                "give as many elements as the size, or a single element to fill the array with, \
+                 with each row of a multi-dimensional array as a nested `[..]`"
+                    .to_string(),
+            ),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum AssemblyError {
+    #[error("Unknown instruction '{}' on line {}.", .1, .0)]
+    UnknownInstruction(usize, String),
+    #[error("Instruction '{}' on line {} expects an operand.", .1, .0)]
+    MissingOperand(usize, String),
+    #[error("Instruction '{}' on line {} takes no operands, found '{}'.", .1, .0, .2)]
+    UnexpectedOperand(usize, String, String),
+    #[error("Malformed operand '{}' on line {}.", .1, .0)]
+    InvalidOperand(usize, String),
+}
+
+#[derive(Debug, Error)]
+pub enum RuntimeError {
+    #[error("Undefined label '.{0}'.")]
+    UndefinedLabel(String),
+    #[error("Operand stack underflow at instruction {0}.")]
+    StackUnderflow(usize),
+    #[error("No open frame at instruction {0}.")]
+    FrameUnderflow(usize),
+    #[error("Invalid memory access [{}:{}] at instruction {}.", .1, .2, .0)]
+    InvalidMemoryAccess(usize, usize, usize),
+    #[error("Invalid operand {} at instruction {}.", .1, .0)]
+    InvalidOperand(usize, f64),
+    #[error("Jump to invalid address {} at instruction {}.", .1, .0)]
+    InvalidJump(usize, f64),
+    #[error("Return outside of a function call at instruction {0}.")]
+    ReturnOutsideCall(usize),
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
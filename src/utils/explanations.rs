@@ -0,0 +1,555 @@
+/// Longer descriptions of every diagnostic code, shown by `parlc explain`.
+const EXPLANATIONS: &[(&str, &str)] = &[
+    (
+        "E0001",
+        "A character that is not part of PArL was found in the source.
+
+Erroneous code example:
+
+    let x: int = 5 $ 3;
+
+Remove the character, or check that it is not meant to be part of a different
+token, such as `#` at the start of a colour literal like `#ff0000`.",
+    ),
+    (
+        "E0101",
+        "The parser expected one specific token but found something else.
+
+Erroneous code example:
+
+    let x: int = 5
+    __print x;
+
+Here a `;` is expected after `5`. The parser recovers at the next statement, so
+any errors reported right after this one may be caused by it.",
+    ),
+    (
+        "E0102",
+        "The parser expected one of several tokens but found something else, for
+example when an expression or a statement is missing.
+
+Erroneous code example:
+
+    let x: int = ;
+
+An expression is expected after `=`:
+
+    let x: int = 5;",
+    ),
+    (
+        "E0103",
+        "A block was opened with `{` but the file ended before its closing `}`.
+
+Erroneous code example:
+
+    fun f() -> int {
+        return 1;
+
+Add the missing `}` at the end of the block:
+
+    fun f() -> int {
+        return 1;
+    }",
+    ),
+    (
+        "E0104",
+        "Two comparisons were written one after the other, as in mathematics.
+
+Erroneous code example:
+
+    if (0 <= x < 10) {
+        __print x;
+    }
+
+This would compare the `bool` result of the first comparison with the next
+operand, which is almost never what was meant, so it is rejected. Compare
+twice and join the comparisons with `and`:
+
+    if (0 <= x and x < 10) {
+        __print x;
+    }",
+    ),
+    (
+        "E0201",
+        "A variable was used without being declared first.
+
+Erroneous code example:
+
+    __print x;
+
+Declare the variable with `let` before using it:
+
+    let x: int = 5;
+    __print x;",
+    ),
+    (
+        "E0202",
+        "A variable was declared twice in the same scope.
+
+Erroneous code example:
+
+    let x: int = 5;
+    let x: int = 6;
+
+Assign to the existing variable instead, or give the new one another name:
+
+    let x: int = 5;
+    x = 6;",
+    ),
+    (
+        "W0001",
+        "A variable declared in an inner scope has the same name as one in an
+enclosing scope, hiding it for the rest of the inner scope.
+
+Example:
+
+    let x: int = 5;
+    {
+        let x: int = 6;
+        __print x;
+    }
+
+This prints 6. Rename the inner variable if the shadowing is not intended.",
+    ),
+    (
+        "E0203",
+        "A function was called without being declared.
+
+Erroneous code example:
+
+    __print double(2);
+
+Declare the function before calling it:
+
+    fun double(x: int) -> int {
+        return x * 2;
+    }
+
+    __print double(2);",
+    ),
+    (
+        "E0204",
+        "A function was declared twice in the same scope.
+
+Erroneous code example:
+
+    fun f() -> int { return 1; }
+    fun f() -> int { return 2; }
+
+Function names must be unique, so rename one of the functions.",
+    ),
+    (
+        "E0205",
+        "A value does not have the type that its context requires, for example
+when initialising or assigning a variable, passing an argument, or using a
+condition.
+
+Erroneous code example:
+
+    let x: int = 2.5;
+
+Either change the declared type or cast the value with `as`:
+
+    let x: int = 2.5 as int;",
+    ),
+    (
+        "E0206",
+        "A value does not have any of the types that its context accepts. For
+example, `__print` accepts `int`, `float`, `bool` and `colour` values only.
+
+Erroneous code example:
+
+    __print 1 + true;
+
+Make sure that the expression has one of the expected types.",
+    ),
+    (
+        "E0207",
+        "An operator was applied to operands of types that it does not support.
+
+Erroneous code example:
+
+    let x: int = 1 + true;
+
+Arithmetic operators work on `int`, `float` and `colour` values, comparisons on
+values of the same kind, and `and`, `or` and `not` on `bool` values only.",
+    ),
+    (
+        "E0208",
+        "A value was cast to a type that it cannot be converted to.
+
+Erroneous code example:
+
+    let x: bool = 5 as bool;
+
+The casts that are allowed are `int` to `float`, `float` to `int`, `colour` to
+`int`, `bool` to `int` and `int` to `colour`.",
+    ),
+    (
+        "E0209",
+        "The value returned by a function does not match its declared return type.
+
+Erroneous code example:
+
+    fun f() -> int {
+        return 1.5;
+    }
+
+Return a value of the declared type, or change the return type:
+
+    fun f() -> float {
+        return 1.5;
+    }",
+    ),
+    (
+        "E0210",
+        "Arguments were passed to a function that takes no parameters.
+
+Erroneous code example:
+
+    fun f() -> int { return 1; }
+    __print f(2);
+
+Call the function without arguments, or declare the parameters it needs.",
+    ),
+    (
+        "E0211",
+        "A function used a variable that is not visible inside it. Functions can
+only use their own parameters and the variables declared inside them, so
+variables declared outside of a function cannot be used in it.
+
+Erroneous code example:
+
+    let y: int = 5;
+
+    fun f() -> int {
+        return y;
+    }
+
+Pass the value in as a parameter instead:
+
+    fun f(y: int) -> int {
+        return y;
+    }",
+    ),
+    (
+        "E0212",
+        "An array was indexed with a constant that is out of its bounds. Indices
+start at 0, so an array of size N can be indexed from 0 to N - 1.
+
+Erroneous code example:
+
+    let xs: int[3] = [1, 2, 3];
+    __print xs[3];
+
+Indices that are only known at runtime are not checked unless the program is
+compiled with `--bounds-checks`, in which case an index out of bounds prints
+57005 (0xDEAD) and halts.",
+    ),
+    (
+        "E0213",
+        "An array was indexed with a value that is not an `int`.
+
+Erroneous code example:
+
+    let xs: int[2] = [1, 2];
+    xs[1.0] = 5;
+
+Use an `int` index, casting it with `as int` if need be:
+
+    xs[1] = 5;",
+    ),
+    (
+        "E0214",
+        "An array was initialised with a different number of elements than its
+declared size, or a multi-dimensional array was given the wrong number of
+rows.
+
+Erroneous code example:
+
+    let xs: int[4] = [1, 2, 3];
+    let ys: int[2] = [1, 2, 3];
+    let grid: int[2][3] = [1, 2, 3, 4, 5, 6];
+
+Give as many elements as the size, leave the size out so that it is inferred
+from the elements, or give a single element to fill the whole array with.
+Each row of a multi-dimensional array is a nested list:
+
+    let xs: int[] = [1, 2, 3];
+    let zeros: int[4] = [0];
+    let grid: int[2][3] = [[1, 2, 3], [4, 5, 6]];",
+    ),
+    (
+        "E0215",
+        "An array was indexed with a different number of indices than it has
+dimensions, or a variable that is not an array was indexed.
+
+Erroneous code example:
+
+    let grid: int[2][3] = [0];
+    __print grid[1];
+
+Give one index per dimension, outermost first:
+
+    __print grid[1][2];",
+    ),
+    (
+        "E0217",
+        "A type was named that is neither a built-in type nor a declared struct.
+
+Erroneous code example:
+
+    let p: Point = Point { x: 1, y: 2 };
+
+Declare the struct before using it:
+
+    struct Point { x: int, y: int }
+    let p: Point = Point { x: 1, y: 2 };",
+    ),
+    (
+        "E0218",
+        "A field was accessed that the struct does not have, or a value that is
+not a struct was accessed as one.
+
+Erroneous code example:
+
+    struct Point { x: int, y: int }
+    let p: Point = Point { x: 1, y: 2 };
+    __print p.z;
+
+Only the fields in the declaration of the struct can be accessed:
+
+    __print p.x;",
+    ),
+    (
+        "E0219",
+        "A struct literal left out one of the fields of the struct.
+
+Erroneous code example:
+
+    struct Point { x: int, y: int }
+    let p: Point = Point { x: 1 };
+
+Every field needs a value, in any order:
+
+    let p: Point = Point { y: 2, x: 1 };",
+    ),
+    (
+        "E0220",
+        "A field was declared, or given a value, more than once.
+
+Erroneous code example:
+
+    struct Point { x: int, x: int }
+
+Give every field a different name, and a single value:
+
+    struct Point { x: int, y: int }",
+    ),
+    (
+        "E0221",
+        "A `break` or `continue` was used outside of a `for` or `while` loop.
+
+Erroneous code example:
+
+    fun first() -> int {
+        break;
+        return 0;
+    }
+
+They only make sense in the body of a loop, which is where they jump out of or
+back to the start of:
+
+    for (let i: int = 0; i < 10; i = i + 1) {
+        if (i == 3) {
+            break;
+        }
+    }
+
+A function declared inside a loop does not count as being inside it.",
+    ),
+    (
+        "E0222",
+        "Two arms of a `match` are taken for the same value.
+
+Erroneous code example:
+
+    match (x) {
+        1 => { __print 1; }
+        1 => { __print 2; }
+    }
+
+Only the first of them would ever run. Remove the other one, or give it the
+value it was meant to have. Colours are compared by value, so `#FF0000` and
+`#ff0000` are the same.",
+    ),
+    (
+        "E0223",
+        "The initialiser of a `const` cannot be worked out at compile time.
+
+Erroneous code example:
+
+    let w: int = 240;
+    const HALF: int = w / 2;
+
+A constant is replaced by its value wherever it is used, so that value has to
+be known before the program runs. It can be made of literals, other constants,
+operators, casts and conditional expressions, but not of variables or calls:
+
+    const W: int = 240;
+    const HALF: int = (W / 2) as int;",
+    ),
+    (
+        "E0224",
+        "A `const` was assigned to.
+
+Erroneous code example:
+
+    const W: int = 240;
+    W = 320;
+
+Constants cannot change once declared. Declare a variable with `let` instead
+if the value has to change:
+
+    let w: int = 240;
+    w = 320;",
+    ),
+    (
+        "E0225",
+        "An array size is not a whole number from 0 to 4294967295, given as an int
+literal or an `int` constant.
+
+Erroneous code example:
+
+    let n: int = 4;
+    let a: int[n] = [0];
+
+The size of an array has to be known at compile time. Use a literal, or a
+constant of type `int`, that is neither negative nor too large:
+
+    const N: int = 4;
+    let a: int[N] = [0];",
+    ),
+    (
+        "E0226",
+        "A function was called with a different number of arguments than it has
+parameters.
+
+Erroneous code example:
+
+    fun add(a: int, b: int) -> int { return a + b; }
+    __print add(1, 2, 3);
+
+Pass one argument for each parameter, in the order they are declared:
+
+    __print add(1, 2);",
+    ),
+    (
+        "E0227",
+        "The initialiser of a constant divides, or takes the remainder, by zero.
+Constants are worked out at compile time, so this would give a value that
+is not a number.
+
+Erroneous code example:
+
+    const R: int = 7 % 0;
+
+Divide by a value other than zero.",
+    ),
+];
+
+/// Returns the description of the diagnostic `code`, which is matched case
+/// insensitively.
+pub fn explain(code: &str) -> Option<&'static str> {
+    EXPLANATIONS
+        .iter()
+        .find(|(c, _)| c.eq_ignore_ascii_case(code))
+        .map(|(_, explanation)| *explanation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::{TextSpan, Token, TokenKind},
+        semantics::utils::Type,
+        utils::errors::{LexicalError, ParseError, SemanticError},
+    };
+    use rstest::rstest;
+    use std::{collections::HashSet, path::PathBuf};
+
+    #[rstest]
+    fn test_every_code_is_explained() {
+        let token = Token::new(TokenKind::Identifier, TextSpan::new(1, 1, 1, 2, "x"));
+        let span = TextSpan::new(1, 1, 1, 2, "x");
+
+        let codes = [
+            LexicalError::InvalidCharacter(span.clone()).code(),
+            ParseError::UnexpectedToken {
+                expected: TokenKind::Semicolon,
+                found: token.clone(),
+                source_file: PathBuf::new(),
+            }
+            .code(),
+            ParseError::UnexpectedTokenList {
+                source_file: PathBuf::new(),
+                found: token.clone(),
+                expected: vec![],
+            }
+            .code(),
+            ParseError::UnclosedBlock {
+                opening: token.clone(),
+            }
+            .code(),
+            ParseError::ChainedComparison {
+                source_file: PathBuf::new(),
+                first: token.span.lexeme.clone(),
+                second: token.clone(),
+            }
+            .code(),
+            SemanticError::UndefinedVariable(token.clone()).code(),
+            SemanticError::VariableRedeclaration(token.clone()).code(),
+            SemanticError::VariableShadowing(token.clone()).code(),
+            SemanticError::UndefinedFunction(token.clone()).code(),
+            SemanticError::FunctionAlreadyDefined(token.clone()).code(),
+            SemanticError::TypeMismatch(String::new(), Type::Int, Type::Int, span.clone()).code(),
+            SemanticError::TypeMismatchUnion(String::new(), Type::Int, vec![], span.clone()).code(),
+            SemanticError::InvalidOperation(token.clone()).code(),
+            SemanticError::InvalidCast(token.clone(), Type::Int, Type::Bool).code(),
+            SemanticError::FunctionReturnTypeMismatch(token.clone(), Type::Int, Type::Int).code(),
+            SemanticError::FunctionCallNoParams(token.clone(), vec![], span.clone()).code(),
+            SemanticError::VarUndefinedInFunc(token.clone()).code(),
+            SemanticError::ArrayOverflow(token.clone(), 3, 3, span.clone()).code(),
+            SemanticError::ArrayIndexNotInt(token.clone(), Type::Float).code(),
+            SemanticError::ArraySizeMismatch(token.clone(), 4, 3).code(),
+            SemanticError::ArrayIndexCount(token.clone(), 2, 1).code(),
+            SemanticError::UndefinedStruct(token.clone()).code(),
+            SemanticError::UnknownField(token.clone(), Type::Int).code(),
+            SemanticError::OutsideLoop(span.clone()).code(),
+            SemanticError::DuplicateMatchArm(String::new(), span.clone()).code(),
+            SemanticError::NotConstant(token.clone(), span.clone()).code(),
+            SemanticError::ConstantAssignment(token.clone()).code(),
+            SemanticError::InvalidArraySize(token.clone()).code(),
+            SemanticError::FunctionCallArity(token.clone(), 2, 3, span.clone()).code(),
+            SemanticError::ConstantDivisionByZero(token.clone(), span.clone()).code(),
+            SemanticError::MissingField(token.clone(), String::new()).code(),
+            SemanticError::DuplicateField(token).code(),
+        ];
+
+        assert_eq!(codes.iter().collect::<HashSet<_>>().len(), codes.len());
+        assert_eq!(codes.len(), EXPLANATIONS.len());
+
+        for code in codes {
+            assert!(explain(code).is_some(), "{code} is not explained");
+        }
+    }
+
+    #[rstest]
+    #[case("E0201", true)]
+    #[case("e0201", true)]
+    #[case("w0001", true)]
+    #[case("E9999", false)]
+    fn test_explain(#[case] code: &str, #[case] found: bool) {
+        assert_eq!(explain(code).is_some(), found);
+    }
+}
@@ -1,5 +1,7 @@
 pub mod buffer;
+pub mod diagnostics;
 pub mod errors;
+pub mod explanations;
 
 pub use buffer::SimpleBuffer;
 pub use buffer::Stream;
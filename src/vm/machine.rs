@@ -0,0 +1,1036 @@
+use std::{
+    collections::HashMap,
+    io::Write,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    generation::instructions::{Instruction, Program},
+    semantics::utils::MemoryLocation,
+    utils::{errors::RuntimeError, Result},
+};
+
+use super::pad::{ImageFormat, Pad};
+
+/// An in-process interpreter for PArIR programs.
+///
+/// Every value is stored as an `f64`, mirroring the reference VM, so integer
+/// division yields a float and colours are plain numbers.
+pub struct VirtualMachine<W: Write> {
+    /// The flattened instruction memory, functions first and `.main` last
+    instructions: Vec<Instruction>,
+    /// Maps every `.label` to its address in `instructions`
+    labels: HashMap<String, usize>,
+    /// The address of the instruction being executed
+    pc: usize,
+    /// The operand stack
+    operands: Vec<f64>,
+    /// The memory stack, the last frame being the innermost one
+    frames: Vec<Vec<f64>>,
+    /// Return address and frame depth saved by each `call`
+    calls: Vec<(usize, usize)>,
+    /// The headless pad that the drawing instructions render to
+    pad: Pad,
+    /// Where to dump a frame of the pad on every `delay`, if anywhere
+    frame_dump: Option<(PathBuf, ImageFormat)>,
+    /// The number of frames dumped so far
+    frame_count: usize,
+    /// State of the xorshift generator used by `irnd`
+    seed: u64,
+    /// Where `print` and `printa` write to
+    output: W,
+    halted: bool,
+}
+
+impl<W: Write> VirtualMachine<W> {
+    pub fn new(program: &Program, output: W) -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+
+        let mut vm = VirtualMachine {
+            instructions: Vec::new(),
+            labels: HashMap::new(),
+            pc: 0,
+            operands: Vec::new(),
+            frames: Vec::new(),
+            calls: Vec::new(),
+            pad: Pad::default(),
+            frame_dump: None,
+            frame_count: 0,
+            seed: seed | 1,
+            output,
+            halted: false,
+        };
+
+        vm.load_program(program);
+        vm
+    }
+
+    /// Replaces the instruction memory with `program`, keeping the frames,
+    /// the pad and everything else, so that the REPL can run one chunk after
+    /// another against the same globals.
+    pub fn load_program(&mut self, program: &Program) {
+        self.instructions = program.instructions().cloned().collect();
+
+        self.labels = self
+            .instructions
+            .iter()
+            .enumerate()
+            .filter_map(|(address, instruction)| match instruction {
+                Instruction::FunctionLabel(name) => Some((name.clone(), address)),
+                _ => None,
+            })
+            .collect();
+    }
+
+    /// Empties the operand stack, returning what was left on it, bottom first.
+    pub fn take_operands(&mut self) -> Vec<f64> {
+        std::mem::take(&mut self.operands)
+    }
+
+    /// Unwinds a program that stopped halfway through, e.g. on a runtime
+    /// error, back to the outermost frame.
+    pub fn recover(&mut self) {
+        self.operands.clear();
+        self.calls.clear();
+        self.frames.truncate(1);
+    }
+
+    pub fn output(&mut self) -> &mut W {
+        &mut self.output
+    }
+
+    /// Replaces the pad with a blank one of the given size.
+    pub fn with_pad_size(mut self, width: usize, height: usize) -> Self {
+        self.pad = Pad::new(width, height);
+        self
+    }
+
+    /// Dumps the pad as `frame_<n>.<ext>` inside `dir` on every `delay`.
+    pub fn with_frame_dump(mut self, dir: PathBuf, format: ImageFormat) -> Self {
+        self.frame_dump = Some((dir, format));
+        self
+    }
+
+    pub fn pad(&self) -> &Pad {
+        &self.pad
+    }
+
+    /// Executes the program from `.main` until it halts or runs off the end
+    /// of the instruction memory.
+    pub fn run(&mut self) -> Result<()> {
+        self.pc = self.label_address("main")?;
+        self.halted = false;
+
+        while !self.halted {
+            let instruction = match self.instructions.get(self.pc) {
+                Some(instruction) => instruction.clone(),
+                None => break,
+            };
+
+            self.execute(instruction)?;
+        }
+
+        self.output.flush()?;
+        Ok(())
+    }
+
+    fn label_address(&self, label: &str) -> Result<usize> {
+        self.labels
+            .get(label)
+            .copied()
+            .ok_or_else(|| RuntimeError::UndefinedLabel(label.to_string()).into())
+    }
+
+    fn push(&mut self, value: f64) {
+        self.operands.push(value);
+    }
+
+    fn pop(&mut self) -> Result<f64> {
+        self.operands
+            .pop()
+            .ok_or_else(|| RuntimeError::StackUnderflow(self.pc).into())
+    }
+
+    /// Pops a value that is used as a count, index or address.
+    fn pop_usize(&mut self) -> Result<usize> {
+        let value = self.pop()?;
+
+        if value < 0.0 || !value.is_finite() {
+            return Err(RuntimeError::InvalidOperand(self.pc, value).into());
+        }
+
+        Ok(value as usize)
+    }
+
+    fn pop_n(&mut self, n: usize) -> Result<Vec<f64>> {
+        (0..n).map(|_| self.pop()).collect()
+    }
+
+    fn frame(&mut self, level: usize) -> Result<&mut Vec<f64>> {
+        let depth = self.frames.len();
+
+        if level >= depth {
+            return Err(RuntimeError::FrameUnderflow(self.pc).into());
+        }
+
+        Ok(&mut self.frames[depth - 1 - level])
+    }
+
+    fn load(&mut self, index: usize, level: usize) -> Result<f64> {
+        let pc = self.pc;
+
+        self.frame(level)?
+            .get(index)
+            .copied()
+            .ok_or_else(|| RuntimeError::InvalidMemoryAccess(pc, index, level).into())
+    }
+
+    fn store(&mut self, index: usize, level: usize, value: f64) -> Result<()> {
+        let pc = self.pc;
+
+        match self.frame(level)?.get_mut(index) {
+            Some(slot) => {
+                *slot = value;
+                Ok(())
+            }
+            None => Err(RuntimeError::InvalidMemoryAccess(pc, index, level).into()),
+        }
+    }
+
+    fn jump(&mut self, address: f64) -> Result<()> {
+        if address < 0.0 || address as usize >= self.instructions.len() {
+            return Err(RuntimeError::InvalidJump(self.pc, address).into());
+        }
+
+        self.pc = address as usize;
+        Ok(())
+    }
+
+    fn random(&mut self) -> u64 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 7;
+        self.seed ^= self.seed << 17;
+        self.seed
+    }
+
+    fn dump_frame(&mut self) -> Result<()> {
+        if let Some((dir, format)) = &self.frame_dump {
+            let path = dir.join(format!(
+                "frame_{:04}.{}",
+                self.frame_count,
+                format.extension()
+            ));
+            self.pad.save(&path, *format)?;
+            self.frame_count += 1;
+        }
+
+        Ok(())
+    }
+
+    fn execute(&mut self, instruction: Instruction) -> Result<()> {
+        let mut next_pc = self.pc + 1;
+
+        match instruction {
+            Instruction::FunctionLabel(_) | Instruction::NoOperation => {}
+
+            Instruction::PushIntValue(value) => self.push(value as f64),
+
+            Instruction::PushFloatValue(value) => self.push(value),
+
+            Instruction::PushFunction(label) => {
+                let address = self.label_address(&label)?;
+                self.push(address as f64);
+            }
+
+            Instruction::PushOffsetFromPC(offset) => self.push(self.pc as f64 + offset as f64),
+
+            Instruction::PushFromStack(MemoryLocation {
+                stack_level,
+                frame_index,
+            }) => {
+                let value = self.load(frame_index, stack_level)?;
+                self.push(value);
+            }
+
+            Instruction::PushOffsetFromOpS(MemoryLocation {
+                stack_level,
+                frame_index,
+            }) => {
+                let offset = self.pop_usize()?;
+                let value = self.load(frame_index + offset, stack_level)?;
+                self.push(value);
+            }
+
+            Instruction::PushArray(MemoryLocation {
+                stack_level,
+                frame_index,
+            }) => {
+                let count = self.pop_usize()?;
+                for offset in 0..count {
+                    let value = self.load(frame_index + offset, stack_level)?;
+                    self.push(value);
+                }
+            }
+
+            Instruction::Store => {
+                let level = self.pop_usize()?;
+                let index = self.pop_usize()?;
+                let value = self.pop()?;
+                self.store(index, level, value)?;
+            }
+
+            Instruction::StoreArray => {
+                let level = self.pop_usize()?;
+                let index = self.pop_usize()?;
+                let count = self.pop_usize()?;
+                for (offset, value) in self.pop_n(count)?.into_iter().enumerate() {
+                    self.store(index + offset, level, value)?;
+                }
+            }
+
+            Instruction::Add
+            | Instruction::Sub
+            | Instruction::Mul
+            | Instruction::Div
+            | Instruction::Mod
+            | Instruction::And
+            | Instruction::Or
+            | Instruction::LessThan
+            | Instruction::GreaterThan
+            | Instruction::LessThanOrEqual
+            | Instruction::GreaterThanOrEqual
+            | Instruction::Equal => {
+                let a = self.pop()?;
+                let b = self.pop()?;
+
+                let result = match instruction {
+                    Instruction::Add => a + b,
+                    Instruction::Sub => a - b,
+                    Instruction::Mul => a * b,
+                    Instruction::Div => a / b,
+                    Instruction::Mod => a % b,
+                    Instruction::And => (a != 0.0 && b != 0.0) as u8 as f64,
+                    Instruction::Or => (a != 0.0 || b != 0.0) as u8 as f64,
+                    Instruction::LessThan => (a < b) as u8 as f64,
+                    Instruction::GreaterThan => (a > b) as u8 as f64,
+                    Instruction::LessThanOrEqual => (a <= b) as u8 as f64,
+                    Instruction::GreaterThanOrEqual => (a >= b) as u8 as f64,
+                    Instruction::Equal => (a == b) as u8 as f64,
+                    _ => unreachable!(),
+                };
+
+                self.push(result);
+            }
+
+            Instruction::Not => {
+                let a = self.pop()?;
+                self.push((a == 0.0) as u8 as f64);
+            }
+
+            Instruction::RandInt => {
+                let upper_bound = self.pop_usize()?;
+                let value = match upper_bound {
+                    0 => 0,
+                    _ => self.random() % upper_bound as u64,
+                };
+                self.push(value as f64);
+            }
+
+            Instruction::Jump => {
+                let address = self.pop()?;
+                self.jump(address)?;
+                next_pc = self.pc;
+            }
+
+            Instruction::JumpIfNotZero => {
+                let address = self.pop()?;
+                let condition = self.pop()?;
+                if condition != 0.0 {
+                    self.jump(address)?;
+                    next_pc = self.pc;
+                }
+            }
+
+            Instruction::Call => {
+                let address = self.pop()?;
+                let count = self.pop_usize()?;
+                let args = self.pop_n(count)?;
+
+                self.calls.push((self.pc + 1, self.frames.len()));
+                self.frames.push(args);
+                self.jump(address)?;
+                next_pc = self.pc;
+            }
+
+            Instruction::Return => {
+                let (return_address, depth) = self
+                    .calls
+                    .pop()
+                    .ok_or(RuntimeError::ReturnOutsideCall(self.pc))?;
+
+                // Closes the call frame along with any block frames that were
+                // opened inside the function body.
+                self.frames.truncate(depth);
+                next_pc = return_address;
+            }
+
+            Instruction::Halt => self.halted = true,
+
+            Instruction::NewFrame => {
+                let size = self.pop_usize()?;
+                self.frames.push(vec![0.0; size]);
+            }
+
+            Instruction::PopFrame => {
+                if self.frames.pop().is_none() {
+                    return Err(RuntimeError::FrameUnderflow(self.pc).into());
+                }
+            }
+
+            Instruction::Alloc => {
+                let size = self.pop_usize()?;
+                let frame = self.frame(0)?;
+                frame.resize(frame.len() + size, 0.0);
+            }
+
+            Instruction::Delay => {
+                self.pop()?;
+                self.dump_frame()?;
+            }
+
+            Instruction::Write => {
+                let x = self.pop()?;
+                let y = self.pop()?;
+                let colour = self.pop()?;
+                self.pad.write(x, y, colour);
+            }
+
+            Instruction::WriteBox => {
+                let x = self.pop()?;
+                let y = self.pop()?;
+                let w = self.pop()?;
+                let h = self.pop()?;
+                let colour = self.pop()?;
+                self.pad.write_box(x, y, w, h, colour);
+            }
+
+            Instruction::Clear => {
+                let colour = self.pop()?;
+                self.pad.clear(colour);
+            }
+
+            Instruction::Read => {
+                let x = self.pop()?;
+                let y = self.pop()?;
+                self.push(self.pad.read(x, y));
+            }
+
+            Instruction::Width => self.push(self.pad.width() as f64),
+
+            Instruction::Height => self.push(self.pad.height() as f64),
+
+            Instruction::Print => {
+                let value = self.pop()?;
+                writeln!(self.output, "{}", value)?;
+            }
+
+            Instruction::PrintArray => {
+                let count = self.pop_usize()?;
+                for value in self.pop_n(count)? {
+                    writeln!(self.output, "{}", value)?;
+                }
+            }
+        }
+
+        self.pc = next_pc;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{generation::PArIRWriter, lexing::Lexer, parsing::Parser, utils::SimpleBuffer};
+
+    use super::*;
+    use rstest::rstest;
+    use std::path::Path;
+
+    fn compile(input: &str) -> Program {
+        compile_with(input, PArIRWriter::new())
+    }
+
+    fn compile_with(input: &str, mut writer: PArIRWriter) -> Program {
+        let mut lexer: Lexer<SimpleBuffer> = Lexer::new(input, Path::new(""), None);
+        let tokens = lexer.lex().unwrap();
+
+        let mut parser = Parser::new(&tokens, Path::new(""));
+        let ast = parser.parse().unwrap();
+
+        writer.get_program(ast).clone()
+    }
+
+    fn run_program(input: &str) -> Vec<String> {
+        run_compiled(&compile(input))
+    }
+
+    fn run_compiled(program: &Program) -> Vec<String> {
+        let mut output = Vec::new();
+        VirtualMachine::new(program, &mut output).run().unwrap();
+
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[rstest]
+    fn test_variable_declaration() {
+        let input = r#"
+            let a: int = 5;
+            let b: colour = #000000;
+            let c: float = 1.23;
+            let d: int[5] = [1, 2, 3, 4, 5];
+
+            __print a;
+            __print b;
+            __print c;
+            __print d;
+
+            {
+                let a: int = 1;
+                __print a;
+            }
+
+            __print a;
+        "#;
+
+        assert_eq!(
+            run_program(input),
+            ["5", "0", "1.23", "1", "2", "3", "4", "5", "1", "5"]
+        );
+    }
+
+    #[rstest]
+    fn test_arrays() {
+        let input = r#"
+            let a: int[] = [1, 2, 3];
+            let z: float[4] = [0.5];
+
+            __print a;
+            __print z;
+            __print a[2];
+        "#;
+
+        assert_eq!(
+            run_program(input),
+            ["1", "2", "3", "0.5", "0.5", "0.5", "0.5", "3"]
+        );
+    }
+
+    #[rstest]
+    fn test_array_initialiser_expressions() {
+        let input = r#"
+            fun f(x: int) -> int {
+                __print x;
+                return x * 10;
+            }
+
+            let x: int = 2;
+            let a: int[] = [x, x * 2, f(1), f(2)];
+            __print a;
+
+            let b: int[3] = [f(3)];
+            __print b;
+        "#;
+
+        assert_eq!(
+            run_program(input),
+            ["1", "2", "2", "4", "10", "20", "3", "30", "30", "30"]
+        );
+    }
+
+    #[rstest]
+    fn test_multi_dimensional_arrays() {
+        let input = r#"
+            fun corner(g: int[2][3]) -> int {
+                return g[1][2];
+            }
+
+            let g: int[2][3] = [[1, 2, 3], [4, 5, 6]];
+            __print g[1][0];
+
+            let y: int = 0;
+            let x: int = 2;
+            g[y][x] = 7;
+            __print g;
+            __print corner(g);
+
+            let h: int[][2] = [[1, 2], 0, [3, 4]];
+            __print h;
+        "#;
+
+        assert_eq!(
+            run_program(input),
+            ["4", "1", "2", "7", "4", "5", "6", "6", "1", "2", "0", "0", "3", "4"]
+        );
+    }
+
+    #[rstest]
+    #[case::subtraction("10 - 3 - 2", "5")]
+    #[case::division("8.0 / 4.0 / 2.0", "1")]
+    #[case::mixed("1 + 2 * 3 - 4 % 3", "6")]
+    #[case::double_negation("--5", "5")]
+    #[case::minus_negative("7--2", "9")]
+    #[case::double_not("not not true", "1")]
+    #[case::not_equal("1 != 2", "1")]
+    #[case::equal_not_equal("2 != 2", "0")]
+    #[case::bool_not_equal("true != false", "1")]
+    #[case::float_not_equal("1.5 != 1.5", "0")]
+    #[case::comparisons("1 < 2 == 3 < 4", "1")]
+    fn test_arithmetic(#[case] expression: &str, #[case] expected: &str) {
+        let input = format!("__print {};", expression);
+
+        assert_eq!(run_program(&input), [expected]);
+    }
+
+    #[rstest]
+    fn test_logical_operators() {
+        let input = r#"
+            fun loud(x: bool) -> bool {
+                __print 7;
+                return x;
+            }
+
+            __print false and true or true;
+            __print true or true and false;
+            __print false and loud(true);
+            __print true or loud(true);
+            __print true and loud(false);
+            __print false or loud(true);
+
+            let a: int[3] = [1, 2, 3];
+            let i: int = 3;
+            __print i < 3 and a[i] > 0;
+        "#;
+
+        let program = compile_with(input, PArIRWriter::new().with_bounds_checks(true));
+        assert_eq!(
+            run_compiled(&program),
+            ["1", "1", "0", "1", "7", "0", "7", "1", "0"]
+        );
+    }
+
+    #[rstest]
+    fn test_compound_assignment() {
+        let input = r#"
+            struct P { x: int }
+
+            let a: int[4] = [1, 2, 3, 4];
+            for (let i: int = 0; i < 4; i++) {
+                a[i] *= 10;
+                a[i] += i;
+            }
+            __print a;
+
+            let p: P = P { x: 5 };
+            p.x -= 2;
+            __print p.x;
+
+            let f: float = 1.0;
+            f /= 4;
+            __print f;
+
+            let n: int = 7;
+            n %= 4;
+            n--;
+            n++;
+            n++;
+            __print n;
+
+            let g: int[2][2] = [0];
+            g[1][n - 3] += 9;
+            __print g;
+        "#;
+
+        assert_eq!(
+            run_program(input),
+            ["10", "21", "32", "43", "3", "0.25", "4", "0", "0", "0", "9"]
+        );
+    }
+
+    #[rstest]
+    fn test_else_if() {
+        let input = r#"
+            fun sign(x: int) -> int {
+                let s: int = 1;
+                if (x < 0) {
+                    s = 0 - 1;
+                } else if (x == 0) {
+                    s = 0;
+                }
+                return s;
+            }
+
+            __print sign(0 - 5);
+            __print sign(0);
+            __print sign(5);
+        "#;
+
+        assert_eq!(run_program(input), ["-1", "0", "1"]);
+    }
+
+    #[rstest]
+    fn test_match() {
+        let input = r#"
+            for (let i: int = -2; i < 4; i = i + 1) {
+                match (i) {
+                    -1 => {
+                        __print 100;
+                    }
+                    0 => {
+                        let zero: int = i;
+                        __print zero;
+                    }
+                    2 => {
+                        continue;
+                    }
+                    _ => {
+                        __print i * 10;
+                    }
+                }
+            }
+
+            let c: colour = #00ff00;
+            match (c) {
+                #ff0000 => {
+                    __print 1;
+                }
+                #00FF00 => {
+                    __print 2;
+                }
+            }
+            match (c) {
+                #0000ff => {
+                    __print 3;
+                }
+            }
+            __print 42;
+        "#;
+
+        assert_eq!(
+            run_program(input),
+            ["-20", "100", "0", "10", "30", "2", "42"]
+        );
+    }
+
+    #[rstest]
+    fn test_constants() {
+        let input = r#"
+            const W: int = 4;
+            const H: int = W * 2 - 1;
+            const HALF: float = W / 2;
+            const NEG: int = -W;
+            const T: int = 7.9 as int;
+            const BIG: bool = H > 5 and not false;
+
+            fun last(a: int[W]) -> int {
+                return a[W - 1] * H;
+            }
+
+            let a: int[W] = [3];
+            __print last(a);
+            __print HALF;
+            __print NEG;
+            __print T;
+            __print 7.9 as int;
+            __print if BIG then 1 else 0;
+        "#;
+
+        // A cast only changes the type, whether it is folded or not
+        assert_eq!(run_program(input), ["21", "2", "-4", "7.9", "7.9", "1"]);
+    }
+
+    #[rstest]
+    fn test_conditional() {
+        let input = r#"
+            fun pick(x: int) -> colour {
+                return if x < 0 then #ff0000 else if x == 0 then #00ff00 else #0000ff;
+            }
+
+            __print pick(-1);
+            __print pick(0);
+            __print pick(1);
+
+            __print (if false then 1 else 2) * 3;
+            __print if 2 > 1 then 1.5 else 2.5 as float;
+        "#;
+
+        assert_eq!(run_program(input), ["16711680", "65280", "255", "6", "1.5"]);
+    }
+
+    #[rstest]
+    fn test_break_and_continue() {
+        let input = r#"
+            for (let i: int = 0; i < 10; i = i + 1) {
+                if (i == 1) {
+                    continue;
+                }
+                if (i == 3) {
+                    {
+                        let last: int = i;
+                        break;
+                    }
+                }
+                __print i;
+            }
+
+            let j: int = 0;
+            while (true) {
+                j = j + 1;
+                if (j < 3) {
+                    continue;
+                }
+                for (let k: int = 1; k < 10; k = k + 1) {
+                    if (k > 2) {
+                        break;
+                    }
+                    __print k * 10;
+                }
+                if (j == 4) {
+                    break;
+                }
+                __print j;
+            }
+            __print j;
+        "#;
+
+        assert_eq!(
+            run_program(input),
+            ["0", "2", "10", "20", "3", "10", "20", "4"]
+        );
+    }
+
+    #[rstest]
+    fn test_declarations_after_scopes() {
+        let input = r#"
+            let a: int = 1;
+            fun f() -> int {
+                let z: int = 9;
+                return z;
+            }
+            let b: int = 2;
+            for (let i: int = 0; i < 1; i = i + 1) {}
+            {
+                let c: int = 3;
+            }
+            let d: int = 4;
+            __print a;
+            __print b;
+            __print d;
+            __print f();
+        "#;
+
+        assert_eq!(run_program(input), ["1", "2", "4", "9"]);
+    }
+
+    #[rstest]
+    fn test_structs() {
+        let input = r#"
+            struct Point { x: int, y: int }
+            struct Rect { min: Point, max: Point }
+
+            fun area(r: Rect) -> int {
+                return (r.max.x - r.min.x) * (r.max.y - r.min.y);
+            }
+
+            fun origin() -> Point {
+                return Point { x: 0, y: 0 };
+            }
+
+            let p: Point = Point { y: 4, x: 3 };
+            __print p.y;
+            p.x = 10;
+            __print p;
+
+            let r: Rect = Rect { min: origin(), max: p };
+            __print area(r);
+            r.min.y = 2;
+            __print r;
+
+            let q: Point = r.max;
+            q = origin();
+            __print q;
+        "#;
+
+        assert_eq!(
+            run_program(input),
+            ["4", "10", "4", "40", "0", "2", "10", "4", "0", "0"]
+        );
+    }
+
+    #[rstest]
+    fn test_struct_fields_are_evaluated_in_source_order() {
+        let input = r#"
+            struct Point { x: int, y: int }
+            struct Line { start: Point, end: Point }
+
+            fun say(n: int) -> int {
+                __print n;
+                return n;
+            }
+
+            let p: Point = Point { x: say(1), y: say(2) };
+            let q: Point = Point { y: say(3), x: say(4) };
+            let l: Line = Line { end: Point { x: say(5), y: say(6) }, start: p };
+            __print p;
+            __print q;
+            __print l;
+        "#;
+
+        assert_eq!(
+            run_program(input),
+            ["1", "2", "3", "4", "5", "6", "1", "2", "4", "3", "1", "2", "5", "6"]
+        );
+    }
+
+    #[rstest]
+    #[case::in_range("let i: int = 2;", &["3", "6", "42"])]
+    #[case::past_the_end("let i: int = 3;", &["57005"])]
+    #[case::negative("let i: int = 0 - 1;", &["57005"])]
+    fn test_bounds_checks(#[case] index: &str, #[case] expected: &[&str]) {
+        let input = format!(
+            r#"
+            let a: int[3] = [1, 2, 3];
+            let g: int[2][3] = [0];
+            {}
+            __print a[i];
+            g[1][i] = 6;
+            __print g[1][i];
+            __print 42;
+        "#,
+            index
+        );
+        let writer = PArIRWriter::new().with_bounds_checks(true);
+
+        assert_eq!(run_compiled(&compile_with(&input, writer)), expected);
+    }
+
+    #[rstest]
+    fn test_control_flow() {
+        let input = r#"
+            let loop_max: int = 3;
+
+            for (let i: int = 0; i < loop_max; i = i + 1) {
+                __print i;
+            }
+
+            if (1 < 2) {
+                __print 1;
+            } else {
+                __print 0;
+            }
+
+            let i: int = 0;
+            while (i < loop_max) {
+                __print i;
+                i = i + 1;
+            }
+        "#;
+
+        assert_eq!(run_program(input), ["0", "1", "2", "1", "0", "1", "2"]);
+    }
+
+    #[rstest]
+    fn test_functions() {
+        let input = r#"
+            fun add(a: int, b: int) -> int {
+                return a + b;
+            }
+
+            __print add(5, 10);
+
+            fun sum(a: int[4]) -> int {
+                let total: int = 0;
+                for (let i: int = 0; i < 4; i = i + 1) {
+                    total = total + a[i];
+                }
+                return total;
+            }
+
+            let arr: int[4] = [5, 1, 2, 3];
+            __print sum(arr);
+        "#;
+
+        assert_eq!(run_program(input), ["15", "11"]);
+    }
+
+    #[rstest]
+    fn test_expressions() {
+        let input = r#"
+            let a: int = 5;
+            let b: int = 10;
+
+            __print (a + b) == 20;
+            __print ((((a + b) * 2) / 3) - 1) == 9;
+            __print ((12 + 5) * (8 - 3) + (18 / 2)) - ((25 - 5) / (4 + 1));
+        "#;
+
+        assert_eq!(run_program(input), ["0", "1", "90"]);
+    }
+
+    #[rstest]
+    fn test_pad() {
+        let input = r#"
+            __write 5, 10, #213b5f;
+            __print __read 5, 10;
+
+            __write_box 5, 10, 20, 20, #ffa400;
+            __print __read 8, 13;
+            __print __width;
+        "#;
+
+        assert_eq!(
+            run_program(input),
+            [
+                0x213b5f.to_string(),
+                0xffa400.to_string(),
+                Pad::default().width().to_string()
+            ]
+        );
+    }
+
+    #[rstest]
+    fn test_flags() {
+        let program = compile(include_str!("../../samples/flags_good.parl"));
+        let frames = std::env::temp_dir().join(format!("parlc-flags-{}", std::process::id()));
+        std::fs::create_dir_all(&frames).unwrap();
+
+        let mut vm = VirtualMachine::new(&program, std::io::sink())
+            .with_pad_size(240, 132)
+            .with_frame_dump(frames.clone(), ImageFormat::Ppm);
+        vm.run().unwrap();
+
+        // The Polish flag is on the pad when the program hits `__delay`
+        let polish = std::fs::read(frames.join("frame_0000.ppm")).unwrap();
+        let header = b"P6\n240 132\n255\n".len();
+        let pixel = |x: usize, y: usize| &polish[header + (y * 240 + x) * 3..][..3];
+        assert_eq!(pixel(0, 0), [0xff, 0x00, 0x00]);
+        assert_eq!(pixel(239, 65), [0xff, 0x00, 0x00]);
+        assert_eq!(pixel(0, 66), [0xff, 0xff, 0xff]);
+        assert!(!frames.join("frame_0001.ppm").exists());
+        std::fs::remove_dir_all(&frames).unwrap();
+
+        // The Italian flag is drawn over it by the end of the program
+        let pad = vm.pad();
+        assert_eq!(pad.rgb(0, 0), [0x00, 0x92, 0x46]);
+        assert_eq!(pad.rgb(79, 131), [0x00, 0x92, 0x46]);
+        assert_eq!(pad.rgb(80, 0), [0xff, 0xff, 0xff]);
+        assert_eq!(pad.rgb(160, 131), [0xce, 0x2b, 0x37]);
+        assert_eq!(pad.rgb(239, 0), [0xce, 0x2b, 0x37]);
+    }
+}
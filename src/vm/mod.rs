@@ -0,0 +1,5 @@
+mod machine;
+mod pad;
+
+pub use machine::VirtualMachine;
+pub use pad::ImageFormat;
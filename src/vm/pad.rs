@@ -0,0 +1,270 @@
+use std::{fs::File, io::Write, path::Path};
+
+use crate::utils::Result;
+
+pub const DEFAULT_WIDTH: usize = 36;
+pub const DEFAULT_HEIGHT: usize = 36;
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// The image formats a [`Pad`] can be dumped as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ImageFormat {
+    Ppm,
+    Png,
+}
+
+impl ImageFormat {
+    /// Guesses the format from the extension of `path`.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "ppm" => Some(ImageFormat::Ppm),
+            "png" => Some(ImageFormat::Png),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Ppm => "ppm",
+            ImageFormat::Png => "png",
+        }
+    }
+}
+
+/// A headless framebuffer that the VM draws into.
+///
+/// Pixels hold whatever value was written to them, so `__read` gives back
+/// exactly what `__write` stored, even if it is not a valid colour.
+#[derive(Debug, Clone)]
+pub struct Pad {
+    width: usize,
+    height: usize,
+    /// The pixels, stored row by row from the top left corner
+    pixels: Vec<f64>,
+}
+
+impl Pad {
+    pub fn new(width: usize, height: usize) -> Self {
+        Pad {
+            width,
+            height,
+            pixels: vec![0.0; width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    fn index(&self, x: f64, y: f64) -> Option<usize> {
+        if x < 0.0 || y < 0.0 || x as usize >= self.width || y as usize >= self.height {
+            return None;
+        }
+
+        Some(y as usize * self.width + x as usize)
+    }
+
+    /// Returns the value at `(x, y)`, or 0 if it lies outside the pad.
+    pub fn read(&self, x: f64, y: f64) -> f64 {
+        self.index(x, y).map(|i| self.pixels[i]).unwrap_or(0.0)
+    }
+
+    pub fn write(&mut self, x: f64, y: f64, colour: f64) {
+        if let Some(i) = self.index(x, y) {
+            self.pixels[i] = colour;
+        }
+    }
+
+    /// Fills a `w` by `h` box whose top left corner is at `(x, y)`, clipping
+    /// whatever falls outside the pad.
+    pub fn write_box(&mut self, x: f64, y: f64, w: f64, h: f64, colour: f64) {
+        let (x, y) = (x.max(0.0) as usize, y.max(0.0) as usize);
+        let (w, h) = (w.max(0.0) as usize, h.max(0.0) as usize);
+
+        for row in y..(y + h).min(self.height) {
+            for col in x..(x + w).min(self.width) {
+                self.pixels[row * self.width + col] = colour;
+            }
+        }
+    }
+
+    pub fn clear(&mut self, colour: f64) {
+        self.pixels.fill(colour);
+    }
+
+    /// Returns the pixel at `(x, y)` as an `[r, g, b]` triple.
+    #[cfg(test)]
+    pub fn rgb(&self, x: usize, y: usize) -> [u8; 3] {
+        to_rgb(self.read(x as f64, y as f64))
+    }
+
+    pub fn save(&self, path: &Path, format: ImageFormat) -> Result<()> {
+        let bytes = match format {
+            ImageFormat::Ppm => self.to_ppm(),
+            ImageFormat::Png => self.to_png(),
+        };
+
+        File::create(path)?.write_all(&bytes)?;
+        Ok(())
+    }
+
+    /// Encodes the pad as a binary (P6) PPM image.
+    pub fn to_ppm(&self) -> Vec<u8> {
+        let mut ppm = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+
+        for pixel in &self.pixels {
+            ppm.extend(to_rgb(*pixel));
+        }
+
+        ppm
+    }
+
+    /// Encodes the pad as an 8-bit RGB PNG image. The image data is stored
+    /// uncompressed, which keeps the encoder free of any dependencies.
+    pub fn to_png(&self) -> Vec<u8> {
+        let mut scanlines = Vec::with_capacity((self.width * 3 + 1) * self.height);
+
+        for row in self.pixels.chunks(self.width.max(1)) {
+            // Every scanline starts with its filter type, 0 being no filter
+            scanlines.push(0);
+            for pixel in row {
+                scanlines.extend(to_rgb(*pixel));
+            }
+        }
+
+        let mut header = Vec::with_capacity(13);
+        header.extend((self.width as u32).to_be_bytes());
+        header.extend((self.height as u32).to_be_bytes());
+        // Bit depth, colour type (RGB), compression, filter and interlace
+        header.extend([8, 2, 0, 0, 0]);
+
+        let mut png = PNG_SIGNATURE.to_vec();
+        write_png_chunk(&mut png, b"IHDR", &header);
+        write_png_chunk(&mut png, b"IDAT", &zlib_stored(&scanlines));
+        write_png_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+}
+
+impl Default for Pad {
+    fn default() -> Self {
+        Pad::new(DEFAULT_WIDTH, DEFAULT_HEIGHT)
+    }
+}
+
+fn to_rgb(colour: f64) -> [u8; 3] {
+    let colour = colour.clamp(0.0, 0xffffff as f64) as u32;
+    let [_, r, g, b] = colour.to_be_bytes();
+    [r, g, b]
+}
+
+fn write_png_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend((data.len() as u32).to_be_bytes());
+
+    let start = png.len();
+    png.extend(kind);
+    png.extend(data);
+
+    let crc = crc32(&png[start..]);
+    png.extend(crc.to_be_bytes());
+}
+
+/// Wraps `data` in a zlib stream made up of uncompressed deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    const MAX_BLOCK: usize = u16::MAX as usize;
+
+    let mut stream = vec![0x78, 0x01];
+    let mut blocks = data.chunks(MAX_BLOCK).peekable();
+
+    if blocks.peek().is_none() {
+        stream.extend([1, 0, 0, 0xff, 0xff]);
+    }
+
+    while let Some(block) = blocks.next() {
+        let is_final = blocks.peek().is_none();
+        let len = block.len() as u16;
+
+        stream.push(is_final as u8);
+        stream.extend(len.to_le_bytes());
+        stream.extend((!len).to_le_bytes());
+        stream.extend(block);
+    }
+
+    stream.extend(adler32(data).to_be_bytes());
+    stream
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffffffffu32;
+
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = match crc & 1 {
+                1 => (crc >> 1) ^ 0xedb88320,
+                _ => crc >> 1,
+            };
+        }
+    }
+
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    fn test_write_box_clips() {
+        let mut pad = Pad::new(4, 3);
+
+        pad.write_box(2.0, 1.0, 10.0, 10.0, 0xff0000 as f64);
+
+        assert_eq!(pad.rgb(1, 1), [0, 0, 0]);
+        assert_eq!(pad.rgb(3, 2), [0xff, 0, 0]);
+        assert_eq!(pad.read(4.0, 2.0), 0.0);
+    }
+
+    #[rstest]
+    fn test_ppm() {
+        let mut pad = Pad::new(2, 1);
+        pad.write(1.0, 0.0, 0x00ff80 as f64);
+
+        assert_eq!(pad.to_ppm(), b"P6\n2 1\n255\n\x00\x00\x00\x00\xff\x80");
+    }
+
+    #[rstest]
+    fn test_png() {
+        let png = Pad::new(3, 2).to_png();
+
+        assert!(png.starts_with(PNG_SIGNATURE));
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(&png[16..24], [0, 0, 0, 3, 0, 0, 0, 2]);
+        assert!(png.ends_with(&[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]));
+    }
+
+    #[rstest]
+    #[case(b"", 0x00000000)]
+    #[case(b"IEND", 0xae426082)]
+    #[case(b"123456789", 0xcbf43926)]
+    fn test_crc32(#[case] data: &[u8], #[case] expected: u32) {
+        assert_eq!(crc32(data), expected);
+    }
+}
@@ -4,11 +4,8 @@ import os
 import subprocess
 from time import sleep
 
-from playwright.sync_api import sync_playwright
-
 
 class Runner:
-    vm_url = "http://16.170.124.96:3001/"
     source_path: str
     source: str
 
@@ -89,30 +86,30 @@ class Runner:
 
     def compile_and_run(self) -> tuple[str, list[str]]:
         """
-        Compile and run the program on the VM.
+        Compile and run the program on the built-in VM.
 
-        Returns a list containing the lines outputted by the log area in the VM.
+        Returns the compiled program along with the lines printed by the VM.
         """
         if not self.source and not self.source_path:
             raise ValueError("Either source or source_path must be provided.")
 
         parir = self.compile()
 
-        with sync_playwright() as p:
-            browser = p.chromium.launch(headless=True)
-            page = browser.new_page()
-            page.goto(self.vm_url)
-            element = page.query_selector("#pad_program")
-            element.fill(parir)
-            element = page.query_selector("#RunBtn")
-            element.click()
+        # `compile` removes the temporary source file once it is done with it
+        if self.source:
+            with open(self.source_path, "w") as f:
+                f.write(self.source)
+
+        vm = subprocess.run(
+            ["cargo", "run", "run", self.source_path],
+            stdout=subprocess.PIPE,
+            stderr=subprocess.PIPE,
+        )
 
-            sleep(0.5)
+        if self.source and os.path.exists(self.source_path):
+            os.remove(self.source_path)
 
-            logs_area = page.query_selector("#logsarea")
+        if vm.returncode != 0:
+            print(vm.stderr.decode())
 
-            return parir, list(
-                map(
-                    lambda x: x.split("-- ")[1], logs_area.inner_text().splitlines()[1:]
-                )
-            )
+        return parir, vm.stdout.decode().splitlines()